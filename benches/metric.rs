@@ -3,16 +3,19 @@ use criterion::{
     Criterion,
 };
 use opentelemetry::{
-    metrics::{Descriptor, Meter},
+    metrics::{Descriptor, Meter, MeterProvider},
     sdk::{
-        export::metrics::{AggregatorSelector, Processor},
-        metrics::{accumulator, aggregators},
+        export::metrics::{AggregatorSelector, ExportKind, Processor},
+        metrics::{accumulator, aggregators, controllers},
     },
     Key, KeyValue,
 };
 use rand::{rngs, Rng};
 use std::cell::RefCell;
-use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Barrier};
+use std::thread;
+use std::time::{Duration, Instant};
 
 pub fn counters(c: &mut Criterion) {
     let meter = build_meter();
@@ -55,6 +58,75 @@ pub fn counters(c: &mut Criterion) {
     g.finish();
 }
 
+pub fn value_recorders(c: &mut Criterion) {
+    let mut controller = controllers::pull(
+        Box::new(BenchAggregatorSelector),
+        Box::new(ExportKind::Cumulative),
+    )
+    .with_cache_period(Duration::from_secs(0))
+    .build();
+    let meter = controller.provider().meter("benches");
+    let labels: &'static [KeyValue] = Box::leak(build_kv(1).into_boxed_slice());
+
+    // Collect continuously, so that recording races with checkpoints.
+    let collecting = Arc::new(AtomicBool::new(true));
+    let collector = {
+        let collecting = collecting.clone();
+        thread::spawn(move || {
+            while collecting.load(Ordering::Relaxed) {
+                controller.collect().unwrap();
+            }
+        })
+    };
+
+    let mut g = c.benchmark_group("ValueRecorder");
+
+    for aggregator in &["minmaxsumcount", "lastvalue", "histogram"] {
+        let recorder = meter
+            .f64_value_recorder(format!("f64_bound.{}", aggregator))
+            .init()
+            .bind(labels);
+
+        // bound f64, recorded concurrently from each number of threads
+        for threads in [1, 4, 16].iter() {
+            g.bench_with_input(
+                BenchmarkId::new(format!("{}_threads", aggregator), threads),
+                threads,
+                |b, &threads| {
+                    b.iter_custom(|iters| {
+                        // Time the recording only, from once all threads are
+                        // started.
+                        let barrier = Arc::new(Barrier::new(threads as usize));
+                        let workers = (0..threads)
+                            .map(|_| {
+                                let recorder = recorder.clone();
+                                let barrier = barrier.clone();
+                                thread::spawn(move || {
+                                    barrier.wait();
+                                    let start = Instant::now();
+                                    for _ in 0..iters / threads {
+                                        recorder.record(1.0);
+                                    }
+                                    start.elapsed()
+                                })
+                            })
+                            .collect::<Vec<_>>();
+                        workers
+                            .into_iter()
+                            .map(|worker| worker.join().unwrap())
+                            .max()
+                            .unwrap_or_default()
+                    })
+                },
+            );
+        }
+    }
+
+    g.finish();
+    collecting.store(false, Ordering::Relaxed);
+    collector.join().unwrap();
+}
+
 fn benchmark_unbound_metric<M: Measurement, F: Fn(&[KeyValue])>(
     name: &str,
     g: &mut BenchmarkGroup<M>,
@@ -134,5 +206,5 @@ fn build_meter() -> Meter {
     Meter::new("benches", Arc::new(core))
 }
 
-criterion_group!(benches, counters);
+criterion_group!(benches, counters, value_recorders);
 criterion_main!(benches);
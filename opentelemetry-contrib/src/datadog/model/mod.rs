@@ -30,6 +30,8 @@ impl From<rmp::encode::ValueWriteError> for Error {
 pub enum ApiVersion {
     /// Version 0.3
     Version03,
+    /// Version 0.4 - the version 0.3 payload with a `metrics` map on every span
+    Version04,
     /// Version 0.5 - requires datadog-agent v7.22.0 or above
     Version05,
 }
@@ -38,6 +40,7 @@ impl ApiVersion {
     pub(crate) fn path(self) -> &'static str {
         match self {
             ApiVersion::Version03 => "/v0.3/traces",
+            ApiVersion::Version04 => "/v0.4/traces",
             ApiVersion::Version05 => "/v0.5/traces",
         }
     }
@@ -45,6 +48,7 @@ impl ApiVersion {
     pub(crate) fn content_type(self) -> &'static str {
         match self {
             ApiVersion::Version03 => "application/msgpack",
+            ApiVersion::Version04 => "application/msgpack",
             ApiVersion::Version05 => "application/msgpack",
         }
     }
@@ -55,7 +59,8 @@ impl ApiVersion {
         spans: Vec<trace::SpanData>,
     ) -> Result<Vec<u8>, Error> {
         match self {
-            Self::Version03 => v03::encode(service_name, spans),
+            Self::Version03 => v03::encode(service_name, spans, false),
+            Self::Version04 => v03::encode(service_name, spans, true),
             Self::Version05 => v05::encode(service_name, spans),
         }
     }
@@ -115,23 +120,114 @@ mod tests {
         vec![span_data]
     }
 
-    #[test]
-    fn test_encode_v03() -> Result<(), Box<dyn std::error::Error>> {
-        let spans = get_spans();
-        let encoded = base64::encode(ApiVersion::Version03.encode("service_name", spans)?);
+    // Payloads of the spans of `get_spans`. The v0.3 and v0.5 ones are those the
+    // encoders were first tested against, the v0.4 one is the v0.3 one with an
+    // empty `metrics` map on the span.
+    const PAYLOADS: [(ApiVersion, &[u8]); 3] = [
+        (
+            ApiVersion::Version03,
+            include_bytes!("fixtures/v03.msgpack"),
+        ),
+        (
+            ApiVersion::Version04,
+            include_bytes!("fixtures/v04.msgpack"),
+        ),
+        (
+            ApiVersion::Version05,
+            include_bytes!("fixtures/v05.msgpack"),
+        ),
+    ];
 
-        assert_eq!(encoded.as_str(), "kZGLpHR5cGWjd2Vip3NlcnZpY2Wsc2VydmljZV9uYW1lpG5hbWWpY29tcG9uZW50qHJlc291cmNlqHJlc291cmNlqHRyYWNlX2lkzwAAAAAAAAAHp3NwYW5faWTPAAAAAAAAAGOpcGFyZW50X2lkzwAAAAAAAAABpXN0YXJ00wAAAAAAAAAAqGR1cmF0aW9u0wAAAAA7msoApWVycm9y0gAAAACkbWV0YYGpc3Bhbi50eXBlo3dlYg==");
+    #[test]
+    fn test_encode() -> Result<(), Box<dyn std::error::Error>> {
+        for (version, payload) in PAYLOADS.iter() {
+            let encoded = version.encode("service_name", get_spans())?;
+            assert_eq!(encoded.as_slice(), *payload, "{:?} payload", version);
+        }
 
         Ok(())
     }
 
-    #[test]
-    fn test_encode_v05() -> Result<(), Box<dyn std::error::Error>> {
-        let spans = get_spans();
-        let encoded = base64::encode(ApiVersion::Version05.encode("service_name", spans)?);
+    // Decodes the single span of a payload into its fields, following the span schema of
+    // https://github.com/DataDog/datadog-agent/blob/c076ea9a1ffbde4c76d35343dbc32aecbbf99cb9/pkg/trace/pb/span.proto
+    fn decode_span(mut payload: &[u8]) -> Vec<(String, String)> {
+        fn read_string(rd: &mut &[u8]) -> String {
+            let mut buf = [0; 64];
+            rmp::decode::read_str(rd, &mut buf).unwrap().to_string()
+        }
 
-        assert_eq!(encoded.as_str(), "kpWsc2VydmljZV9uYW1lo3dlYqljb21wb25lbnSocmVzb3VyY2Wpc3Bhbi50eXBlkZGczgAAAADOAAAAAs4AAAADzwAAAAAAAAAHzwAAAAAAAABjzwAAAAAAAAAB0wAAAAAAAAAA0wAAAAA7msoA0gAAAACBzgAAAATOAAAAAYDOAAAAAQ==");
+        assert_eq!(rmp::decode::read_array_len(&mut payload).unwrap(), 1);
+        assert_eq!(rmp::decode::read_array_len(&mut payload).unwrap(), 1);
+        let len = rmp::decode::read_map_len(&mut payload).unwrap();
+        let mut fields = Vec::new();
+        for _ in 0..len {
+            let key = read_string(&mut payload);
+            let value = match key.as_str() {
+                "type" | "service" | "name" | "resource" => read_string(&mut payload),
+                "trace_id" | "span_id" | "parent_id" => {
+                    rmp::decode::read_int::<u64, _>(&mut payload)
+                        .unwrap()
+                        .to_string()
+                }
+                "start" | "duration" => rmp::decode::read_int::<i64, _>(&mut payload)
+                    .unwrap()
+                    .to_string(),
+                "error" => rmp::decode::read_int::<i32, _>(&mut payload)
+                    .unwrap()
+                    .to_string(),
+                "meta" => {
+                    let len = rmp::decode::read_map_len(&mut payload).unwrap();
+                    (0..len)
+                        .map(|_| {
+                            let key = read_string(&mut payload);
+                            format!("{}={}", key, read_string(&mut payload))
+                        })
+                        .collect::<Vec<_>>()
+                        .join(",")
+                }
+                "metrics" => {
+                    let len = rmp::decode::read_map_len(&mut payload).unwrap();
+                    (0..len)
+                        .map(|_| {
+                            let key = read_string(&mut payload);
+                            format!("{}={}", key, rmp::decode::read_f64(&mut payload).unwrap())
+                        })
+                        .collect::<Vec<_>>()
+                        .join(",")
+                }
+                other => panic!("unexpected span field {}", other),
+            };
+            fields.push((key, value));
+        }
+        assert!(payload.is_empty());
 
-        Ok(())
+        fields
+    }
+
+    #[test]
+    fn test_v04_payload() {
+        let v03 = decode_span(PAYLOADS[0].1);
+        let v04 = decode_span(PAYLOADS[1].1);
+
+        let expected = [
+            ("type", "web"),
+            ("service", "service_name"),
+            ("name", "component"),
+            ("resource", "resource"),
+            ("trace_id", "7"),
+            ("span_id", "99"),
+            ("parent_id", "1"),
+            ("start", "0"),
+            ("duration", "1000000000"),
+            ("error", "0"),
+            ("meta", "span.type=web"),
+            ("metrics", ""),
+        ]
+        .iter()
+        .map(|(key, value)| (key.to_string(), value.to_string()))
+        .collect::<Vec<_>>();
+        assert_eq!(v04, expected);
+        // The v0.4 payload is the v0.3 one with a `metrics` map on every span.
+        assert_eq!(v03, expected[..expected.len() - 1]);
     }
 }
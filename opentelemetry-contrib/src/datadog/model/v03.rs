@@ -3,7 +3,18 @@ use opentelemetry::exporter::trace;
 use opentelemetry::{Key, Value};
 use std::time::SystemTime;
 
-pub(crate) fn encode(service_name: &str, spans: Vec<trace::SpanData>) -> Result<Vec<u8>, Error> {
+// Version 0.4 shares this payload: an array of traces, where each trace is an array of spans
+// encoded as msgpack maps. See https://github.com/DataDog/datadog-agent/blob/c076ea9a1ffbde4c76d35343dbc32aecbbf99cb9/pkg/trace/api/version.go
+//
+// With `with_metrics`, every span also carries a "metrics" map (map[string]float64) next to
+// "meta", as produced by the official Datadog tracers for version 0.4.
+pub(crate) fn encode(
+    service_name: &str,
+    spans: Vec<trace::SpanData>,
+    with_metrics: bool,
+) -> Result<Vec<u8>, Error> {
+    let fields = if with_metrics { 11 } else { 10 };
+
     let mut encoded = Vec::new();
     rmp::encode::write_array_len(&mut encoded, spans.len() as u32)?;
 
@@ -25,11 +36,11 @@ pub(crate) fn encode(service_name: &str, spans: Vec<trace::SpanData>) -> Result<
             .unwrap_or(0);
 
         if let Some(Value::String(s)) = span.attributes.get(&Key::new("span.type")) {
-            rmp::encode::write_map_len(&mut encoded, 11)?;
+            rmp::encode::write_map_len(&mut encoded, fields + 1)?;
             rmp::encode::write_str(&mut encoded, "type")?;
             rmp::encode::write_str(&mut encoded, s.as_str())?;
         } else {
-            rmp::encode::write_map_len(&mut encoded, 10)?;
+            rmp::encode::write_map_len(&mut encoded, fields)?;
         }
 
         // Datadog span name is OpenTelemetry component name - see module docs for more information
@@ -67,6 +78,11 @@ pub(crate) fn encode(service_name: &str, spans: Vec<trace::SpanData>) -> Result<
             rmp::encode::write_str(&mut encoded, key.as_str())?;
             rmp::encode::write_str(&mut encoded, value_string.as_str())?;
         }
+
+        if with_metrics {
+            rmp::encode::write_str(&mut encoded, "metrics")?;
+            rmp::encode::write_map_len(&mut encoded, 0)?;
+        }
     }
 
     Ok(encoded)
@@ -3,7 +3,8 @@
 //! This is a library for extensions that are not part of the core API, but still may be useful for
 //! some users.
 //!
-//! Typically, those include vendor specific propagators.
+//! Typically, those include vendor specific propagators and standard
+//! instrumentation.
 #![warn(
     future_incompatible,
     missing_debug_implementations,
@@ -22,3 +23,7 @@
 #[cfg(feature = "datadog")]
 #[cfg_attr(docsrs, doc(cfg(feature = "datadog")))]
 pub mod datadog;
+
+#[cfg(feature = "process-metrics")]
+#[cfg_attr(docsrs, doc(cfg(feature = "process-metrics")))]
+pub mod process_metrics;
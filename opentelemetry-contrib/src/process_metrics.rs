@@ -0,0 +1,280 @@
+//! # Process Metrics
+//!
+//! Observes the standard metrics of the current process through any `Meter`,
+//! under the same instrument names in every service:
+//!
+//! | Instrument                      | Kind            | Unit | Labels                   |
+//! |---------------------------------|-----------------|------|--------------------------|
+//! | `process.cpu.time`              | `SumObserver`   | `s`  | `state`: `user`/`system` |
+//! | `process.memory.usage`          | `ValueObserver` | `By` |                          |
+//! | `process.memory.virtual`        | `ValueObserver` | `By` |                          |
+//! | `process.open_file_descriptors` | `ValueObserver` |      |                          |
+//! | `process.threads`               | `ValueObserver` |      |                          |
+//! | `process.uptime`                | `ValueObserver` | `s`  |                          |
+//!
+//! The values are read from `/proc/self` on each collection. On other
+//! platforms, or if `/proc` is not mounted, the instruments are registered
+//! but never observed.
+//!
+//! ```no_run
+//! use opentelemetry::{global, metrics::MeterProvider};
+//!
+//! let meter = global::meter_provider().meter("my-service");
+//! opentelemetry_contrib::process_metrics::register(&meter).unwrap();
+//! ```
+use opentelemetry::{
+    metrics::{BatchObserverResult, Meter, Result},
+    KeyValue, Unit,
+};
+use std::fs;
+use std::io;
+use std::path::Path;
+
+/// Register the process metric instruments with the given meter.
+pub fn register(meter: &Meter) -> Result<()> {
+    let ticks_per_second = clock_ticks_per_second().ok();
+
+    meter.build_batch_observer(|batch| {
+        let cpu_time = batch
+            .f64_sum_observer("process.cpu.time")
+            .with_description("Total CPU seconds of the process, by state")
+            .with_unit(Unit::new("s"))
+            .try_init()?;
+        let memory_usage = batch
+            .u64_value_observer("process.memory.usage")
+            .with_description("Resident memory of the process")
+            .with_unit(Unit::new("By"))
+            .try_init()?;
+        let memory_virtual = batch
+            .u64_value_observer("process.memory.virtual")
+            .with_description("Virtual memory of the process")
+            .with_unit(Unit::new("By"))
+            .try_init()?;
+        let open_file_descriptors = batch
+            .u64_value_observer("process.open_file_descriptors")
+            .with_description("Number of file descriptors opened by the process")
+            .try_init()?;
+        let threads = batch
+            .u64_value_observer("process.threads")
+            .with_description("Number of threads of the process")
+            .try_init()?;
+        let uptime = batch
+            .f64_value_observer("process.uptime")
+            .with_description("Seconds since the process started")
+            .with_unit(Unit::new("s"))
+            .try_init()?;
+
+        // Each file of `/proc` is read once per collection.
+        Ok(move |result: BatchObserverResult| {
+            let mut observations = Vec::new();
+
+            if let Some(stat) = ticks_per_second.and_then(|ticks| Stat::read(ticks).ok()) {
+                result.observe(
+                    &[KeyValue::new("state", "user")],
+                    &[cpu_time.observation(stat.user_time)],
+                );
+                result.observe(
+                    &[KeyValue::new("state", "system")],
+                    &[cpu_time.observation(stat.system_time)],
+                );
+                if let Ok(system_uptime) = system_uptime() {
+                    observations
+                        .push(uptime.observation((system_uptime - stat.start_time).max(0.0)));
+                }
+            }
+            if let Ok(status) = Status::read() {
+                observations.push(memory_usage.observation(status.resident_bytes));
+                observations.push(memory_virtual.observation(status.virtual_bytes));
+                observations.push(threads.observation(status.threads));
+            }
+            if let Ok(count) = count_open_file_descriptors() {
+                observations.push(open_file_descriptors.observation(count));
+            }
+
+            result.observe(&[], &observations);
+        })
+    })
+}
+
+/// The fields of `/proc/self/stat` used by the instruments, in seconds.
+#[derive(Debug, PartialEq)]
+struct Stat {
+    user_time: f64,
+    system_time: f64,
+    /// Seconds between the system boot and the start of the process.
+    start_time: f64,
+}
+
+impl Stat {
+    fn read(ticks_per_second: f64) -> io::Result<Self> {
+        Stat::parse(&fs::read_to_string("/proc/self/stat")?, ticks_per_second)
+    }
+
+    /// Parse the stat file, whose times are in clock ticks.
+    fn parse(stat: &str, ticks_per_second: f64) -> io::Result<Self> {
+        // The executable name in parentheses may contain spaces, the fields
+        // are counted from the process state following it.
+        let fields = stat
+            .rfind(')')
+            .map(|end| stat[end + 1..].split_whitespace().collect::<Vec<_>>())
+            .unwrap_or_default();
+        let field = |index: usize| -> io::Result<u64> {
+            fields
+                .get(index)
+                .and_then(|field| field.parse().ok())
+                .ok_or_else(|| invalid_data("malformed /proc/self/stat"))
+        };
+
+        Ok(Stat {
+            user_time: field(11)? as f64 / ticks_per_second,
+            system_time: field(12)? as f64 / ticks_per_second,
+            start_time: field(19)? as f64 / ticks_per_second,
+        })
+    }
+}
+
+/// The fields of `/proc/self/status` used by the instruments.
+#[derive(Debug, Default, PartialEq)]
+struct Status {
+    resident_bytes: u64,
+    virtual_bytes: u64,
+    threads: u64,
+}
+
+impl Status {
+    fn read() -> io::Result<Self> {
+        Status::parse(&fs::read_to_string("/proc/self/status")?)
+    }
+
+    fn parse(status: &str) -> io::Result<Self> {
+        let mut parsed = Status::default();
+        for line in status.lines() {
+            let mut parts = line.split_whitespace();
+            let (field, scale) = match parts.next() {
+                // Memory sizes are reported in kibibytes.
+                Some("VmRSS:") => (&mut parsed.resident_bytes, 1024),
+                Some("VmSize:") => (&mut parsed.virtual_bytes, 1024),
+                Some("Threads:") => (&mut parsed.threads, 1),
+                _ => continue,
+            };
+            let value: u64 = parts
+                .next()
+                .and_then(|value| value.parse().ok())
+                .ok_or_else(|| invalid_data("malformed /proc/self/status"))?;
+            *field = value * scale;
+        }
+
+        Ok(parsed)
+    }
+}
+
+fn count_open_file_descriptors() -> io::Result<u64> {
+    // Listing the directory opens one more descriptor, which is listed too.
+    let count = fs::read_dir(Path::new("/proc/self/fd"))?.count() as u64;
+    Ok(count.saturating_sub(1))
+}
+
+/// Seconds since the system boot.
+fn system_uptime() -> io::Result<f64> {
+    fs::read_to_string("/proc/uptime")?
+        .split_whitespace()
+        .next()
+        .and_then(|uptime| uptime.parse::<f64>().ok())
+        .ok_or_else(|| invalid_data("malformed /proc/uptime"))
+}
+
+/// The frequency of the clock ticks `/proc` reports times in.
+fn clock_ticks_per_second() -> io::Result<f64> {
+    #[cfg(unix)]
+    // Safety: `sysconf` only reads a configuration value of the system.
+    let ticks = unsafe { libc::sysconf(libc::_SC_CLK_TCK) };
+    #[cfg(not(unix))]
+    let ticks = -1;
+
+    if ticks > 0 {
+        Ok(ticks as f64)
+    } else {
+        Err(io::Error::new(
+            io::ErrorKind::Other,
+            "unknown clock tick rate",
+        ))
+    }
+}
+
+fn invalid_data(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use opentelemetry::metrics::MeterProvider;
+    use opentelemetry::sdk::{
+        export::metrics::{CheckpointSet, ExportKind},
+        metrics::{controllers, selectors::simple::Selector},
+    };
+    use std::collections::HashSet;
+    use std::time::Duration;
+
+    #[test]
+    fn parse_stat() {
+        let stat = "1234 (my (odd) app) S 1 1234 1234 0 -1 4194560 1530 0 0 0 \
+                    250 75 0 0 20 0 4 0 1000 12345678 512 18446744073709551615";
+
+        assert_eq!(
+            Stat::parse(stat, 100.0).unwrap(),
+            Stat {
+                user_time: 2.5,
+                system_time: 0.75,
+                start_time: 10.0,
+            }
+        );
+        assert!(Stat::parse("1234 (app) S 1", 100.0).is_err());
+    }
+
+    #[test]
+    fn parse_status() {
+        let status = "Name:\tapp\nVmSize:\t  20000 kB\nVmRSS:\t   1000 kB\nThreads:\t4\n";
+
+        assert_eq!(
+            Status::parse(status).unwrap(),
+            Status {
+                resident_bytes: 1_024_000,
+                virtual_bytes: 20_480_000,
+                threads: 4,
+            }
+        );
+    }
+
+    #[test]
+    #[cfg(target_os = "linux")]
+    fn observe_process() {
+        let mut controller =
+            controllers::pull(Box::new(Selector::Exact), Box::new(ExportKind::Cumulative))
+                .with_cache_period(Duration::from_secs(0))
+                .build();
+        register(&controller.provider().meter("test")).unwrap();
+
+        controller.collect().unwrap();
+        let mut names = HashSet::new();
+        controller
+            .try_for_each(&ExportKind::Cumulative, &mut |record| {
+                names.insert(record.descriptor().name().to_string());
+                Ok(())
+            })
+            .unwrap();
+
+        let expected = [
+            "process.cpu.time",
+            "process.memory.usage",
+            "process.memory.virtual",
+            "process.open_file_descriptors",
+            "process.threads",
+            "process.uptime",
+        ];
+        assert_eq!(
+            names,
+            expected.iter().map(|name| name.to_string()).collect()
+        );
+    }
+}
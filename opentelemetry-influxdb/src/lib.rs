@@ -0,0 +1,421 @@
+//! # OpenTelemetry InfluxDB Exporter
+//!
+//! Writes the aggregations of the metric instruments in the InfluxDB [line
+//! protocol]. Each record becomes one line, measured by the instrument name,
+//! tagged with the resource and labels, and with a field per aggregated value:
+//!
+//! ```text
+//! http.requests,host=a,method=GET sum=12u 1604000000000000000
+//! http.duration,host=a min=0.1,max=0.9,sum=1.5,count=3i,p50=0.5,p99=0.9 1604000000000000000
+//! cpu.temperature,host=a last=41.5 1604000000000000000
+//! ```
+//!
+//! The lines are written to a [`LineSink`]: an InfluxDB `/write` endpoint
+//! with `HttpSink`, enabled by the `http-sink` feature, a UDP listener with
+//! [`UdpSink`], or any `io::Write` with [`WriterSink`], which writes to stdout
+//! by default.
+//!
+//! ### InfluxDB Exporter Example
+//!
+//! ```no_run
+//! use futures::stream::{Stream, StreamExt as _};
+//! use opentelemetry::{global, KeyValue};
+//! use opentelemetry_influxdb::UdpSink;
+//! use std::time::Duration;
+//!
+//! // Skip first immediate tick from tokio, not needed for async_std.
+//! fn delayed_interval(duration: Duration) -> impl Stream<Item = tokio::time::Instant> {
+//!     tokio::time::interval(duration).skip(1)
+//! }
+//!
+//! # #[tokio::main] async fn main() {
+//! let _controller = opentelemetry_influxdb::exporter(tokio::spawn, delayed_interval)
+//!     .with_sink(UdpSink::new("127.0.0.1:8089").unwrap())
+//!     .init();
+//!
+//! let meter = global::meter("my-app");
+//! let counter = meter.u64_counter("requests").init();
+//! counter.add(1, &[KeyValue::new("method", "GET")]);
+//! # }
+//! ```
+//!
+//! [line protocol]: https://docs.influxdata.com/influxdb/v1.8/write_protocols/line_protocol_reference/
+#![warn(
+    future_incompatible,
+    missing_debug_implementations,
+    missing_docs,
+    nonstandard_style,
+    rust_2018_idioms,
+    unreachable_pub,
+    unused
+)]
+#![cfg_attr(docsrs, feature(doc_cfg), deny(broken_intra_doc_links))]
+#![doc(
+    html_logo_url = "https://raw.githubusercontent.com/open-telemetry/opentelemetry-rust/master/assets/logo.svg"
+)]
+#![cfg_attr(test, deny(warnings))]
+
+use futures::Stream;
+use opentelemetry::global;
+use opentelemetry::metrics::{Descriptor, MetricsError, Number, NumberKind, Result};
+use opentelemetry::sdk::{
+    export::metrics::{
+        CheckpointSet, Count, ExportKind, ExportKindSelector, Exporter, Histogram, LastValue, Max,
+        Min, Quantile, Record, Sum,
+    },
+    metrics::{
+        aggregators::{
+            ArrayAggregator, DDSKetchAggregator, HistogramAggregator, LastValueAggregator,
+            MinMaxSumCountAggregator, SumAggregator,
+        },
+        controllers::{self, PushController, PushControllerWorker},
+        selectors::simple::Selector,
+    },
+    Resource,
+};
+use std::collections::BTreeMap;
+use std::fmt::Write as _;
+use std::io;
+use std::time::{Duration, UNIX_EPOCH};
+
+mod sink;
+
+#[cfg(feature = "http-sink")]
+pub use sink::HttpSink;
+pub use sink::{LineSink, UdpSink, WriterSink};
+
+/// Create a new InfluxDB exporter builder, pushing the metrics with the given
+/// spawn and interval functions.
+pub fn exporter<S, SO, I, IS, ISI>(spawn: S, interval: I) -> ExporterBuilder<S, I>
+where
+    S: Fn(PushControllerWorker) -> SO,
+    I: Fn(Duration) -> IS,
+    IS: Stream<Item = ISI> + Send + 'static,
+{
+    ExporterBuilder {
+        spawn,
+        interval,
+        sink: None,
+        aggregator_selector: Selector::Exact,
+        export_kind: ExportKind::Cumulative,
+        quantiles: None,
+        period: None,
+        resource: None,
+    }
+}
+
+/// Configuration for the InfluxDB exporter.
+#[derive(Debug)]
+pub struct ExporterBuilder<S, I> {
+    spawn: S,
+    interval: I,
+    sink: Option<Box<dyn LineSink>>,
+    aggregator_selector: Selector,
+    export_kind: ExportKind,
+    quantiles: Option<Vec<f64>>,
+    period: Option<Duration>,
+    resource: Option<Resource>,
+}
+
+impl<S, SO, I, IS, ISI> ExporterBuilder<S, I>
+where
+    S: Fn(PushControllerWorker) -> SO,
+    I: Fn(Duration) -> IS,
+    IS: Stream<Item = ISI> + Send + 'static,
+{
+    /// Set the destination of the lines, stdout by default.
+    pub fn with_sink<T: LineSink + 'static>(self, sink: T) -> Self {
+        ExporterBuilder {
+            sink: Some(Box::new(sink)),
+            ..self
+        }
+    }
+
+    /// Set the aggregator selector, `Selector::Exact` by default.
+    pub fn with_aggregator_selector(self, aggregator_selector: Selector) -> Self {
+        ExporterBuilder {
+            aggregator_selector,
+            ..self
+        }
+    }
+
+    /// Set whether sums are exported as cumulative values, the default, or as
+    /// deltas.
+    pub fn with_export_kind(self, export_kind: ExportKind) -> Self {
+        ExporterBuilder {
+            export_kind,
+            ..self
+        }
+    }
+
+    /// Set the quantiles exported for distributions, 0.5, 0.9 and 0.99 by
+    /// default.
+    pub fn with_quantiles(self, quantiles: Vec<f64>) -> Self {
+        ExporterBuilder {
+            quantiles: Some(quantiles),
+            ..self
+        }
+    }
+
+    /// Set the frequency in which metrics are exported.
+    pub fn with_period(self, period: Duration) -> Self {
+        ExporterBuilder {
+            period: Some(period),
+            ..self
+        }
+    }
+
+    /// Set the resource associated with all meters, exported as tags.
+    pub fn with_resource(self, resource: Resource) -> Self {
+        ExporterBuilder {
+            resource: Some(resource),
+            ..self
+        }
+    }
+
+    /// Build a new push controller, returning errors if they arise.
+    pub fn try_init(self) -> Result<PushController> {
+        let quantiles = self.quantiles.unwrap_or_else(|| vec![0.5, 0.9, 0.99]);
+        if quantiles.iter().any(|q| *q < 0.0 || *q > 1.0) {
+            return Err(MetricsError::InvalidQuantile);
+        }
+
+        let exporter = InfluxDbExporter {
+            sink: self
+                .sink
+                .unwrap_or_else(|| Box::new(WriterSink::new(io::stdout()))),
+            export_kind: self.export_kind.clone(),
+            quantiles,
+        };
+        let mut push_builder = controllers::push(
+            self.aggregator_selector,
+            self.export_kind,
+            exporter,
+            self.spawn,
+            self.interval,
+        );
+        if let Some(period) = self.period {
+            push_builder = push_builder.with_period(period);
+        }
+        if let Some(resource) = self.resource {
+            push_builder = push_builder.with_resource(resource);
+        }
+
+        let controller = push_builder.build();
+        global::set_meter_provider(controller.provider());
+        Ok(controller)
+    }
+
+    /// Build a new push controller.
+    ///
+    /// # Panics
+    ///
+    /// This panics if a configured quantile is not between 0 and 1.
+    pub fn init(self) -> PushController {
+        self.try_init().unwrap()
+    }
+}
+
+/// An exporter writing the records in the InfluxDB line protocol.
+#[derive(Debug)]
+pub struct InfluxDbExporter {
+    sink: Box<dyn LineSink>,
+    export_kind: ExportKind,
+    quantiles: Vec<f64>,
+}
+
+impl ExportKindSelector for InfluxDbExporter {
+    fn export_kind_for(&self, descriptor: &Descriptor) -> ExportKind {
+        self.export_kind.export_kind_for(descriptor)
+    }
+}
+
+impl Exporter for InfluxDbExporter {
+    fn export(&self, checkpoint_set: &mut dyn CheckpointSet) -> Result<()> {
+        let mut lines = String::new();
+        checkpoint_set.try_for_each(self, &mut |record| self.write_line(record, &mut lines))?;
+
+        if lines.is_empty() {
+            return Ok(());
+        }
+        self.sink.write(&lines)
+    }
+}
+
+impl InfluxDbExporter {
+    fn write_line(&self, record: &Record<'_>, lines: &mut String) -> Result<()> {
+        let agg = record.aggregator().ok_or(MetricsError::NoDataCollected)?;
+        let kind = record.descriptor().number_kind();
+        let mut fields = Fields::default();
+
+        if let Some(sum) = agg.as_any().downcast_ref::<SumAggregator>() {
+            fields.number("sum", &sum.sum()?, kind);
+        } else if let Some(last_value) = agg.as_any().downcast_ref::<LastValueAggregator>() {
+            fields.number("last", &last_value.last_value()?.0, kind);
+        } else if let Some(array) = agg.as_any().downcast_ref::<ArrayAggregator>() {
+            self.distribution_fields(array, kind, &mut fields)?;
+        } else if let Some(sketch) = agg.as_any().downcast_ref::<DDSKetchAggregator>() {
+            self.distribution_fields(sketch, kind, &mut fields)?;
+        } else if let Some(mmsc) = agg.as_any().downcast_ref::<MinMaxSumCountAggregator>() {
+            fields.number("min", &mmsc.min()?, kind);
+            fields.number("max", &mmsc.max()?, kind);
+            fields.number("sum", &mmsc.sum()?, kind);
+            fields.count(mmsc.count()?);
+        } else if let Some(histogram) = agg.as_any().downcast_ref::<HistogramAggregator>() {
+            let buckets = histogram.histogram()?;
+            fields.number("sum", &histogram.sum()?, kind);
+            // Cumulative bucket counts keyed by their upper bound, as written by
+            // the Telegraf prometheus input, e.g. `0.5=1i,1=3i,+Inf=4i`.
+            let mut count = 0;
+            for (i, bucket) in buckets.counts().iter().enumerate() {
+                count += *bucket as u64;
+                match buckets.boundaries().get(i) {
+                    Some(bound) => fields.bucket(&bound.to_string(), count),
+                    None => fields.bucket("+Inf", count),
+                }
+            }
+            fields.count(count);
+        }
+
+        if fields.0.is_empty() {
+            return Ok(());
+        }
+
+        // Labels take precedence over resource attributes with the same key.
+        let mut tags = BTreeMap::new();
+        for (key, value) in record.resource().iter().chain(record.labels().iter()) {
+            let value = String::from(value.clone());
+            if !value.is_empty() {
+                tags.insert(key.as_str(), value);
+            }
+        }
+
+        lines.push_str(&escape(record.descriptor().name(), &[',', ' ']));
+        for (key, value) in tags {
+            let _ = write!(
+                lines,
+                ",{}={}",
+                escape(key, &[',', '=', ' ']),
+                escape(&value, &[',', '=', ' '])
+            );
+        }
+        lines.push(' ');
+        lines.push_str(&fields.0.join(","));
+        let timestamp = record
+            .end_time()
+            .duration_since(UNIX_EPOCH)
+            .map(|duration| duration.as_nanos())
+            .unwrap_or(0);
+        let _ = writeln!(lines, " {}", timestamp);
+
+        Ok(())
+    }
+
+    fn distribution_fields<A>(&self, agg: &A, kind: &NumberKind, fields: &mut Fields) -> Result<()>
+    where
+        A: Min + Max + Sum + Count + Quantile,
+    {
+        let count = agg.count()?;
+        if count == 0 {
+            return Ok(());
+        }
+
+        fields.number("min", &agg.min()?, kind);
+        fields.number("max", &agg.max()?, kind);
+        fields.number("sum", &agg.sum()?, kind);
+        fields.count(count);
+        for q in self.quantiles.iter() {
+            // e.g. `p50` or `p99.9`
+            let key = format!("p{}", (q * 1000.0).round() / 10.0);
+            fields.number(&key, &agg.quantile(*q)?, kind);
+        }
+
+        Ok(())
+    }
+}
+
+/// The fields of a line, formatted as `key=value`.
+#[derive(Debug, Default)]
+struct Fields(Vec<String>);
+
+impl Fields {
+    fn number(&mut self, key: &str, value: &Number, kind: &NumberKind) {
+        let value = match kind {
+            NumberKind::F64 => {
+                let value = value.to_f64(kind);
+                // The line protocol has no representation of NaN and infinity.
+                if !value.is_finite() {
+                    return;
+                }
+                value.to_string()
+            }
+            NumberKind::I64 => format!("{}i", value.to_i64(kind)),
+            NumberKind::U64 => format!("{}u", value.to_u64(kind)),
+        };
+        self.0
+            .push(format!("{}={}", escape(key, &[',', '=', ' ']), value));
+    }
+
+    fn count(&mut self, count: u64) {
+        self.0.push(format!("count={}i", count));
+    }
+
+    fn bucket(&mut self, upper_bound: &str, count: u64) {
+        self.0.push(format!(
+            "{}={}i",
+            escape(upper_bound, &[',', '=', ' ']),
+            count
+        ));
+    }
+}
+
+/// Escapes the characters with a special meaning in the given element of a
+/// line with a backslash. Newlines cannot be escaped and are replaced by
+/// spaces.
+fn escape(s: &str, special: &[char]) -> String {
+    let mut escaped = String::with_capacity(s.len());
+    for c in s.chars() {
+        let c = if c == '\n' { ' ' } else { c };
+        if special.contains(&c) {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn escape_special_characters() {
+        assert_eq!(
+            escape("http requests,total", &[',', ' ']),
+            "http\\ requests\\,total"
+        );
+        assert_eq!(escape("a=b\nc", &[',', '=', ' ']), "a\\=b\\ c");
+    }
+
+    #[test]
+    fn format_fields() {
+        let mut fields = Fields::default();
+        fields.number("sum", &Number::from(1.5), &NumberKind::F64);
+        fields.number("nan", &Number::from(f64::NAN), &NumberKind::F64);
+        fields.number("last", &Number::from(-3i64), &NumberKind::I64);
+        fields.number("max", &Number::from(u64::MAX), &NumberKind::U64);
+        fields.count(2);
+        fields.bucket("+Inf", 2);
+
+        assert_eq!(
+            fields.0,
+            vec![
+                "sum=1.5",
+                "last=-3i",
+                "max=18446744073709551615u",
+                "count=2i",
+                "+Inf=2i"
+            ]
+        );
+    }
+}
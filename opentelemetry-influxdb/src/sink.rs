@@ -0,0 +1,220 @@
+//! Destinations of the exported lines.
+#[cfg(feature = "http-sink")]
+use futures::{channel::oneshot, executor::block_on, future::BoxFuture};
+#[cfg(feature = "http-sink")]
+use http::{header, Method, Request, Uri};
+#[cfg(feature = "http-sink")]
+use opentelemetry::exporter::trace::{ExportResult, HttpClient};
+use opentelemetry::metrics::{MetricsError, Result};
+use std::fmt;
+use std::io;
+use std::net::{Ipv4Addr, Ipv6Addr, SocketAddr, ToSocketAddrs, UdpSocket};
+#[cfg(feature = "http-sink")]
+use std::sync::Arc;
+use std::sync::Mutex;
+
+/// The default maximum size of a UDP datagram, fitting in the MTU of most
+/// networks.
+const DEFAULT_MAX_PACKET_SIZE: usize = 1432;
+
+/// A destination of line protocol batches.
+pub trait LineSink: fmt::Debug + Send + Sync {
+    /// Write the lines of one export, each terminated by a newline.
+    fn write(&self, lines: &str) -> Result<()>;
+}
+
+/// Writes the lines to an InfluxDB `/write` endpoint.
+///
+/// The exporting thread blocks until the request completes. By default the
+/// request is sent on that thread, so the client must not depend on a runtime,
+/// like the blocking `reqwest` client. Requests of clients running on a
+/// runtime, like the async `reqwest` client, must be spawned on that runtime
+/// with [`HttpSink::with_spawner`].
+#[cfg(feature = "http-sink")]
+#[cfg_attr(docsrs, doc(cfg(feature = "http-sink")))]
+pub struct HttpSink {
+    client: Arc<dyn HttpClient>,
+    endpoint: Uri,
+    headers: Vec<(String, String)>,
+    spawn: Option<Box<dyn Fn(BoxFuture<'static, ()>) + Send + Sync>>,
+}
+
+#[cfg(feature = "http-sink")]
+impl fmt::Debug for HttpSink {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("HttpSink")
+            .field("client", &self.client)
+            .field("endpoint", &self.endpoint)
+            .field("headers", &self.headers)
+            .field("spawn", &self.spawn.is_some())
+            .finish()
+    }
+}
+
+#[cfg(feature = "http-sink")]
+impl HttpSink {
+    /// Create a sink posting the lines to the given url, including the query
+    /// selecting the database, e.g. `http://localhost:8086/write?db=metrics`.
+    pub fn new<C: HttpClient + 'static>(client: C, endpoint: &str) -> Result<Self> {
+        Ok(HttpSink {
+            client: Arc::new(client),
+            endpoint: endpoint
+                .parse()
+                .map_err(|err: http::uri::InvalidUri| MetricsError::Other(err.to_string()))?,
+            headers: Vec::new(),
+            spawn: None,
+        })
+    }
+
+    /// Add a header sent with every write request, e.g. `Authorization`.
+    pub fn with_header<K: Into<String>, V: Into<String>>(mut self, name: K, value: V) -> Self {
+        self.headers.push((name.into(), value.into()));
+        self
+    }
+
+    /// Send the requests on the runtime of the given spawn function, e.g.
+    /// `move |request| handle.spawn(request)` with the `Handle` of a tokio
+    /// runtime.
+    pub fn with_spawner<F, O>(self, spawn: F) -> Self
+    where
+        F: Fn(BoxFuture<'static, ()>) -> O + Send + Sync + 'static,
+    {
+        HttpSink {
+            spawn: Some(Box::new(move |request| {
+                let _ = spawn(request);
+            })),
+            ..self
+        }
+    }
+}
+
+#[cfg(feature = "http-sink")]
+impl LineSink for HttpSink {
+    fn write(&self, lines: &str) -> Result<()> {
+        let mut request = Request::builder()
+            .method(Method::POST)
+            .uri(self.endpoint.clone())
+            .header(header::CONTENT_TYPE, "text/plain; charset=utf-8");
+        for (name, value) in self.headers.iter() {
+            request = request.header(name.as_str(), value.as_str());
+        }
+        let request = request
+            .body(lines.as_bytes().to_vec())
+            .map_err(|err| MetricsError::Other(err.to_string()))?;
+
+        let response = match self.spawn.as_ref() {
+            Some(spawn) => {
+                let client = self.client.clone();
+                let (tx, rx) = oneshot::channel();
+                spawn(Box::pin(async move {
+                    let _ = tx.send(client.send(request).await);
+                }));
+                block_on(rx)
+                    .map_err(|_| MetricsError::Other("the write request was dropped".to_string()))?
+            }
+            None => block_on(self.client.send(request)),
+        };
+
+        match response {
+            Ok(ExportResult::Success) => Ok(()),
+            Ok(_) => Err(MetricsError::Other(
+                "InfluxDB rejected the write request".to_string(),
+            )),
+            Err(err) => Err(MetricsError::Other(err.to_string())),
+        }
+    }
+}
+
+/// Sends the lines to an InfluxDB or Telegraf UDP listener, packing as many
+/// lines as fit in each datagram.
+#[derive(Debug)]
+pub struct UdpSink {
+    socket: UdpSocket,
+    endpoint: SocketAddr,
+    max_packet_size: usize,
+}
+
+impl UdpSink {
+    /// Create a sink sending to the given address.
+    pub fn new<A: ToSocketAddrs>(endpoint: A) -> io::Result<Self> {
+        let endpoint = endpoint.to_socket_addrs()?.next().ok_or_else(|| {
+            io::Error::new(io::ErrorKind::InvalidInput, "could not resolve endpoint")
+        })?;
+        let local: SocketAddr = if endpoint.is_ipv4() {
+            (Ipv4Addr::UNSPECIFIED, 0).into()
+        } else {
+            (Ipv6Addr::UNSPECIFIED, 0).into()
+        };
+
+        Ok(UdpSink {
+            socket: UdpSocket::bind(local)?,
+            endpoint,
+            max_packet_size: DEFAULT_MAX_PACKET_SIZE,
+        })
+    }
+
+    /// Set the maximum size in bytes of the sent datagrams, 1432 by default.
+    ///
+    /// Lines longer than the maximum size are sent in a datagram of their own.
+    pub fn with_max_packet_size(self, max_packet_size: usize) -> Self {
+        UdpSink {
+            max_packet_size,
+            ..self
+        }
+    }
+
+    fn send(&self, packet: &str) -> Result<()> {
+        self.socket
+            .send_to(packet.as_bytes(), self.endpoint)
+            .map(|_| ())
+            .map_err(|err| MetricsError::Other(err.to_string()))
+    }
+}
+
+impl LineSink for UdpSink {
+    fn write(&self, lines: &str) -> Result<()> {
+        let mut start = 0;
+        let mut end = 0;
+        for line in lines.split_terminator('\n') {
+            let line_end = end + line.len() + 1;
+            if end > start && line_end - start > self.max_packet_size {
+                self.send(&lines[start..end])?;
+                start = end;
+            }
+            end = line_end;
+        }
+        if end > start {
+            self.send(&lines[start..end])?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Writes the lines to any writer, such as stdout or a file.
+#[derive(Debug)]
+pub struct WriterSink<W> {
+    writer: Mutex<W>,
+}
+
+impl<W: io::Write> WriterSink<W> {
+    /// Create a sink writing to the given writer.
+    pub fn new(writer: W) -> Self {
+        WriterSink {
+            writer: Mutex::new(writer),
+        }
+    }
+}
+
+impl<W> LineSink for WriterSink<W>
+where
+    W: io::Write + fmt::Debug + Send,
+{
+    fn write(&self, lines: &str) -> Result<()> {
+        self.writer.lock().map_err(From::from).and_then(|mut w| {
+            w.write_all(lines.as_bytes())
+                .and_then(|_| w.flush())
+                .map_err(|err| MetricsError::Other(err.to_string()))
+        })
+    }
+}
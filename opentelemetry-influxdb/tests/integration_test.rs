@@ -0,0 +1,240 @@
+#[cfg(feature = "http-sink")]
+use async_trait::async_trait;
+#[cfg(feature = "http-sink")]
+use http::Request;
+#[cfg(feature = "http-sink")]
+use opentelemetry::exporter::trace::{ExportResult, HttpClient};
+use opentelemetry::metrics::{MeterProvider, ObserverResult};
+use opentelemetry::sdk::{
+    metrics::{selectors::simple::Selector, PushController},
+    Resource,
+};
+use opentelemetry::KeyValue;
+#[cfg(feature = "http-sink")]
+use opentelemetry_influxdb::HttpSink;
+use opentelemetry_influxdb::{LineSink, UdpSink, WriterSink};
+#[cfg(feature = "http-sink")]
+use std::error::Error;
+use std::io;
+use std::net::UdpSocket;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+#[derive(Clone, Debug, Default)]
+struct Buffer(Arc<Mutex<Vec<u8>>>);
+
+impl io::Write for Buffer {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.lock().unwrap().write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl Buffer {
+    /// The written lines without timestamps, sorted.
+    fn lines(&self) -> Vec<String> {
+        let written = String::from_utf8(self.0.lock().unwrap().clone()).unwrap();
+        let mut lines = written
+            .lines()
+            .map(|line| {
+                let (line, timestamp) = line.split_at(line.rfind(' ').unwrap());
+                assert!(timestamp.trim().parse::<u128>().unwrap() > 0);
+                line.to_string()
+            })
+            .collect::<Vec<_>>();
+        lines.sort();
+        lines
+    }
+}
+
+fn controller<T: LineSink + 'static>(sink: T) -> PushController {
+    controller_with_selector(sink, Selector::Exact)
+}
+
+fn controller_with_selector<T: LineSink + 'static>(sink: T, selector: Selector) -> PushController {
+    opentelemetry_influxdb::exporter(
+        // The worker is never polled, the tests export with `force_flush`.
+        |_worker| (),
+        |_period| futures::stream::pending::<()>(),
+    )
+    .with_sink(sink)
+    .with_aggregator_selector(selector)
+    .with_quantiles(vec![0.5, 0.999])
+    .with_resource(Resource::new(vec![
+        KeyValue::new("host", "a"),
+        KeyValue::new("empty", ""),
+    ]))
+    .init()
+}
+
+#[test]
+fn test_writer_sink() {
+    let buffer = Buffer::default();
+    let controller = controller(WriterSink::new(buffer.clone()));
+    let meter = controller.provider().meter("test");
+
+    let counter = meter.u64_counter("http.requests").init();
+    let recorder = meter.f64_value_recorder("http duration").init();
+    let _observer = meter
+        .f64_value_observer("temperature", |result: ObserverResult<f64>| {
+            result.observe(41.5, &[KeyValue::new("host", "b")])
+        })
+        .init();
+
+    counter.add(12, &[KeyValue::new("method", "GET,POST")]);
+    for value in &[0.1, 0.5, 0.9] {
+        recorder.record(*value, &[]);
+    }
+    controller.force_flush().unwrap();
+
+    assert_eq!(
+        buffer.lines(),
+        vec![
+            "http.requests,host=a,method=GET\\,POST sum=12u",
+            "http\\ duration,host=a min=0.1,max=0.9,sum=1.5,count=3i,p50=0.5,p99.9=0.9",
+            "temperature,host=b last=41.5",
+        ]
+    );
+}
+
+#[test]
+fn test_histogram_buckets() {
+    let buffer = Buffer::default();
+    let controller = controller_with_selector(
+        WriterSink::new(buffer.clone()),
+        Selector::Histogram(vec![0.5, 1.0]),
+    );
+    let recorder = controller
+        .provider()
+        .meter("test")
+        .f64_value_recorder("latency")
+        .init();
+
+    for value in &[0.1, 0.7, 0.9, 2.0] {
+        recorder.record(*value, &[]);
+    }
+    controller.force_flush().unwrap();
+
+    assert_eq!(
+        buffer.lines(),
+        vec!["latency,host=a sum=3.7,0.5=1i,1=3i,+Inf=4i,count=4i"]
+    );
+}
+
+#[test]
+fn test_udp_sink() {
+    let listener = UdpSocket::bind("127.0.0.1:0").unwrap();
+    listener
+        .set_read_timeout(Some(Duration::from_secs(5)))
+        .unwrap();
+    let sink = UdpSink::new(listener.local_addr().unwrap())
+        .unwrap()
+        .with_max_packet_size(50);
+    let controller = controller(sink);
+    let counter = controller.provider().meter("test").u64_counter("a").init();
+
+    for id in 0..3 {
+        counter.add(1, &[KeyValue::new("id", id.to_string())]);
+    }
+    controller.force_flush().unwrap();
+
+    // Each line has 41 bytes, a single line fits in each datagram.
+    let mut buf = [0; 1024];
+    for _ in 0..3 {
+        let len = listener.recv(&mut buf).unwrap();
+        let packet = String::from_utf8_lossy(&buf[..len]);
+        assert_eq!(packet.lines().count(), 1);
+        assert!(packet.starts_with("a,host=a,id="));
+    }
+}
+
+#[cfg(feature = "http-sink")]
+#[derive(Debug, Default)]
+struct TestClient {
+    requests: Arc<Mutex<Vec<Request<Vec<u8>>>>>,
+}
+
+#[cfg(feature = "http-sink")]
+#[async_trait]
+impl HttpClient for TestClient {
+    async fn send(
+        &self,
+        request: Request<Vec<u8>>,
+    ) -> Result<ExportResult, Box<dyn Error + Send + Sync + 'static>> {
+        self.requests.lock().unwrap().push(request);
+        Ok(ExportResult::Success)
+    }
+}
+
+#[cfg(feature = "http-sink")]
+#[test]
+fn test_http_sink() {
+    let requests = Arc::new(Mutex::new(Vec::new()));
+    let client = TestClient {
+        requests: requests.clone(),
+    };
+    let sink = HttpSink::new(client, "http://localhost:8086/write?db=metrics")
+        .unwrap()
+        .with_header("Authorization", "Token secret");
+    let controller = controller(sink);
+    let counter = controller.provider().meter("test").u64_counter("a").init();
+
+    counter.add(1, &[]);
+    controller.force_flush().unwrap();
+
+    let requests = requests.lock().unwrap();
+    assert_eq!(requests.len(), 1);
+    assert_eq!(
+        requests[0].uri().to_string(),
+        "http://localhost:8086/write?db=metrics"
+    );
+    assert_eq!(requests[0].headers()["Authorization"], "Token secret");
+    assert!(String::from_utf8_lossy(requests[0].body()).starts_with("a,host=a sum=1u "));
+}
+
+/// A client depending on the tokio runtime, like the async `reqwest` client.
+#[cfg(feature = "http-sink")]
+#[derive(Debug, Default)]
+struct RuntimeClient {
+    requests: Arc<Mutex<usize>>,
+}
+
+#[cfg(feature = "http-sink")]
+#[async_trait]
+impl HttpClient for RuntimeClient {
+    async fn send(
+        &self,
+        _request: Request<Vec<u8>>,
+    ) -> Result<ExportResult, Box<dyn Error + Send + Sync + 'static>> {
+        // Panics outside of a tokio runtime.
+        tokio::time::delay_for(Duration::from_millis(1)).await;
+        *self.requests.lock().unwrap() += 1;
+        Ok(ExportResult::Success)
+    }
+}
+
+#[cfg(feature = "http-sink")]
+#[tokio::test(threaded_scheduler)]
+async fn test_http_sink_spawner() {
+    let requests = Arc::new(Mutex::new(0));
+    let client = RuntimeClient {
+        requests: requests.clone(),
+    };
+    let handle = tokio::runtime::Handle::current();
+    let sink = HttpSink::new(client, "http://localhost:8086/write?db=metrics")
+        .unwrap()
+        .with_spawner(move |request| handle.spawn(request));
+    let controller = controller(sink);
+    let counter = controller.provider().meter("test").u64_counter("a").init();
+
+    counter.add(1, &[]);
+    tokio::task::spawn_blocking(move || controller.force_flush())
+        .await
+        .unwrap()
+        .unwrap();
+
+    assert_eq!(*requests.lock().unwrap(), 1);
+}
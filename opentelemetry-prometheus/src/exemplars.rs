@@ -0,0 +1,149 @@
+//! Exemplars of the exported series, rendered in the OpenMetrics format.
+use crate::sanitize;
+use opentelemetry::metrics::NumberKind;
+use opentelemetry::sdk::export::metrics::Exemplar;
+use prometheus::proto::{LabelPair, MetricFamily};
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::time::UNIX_EPOCH;
+
+/// The maximum number of UTF-8 characters of the names and values of the
+/// labels of an exemplar.
+const MAX_LABELS_LENGTH: usize = 128;
+
+/// Identifies a sample by the name of its metric family and its labels,
+/// including the `le` label of histogram buckets.
+#[derive(Debug, Hash, PartialEq, Eq)]
+struct SeriesKey {
+    name: String,
+    labels: Vec<(String, String)>,
+}
+
+impl SeriesKey {
+    fn new(name: &str, labels: &[LabelPair], additional_label: Option<(&str, &str)>) -> Self {
+        SeriesKey {
+            name: name.to_string(),
+            labels: labels
+                .iter()
+                .map(|lp| (lp.get_name().to_string(), lp.get_value().to_string()))
+                .chain(additional_label.map(|(name, value)| (name.to_string(), value.to_string())))
+                .collect(),
+        }
+    }
+}
+
+thread_local! {
+    /// The exemplars collected by the gathering running on this thread, if
+    /// any.
+    static GATHERED: RefCell<Option<SeriesExemplars>> = RefCell::new(None);
+}
+
+/// Gather the metric families of the registry, along with the exemplars of
+/// the exporters registered in it that were collected by this gathering.
+///
+/// Registries collect on the gathering thread, so concurrent gatherings each
+/// get the exemplars of their own collection.
+pub(crate) fn gather(registry: &prometheus::Registry) -> (Vec<MetricFamily>, SeriesExemplars) {
+    GATHERED.with(|gathered| *gathered.borrow_mut() = Some(SeriesExemplars::default()));
+    let metric_families = registry.gather();
+    let exemplars = GATHERED
+        .with(|gathered| gathered.borrow_mut().take())
+        .unwrap_or_default();
+
+    (metric_families, exemplars)
+}
+
+/// An exemplar converted for exposition.
+#[derive(Debug)]
+pub(crate) struct SeriesExemplar {
+    pub(crate) labels: Vec<(String, String)>,
+    pub(crate) value: f64,
+    /// Seconds since the unix epoch.
+    pub(crate) timestamp: f64,
+}
+
+impl SeriesExemplar {
+    fn new(exemplar: &Exemplar, kind: &NumberKind) -> Self {
+        let mut labels = vec![
+            (
+                "trace_id".to_string(),
+                format!("{:032x}", exemplar.trace_id()),
+            ),
+            (
+                "span_id".to_string(),
+                format!("{:016x}", exemplar.span_id()),
+            ),
+        ];
+        let mut length: usize = labels
+            .iter()
+            .map(|(name, value)| name.chars().count() + value.chars().count())
+            .sum();
+        for label in exemplar.filtered_labels() {
+            let name = sanitize(label.key.as_str());
+            let value = String::from(label.value.clone());
+            length += name.chars().count() + value.chars().count();
+            if length > MAX_LABELS_LENGTH {
+                break;
+            }
+            labels.push((name, value));
+        }
+
+        SeriesExemplar {
+            labels,
+            value: exemplar.value().to_f64(kind),
+            timestamp: exemplar
+                .timestamp()
+                .duration_since(UNIX_EPOCH)
+                .map(|duration| duration.as_secs_f64())
+                .unwrap_or(0.0),
+        }
+    }
+}
+
+/// The exemplars of the samples of a collection.
+#[derive(Debug, Default)]
+pub(crate) struct SeriesExemplars {
+    exemplars: HashMap<SeriesKey, SeriesExemplar>,
+}
+
+impl SeriesExemplars {
+    /// Hand the exemplars of a collection to the gathering running on this
+    /// thread, they are dropped if the registry is gathered otherwise.
+    pub(crate) fn collected(self) {
+        GATHERED.with(|gathered| {
+            if let Some(gathered) = gathered.borrow_mut().as_mut() {
+                gathered.exemplars.extend(self.exemplars);
+            }
+        })
+    }
+
+    /// Add the exemplar of the sample with the given labels.
+    pub(crate) fn insert(
+        &mut self,
+        name: &str,
+        labels: &[LabelPair],
+        additional_label: Option<(&str, &str)>,
+        exemplar: &Exemplar,
+        kind: &NumberKind,
+    ) {
+        self.exemplars.insert(
+            SeriesKey::new(name, labels, additional_label),
+            SeriesExemplar::new(exemplar, kind),
+        );
+    }
+
+    /// The exemplar of the sample with the given labels.
+    pub(crate) fn get(
+        &self,
+        name: &str,
+        labels: &[LabelPair],
+        additional_label: Option<(&str, &str)>,
+    ) -> Option<&SeriesExemplar> {
+        if self.exemplars.is_empty() {
+            return None;
+        }
+
+        self.exemplars
+            .get(&SeriesKey::new(name, labels, additional_label))
+    }
+}
@@ -37,9 +37,9 @@
 //!
 //! // result now contains encoded metrics:
 //! //
-//! // # HELP a_counter Counts things
-//! // # TYPE a_counter counter
-//! // a_counter{R="V",key="value"} 100
+//! // # HELP a_counter_total Counts things
+//! // # TYPE a_counter_total counter
+//! // a_counter_total{R="V",key="value"} 100
 //! // # HELP a_value_recorder Records values
 //! // # TYPE a_value_recorder histogram
 //! // a_value_recorder_bucket{R="V",key="value",le="0.5"} 0
@@ -49,6 +49,25 @@
 //! // a_value_recorder_sum{R="V",key="value"} 100
 //! // a_value_recorder_count{R="V",key="value"} 1
 //! ```
+//!
+//! ### Serving Metrics
+//!
+//! With the `hyper-server` feature enabled, the exporter can serve its
+//! registry on `/metrics` itself, in the text or OpenMetrics format depending
+//! on the `Accept` header of the scraper:
+//!
+//! ```ignore
+//! let exporter = opentelemetry_prometheus::exporter()
+//!     .with_server_address(([0, 0, 0, 0], 9464).into())
+//!     .init();
+//! ```
+//!
+//! ### Pushing Metrics
+//!
+//! Processes that cannot be scraped can push their metrics to a remote write
+//! endpoint with the `remote-write` feature, see the [`remote_write`] module.
+//! The `remote-write-reqwest` feature additionally provides a default http
+//! client for it.
 #![warn(
     future_incompatible,
     missing_debug_implementations,
@@ -66,11 +85,15 @@
 
 use opentelemetry::global;
 use opentelemetry::sdk::{
-    export::metrics::{CheckpointSet, ExportKind, Histogram, Record, Sum},
+    export::metrics::{
+        AggregatorSelector, CheckpointSet, Exemplars, ExportKind, ExportKindSelector, Histogram,
+        LastValue, Record, Sum,
+    },
     metrics::{
-        aggregators::{HistogramAggregator, SumAggregator},
-        controllers,
+        aggregators::{HistogramAggregator, LastValueAggregator, SumAggregator},
+        controllers::{self, SharedAccumulator},
         selectors::simple::Selector,
+        views::View,
         PullController,
     },
     Resource,
@@ -80,19 +103,46 @@ use opentelemetry::{
     metrics::{
         registry::RegistryMeterProvider, Descriptor, InstrumentKind, MetricsError, NumberKind,
     },
-    KeyValue,
+    Key, KeyValue,
 };
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
+#[cfg(feature = "hyper-server")]
+use std::net::SocketAddr;
 use std::sync::{Arc, Mutex};
-use std::time::Duration;
-
+use std::time::{Duration, SystemTime};
+
+mod exemplars;
+mod naming;
+mod openmetrics;
+#[cfg(feature = "remote-write")]
+#[cfg_attr(docsrs, doc(cfg(feature = "remote-write")))]
+pub mod remote_write;
 mod sanitize;
-
+mod selector;
+#[cfg(feature = "hyper-server")]
+mod server;
+mod summary;
+
+use exemplars::SeriesExemplars;
+use naming::MetricNaming;
+use openmetrics::format_float;
+pub use openmetrics::{OpenMetricsEncoder, OPENMETRICS_FORMAT};
 use sanitize::sanitize;
+use selector::HistogramSelector;
+#[cfg(feature = "hyper-server")]
+use server::MetricsServer;
+use summary::Summaries;
+pub use summary::SummaryAggregation;
 
 /// Cache disabled by default.
 const DEFAULT_CACHE_PERIOD: Duration = Duration::from_secs(0);
-const EXPORT_KIND: ExportKind = ExportKind::Cumulative;
+/// Summary quantiles are computed over the last 10 minutes by default.
+const DEFAULT_SUMMARY_WINDOW: Duration = Duration::from_secs(600);
+/// Name of the metric describing the resource, following the OpenMetrics
+/// convention for target metadata.
+const TARGET_INFO_NAME: &str = "target_info";
+/// Description of the target info metric.
+const TARGET_INFO_DESCRIPTION: &str = "Target metadata";
 
 /// Create a new prometheus exporter builder.
 pub fn exporter() -> ExporterBuilder {
@@ -120,10 +170,41 @@ pub struct ExporterBuilder {
     /// Defines the default histogram bucket boundaries.
     default_histogram_boundaries: Option<Vec<f64>>,
 
+    /// Histogram bucket boundaries of the instruments matching each pattern.
+    histogram_boundaries: Vec<(String, Vec<f64>)>,
+
+    /// Defines how metric names are built from instrument descriptors.
+    naming: MetricNaming,
+
+    /// If set, `ValueRecorder`s are exported as summaries computed with this
+    /// aggregation instead of histograms.
+    summary_aggregation: Option<SummaryAggregation>,
+
+    /// The period over which summary quantiles are computed.
+    summary_window: Option<Duration>,
+
     /// The prometheus registry that will be used to register instruments.
     ///
     /// If not set a new empty `Registry` is created.
     registry: Option<prometheus::Registry>,
+
+    /// If set, the resource is exported as a `target_info` metric and only
+    /// these resource keys are added to the labels of every series.
+    target_info_resource_labels: Option<Vec<Key>>,
+
+    /// Views customizing the export of the instruments they match.
+    views: Vec<View>,
+
+    /// The maximum number of distinct label sets exported per instrument.
+    cardinality_limit: Option<usize>,
+
+    /// If set, the accumulator shared with other controllers whose instruments
+    /// are exported.
+    shared_accumulator: Option<SharedAccumulator>,
+
+    /// If set, the address of the HTTP server serving the registry's metrics.
+    #[cfg(feature = "hyper-server")]
+    server_address: Option<SocketAddr>,
 }
 
 impl ExporterBuilder {
@@ -146,7 +227,7 @@ impl ExporterBuilder {
         }
     }
 
-    /// Set the default summary quantiles to be used by exported prometheus histograms
+    /// Set the default summary quantiles to be used by exported prometheus summaries
     pub fn with_default_summary_quantiles(self, quantiles: Vec<f64>) -> Self {
         ExporterBuilder {
             default_summary_quantiles: Some(quantiles),
@@ -154,6 +235,27 @@ impl ExporterBuilder {
         }
     }
 
+    /// Export `ValueRecorder`s as prometheus summaries instead of histograms.
+    ///
+    /// Quantiles are computed with the given aggregation over a sliding window,
+    /// see `with_summary_window`, while the count and sum of each summary are
+    /// cumulative.
+    pub fn with_summaries(self, aggregation: SummaryAggregation) -> Self {
+        ExporterBuilder {
+            summary_aggregation: Some(aggregation),
+            ..self
+        }
+    }
+
+    /// Set the period over which summary quantiles are computed (10 minutes by
+    /// default).
+    pub fn with_summary_window(self, window: Duration) -> Self {
+        ExporterBuilder {
+            summary_window: Some(window),
+            ..self
+        }
+    }
+
     /// Set the default boundaries to be used by exported prometheus histograms
     pub fn with_default_histogram_boundaries(self, boundaries: Vec<f64>) -> Self {
         ExporterBuilder {
@@ -162,6 +264,44 @@ impl ExporterBuilder {
         }
     }
 
+    /// Set the boundaries of the histograms of the `ValueRecorder`s matching the
+    /// given name pattern, where `*` matches any sequence of characters.
+    ///
+    /// The first matching pattern is used, instruments that match none use the
+    /// default boundaries. Ignored if `ValueRecorder`s are exported as
+    /// summaries.
+    pub fn with_histogram_boundaries<T: Into<String>>(
+        mut self,
+        pattern: T,
+        boundaries: Vec<f64>,
+    ) -> Self {
+        self.histogram_boundaries.push((pattern.into(), boundaries));
+        self
+    }
+
+    /// Do not append the unit of instruments, e.g. `_seconds` or `_bytes`, to
+    /// metric names.
+    pub fn without_units(self) -> Self {
+        ExporterBuilder {
+            naming: MetricNaming {
+                units: false,
+                ..self.naming
+            },
+            ..self
+        }
+    }
+
+    /// Do not append `_total` to the metric names of monotonic counters.
+    pub fn without_counter_suffixes(self) -> Self {
+        ExporterBuilder {
+            naming: MetricNaming {
+                counter_suffixes: false,
+                ..self.naming
+            },
+            ..self
+        }
+    }
+
     /// Set the prometheus registry to be used by this exporter
     pub fn with_registry(self, registry: prometheus::Registry) -> Self {
         ExporterBuilder {
@@ -170,9 +310,78 @@ impl ExporterBuilder {
         }
     }
 
+    /// Export the resource once as a `target_info` gauge instead of adding its
+    /// attributes to the labels of every series.
+    ///
+    /// The given resource keys are still copied onto every series, e.g. to
+    /// join them with the target labels of the scrape; pass an empty `Vec` to
+    /// copy none of them.
+    pub fn with_target_info(self, resource_labels: Vec<Key>) -> Self {
+        ExporterBuilder {
+            target_info_resource_labels: Some(resource_labels),
+            ..self
+        }
+    }
+
+    /// Export the instruments matched by the given views according to the
+    /// first matching view, e.g. to rename them or drop labels.
+    pub fn with_views(self, views: Vec<View>) -> Self {
+        ExporterBuilder { views, ..self }
+    }
+
+    /// Limit the number of distinct label sets exported per instrument.
+    ///
+    /// Measurements with label sets beyond the limit are exported as a single
+    /// series labeled `otel_metric_overflow="true"`.
+    pub fn with_cardinality_limit(self, limit: usize) -> Self {
+        ExporterBuilder {
+            cardinality_limit: Some(limit),
+            ..self
+        }
+    }
+
+    /// Export the instruments of an accumulator shared with other controllers,
+    /// e.g. a push controller exporting the same instruments over OTLP.
+    ///
+    /// The aggregator selector, views, resource and cardinality limit of the
+    /// shared accumulator are used, so it should select histograms or sketches
+    /// for value recorders, like `Selector::Histogram`. Initializing the
+    /// exporter fails if views, a resource, a cardinality limit or histogram
+    /// boundaries by instrument are also configured on this builder.
+    pub fn with_shared_accumulator(self, shared: &SharedAccumulator) -> Self {
+        ExporterBuilder {
+            shared_accumulator: Some(shared.clone()),
+            ..self
+        }
+    }
+
+    /// Serve the registry's metrics on `/metrics` at the given address.
+    ///
+    /// The server negotiates the text or OpenMetrics format with the scraper
+    /// and is shut down once all handles of the exporter are dropped, or when
+    /// `PrometheusExporter::shutdown_server` is called.
+    #[cfg(feature = "hyper-server")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "hyper-server")))]
+    pub fn with_server_address(self, addr: SocketAddr) -> Self {
+        ExporterBuilder {
+            server_address: Some(addr),
+            ..self
+        }
+    }
+
     /// Sets up a complete export pipeline with the recommended setup, using the
     /// recommended selector and standard processor.
     pub fn try_init(self) -> Result<PrometheusExporter, MetricsError> {
+        if self.shared_accumulator.is_some()
+            && (!self.views.is_empty()
+                || self.resource.is_some()
+                || self.cardinality_limit.is_some()
+                || !self.histogram_boundaries.is_empty())
+        {
+            return Err(MetricsError::Other(
+                "the views, resource, cardinality limit and histogram boundaries of a shared accumulator are configured on the accumulator".into(),
+            ));
+        }
         let registry = self.registry.unwrap_or_else(prometheus::Registry::new);
         let default_summary_quantiles = self
             .default_summary_quantiles
@@ -180,23 +389,80 @@ impl ExporterBuilder {
         let default_histogram_boundaries = self
             .default_histogram_boundaries
             .unwrap_or_else(|| vec![0.5, 0.9, 0.99]);
-        let selector = Box::new(Selector::Histogram(default_histogram_boundaries.clone()));
-        let mut controller_builder = controllers::pull(selector, Box::new(EXPORT_KIND))
-            .with_cache_period(self.cache_period.unwrap_or(DEFAULT_CACHE_PERIOD))
-            .with_memory(true);
-        if let Some(resource) = self.resource {
-            controller_builder = controller_builder.with_resource(resource);
-        }
-        let controller = controller_builder.build();
+        let selector: Box<dyn AggregatorSelector + Send + Sync> = match &self.summary_aggregation {
+            Some(SummaryAggregation::Sketch(config)) => Box::new(Selector::Sketch(config.clone())),
+            Some(SummaryAggregation::Exact) => Box::new(Selector::Exact),
+            None => Box::new(HistogramSelector::new(
+                default_histogram_boundaries.clone(),
+                self.histogram_boundaries,
+            )),
+        };
+        let export_kind = PrometheusExportKind {
+            summaries: self.summary_aggregation.is_some(),
+        };
+        let controller = match self.shared_accumulator {
+            Some(shared) => shared
+                .pull(Box::new(export_kind))
+                .with_cache_period(self.cache_period.unwrap_or(DEFAULT_CACHE_PERIOD))
+                .with_memory(true)
+                .build(),
+            None => {
+                let mut controller_builder = controllers::pull(selector, Box::new(export_kind))
+                    .with_cache_period(self.cache_period.unwrap_or(DEFAULT_CACHE_PERIOD))
+                    .with_memory(true)
+                    .with_views(self.views);
+                if let Some(resource) = self.resource {
+                    controller_builder = controller_builder.with_resource(resource);
+                }
+                if let Some(limit) = self.cardinality_limit {
+                    controller_builder = controller_builder.with_cardinality_limit(limit);
+                }
+                controller_builder.build()
+            }
+        };
+        let target_info = self
+            .target_info_resource_labels
+            .map(|resource_labels| TargetInfo {
+                resource: controller.resource().clone(),
+                resource_labels: resource_labels.into_iter().collect(),
+            });
 
         global::set_meter_provider(controller.provider());
 
-        PrometheusExporter::new(
+        let summary_window = self.summary_window.unwrap_or(DEFAULT_SUMMARY_WINDOW);
+        let summaries = self.summary_aggregation.map(|aggregation| {
+            Summaries::new(
+                aggregation,
+                default_summary_quantiles.clone(),
+                summary_window,
+            )
+        });
+
+        let exporter = PrometheusExporter::with_collector(
             registry,
             controller,
             default_summary_quantiles,
             default_histogram_boundaries,
-        )
+            CollectorConfig {
+                summaries,
+                target_info,
+                naming: self.naming,
+            },
+        )?;
+
+        #[cfg(feature = "hyper-server")]
+        let exporter = match self.server_address {
+            Some(addr) => {
+                let server = MetricsServer::start(exporter.registry.clone(), addr)?;
+                PrometheusExporter {
+                    server: Some(Arc::new(server)),
+                    ..exporter
+                }
+            }
+            None => exporter,
+        };
+
+        Ok(exporter)
     }
 
     /// Sets up a complete export pipeline with the recommended setup, using the
@@ -218,8 +484,11 @@ impl ExporterBuilder {
 pub struct PrometheusExporter {
     registry: prometheus::Registry,
     controller: Arc<Mutex<PullController>>,
+    export_kind: PrometheusExportKind,
     default_summary_quantiles: Vec<f64>,
     default_histogram_boundaries: Vec<f64>,
+    #[cfg(feature = "hyper-server")]
+    server: Option<Arc<MetricsServer>>,
 }
 
 impl PrometheusExporter {
@@ -230,8 +499,27 @@ impl PrometheusExporter {
         default_summary_quantiles: Vec<f64>,
         default_histogram_boundaries: Vec<f64>,
     ) -> Result<Self, MetricsError> {
+        PrometheusExporter::with_collector(
+            registry,
+            controller,
+            default_summary_quantiles,
+            default_histogram_boundaries,
+            CollectorConfig::default(),
+        )
+    }
+
+    fn with_collector(
+        registry: prometheus::Registry,
+        controller: PullController,
+        default_summary_quantiles: Vec<f64>,
+        default_histogram_boundaries: Vec<f64>,
+        config: CollectorConfig,
+    ) -> Result<Self, MetricsError> {
+        let export_kind = PrometheusExportKind {
+            summaries: config.summaries.is_some(),
+        };
         let controller = Arc::new(Mutex::new(controller));
-        let collector = Collector::with_controller(controller.clone());
+        let collector = Collector::with_controller(controller.clone(), export_kind, config);
         registry
             .register(Box::new(collector))
             .map_err(|e| MetricsError::Other(e.to_string()))?;
@@ -239,8 +527,11 @@ impl PrometheusExporter {
         Ok(PrometheusExporter {
             registry,
             controller,
+            export_kind,
             default_summary_quantiles,
             default_histogram_boundaries,
+            #[cfg(feature = "hyper-server")]
+            server: None,
         })
     }
 
@@ -249,6 +540,40 @@ impl PrometheusExporter {
         &self.registry
     }
 
+    /// Gathers the metric families of the registry, returning them with an
+    /// OpenMetrics encoder that also writes the exemplars of counters and
+    /// histogram buckets sampled from traces.
+    ///
+    /// The exemplars are those collected by this gathering, so concurrent
+    /// gatherings of the registry do not affect each other.
+    pub fn gather_openmetrics(&self) -> (Vec<prometheus::proto::MetricFamily>, OpenMetricsEncoder) {
+        let (metric_families, exemplars) = exemplars::gather(&self.registry);
+        (
+            metric_families,
+            OpenMetricsEncoder::with_exemplars(exemplars),
+        )
+    }
+
+    /// Returns the address the metrics server is listening on, if one was
+    /// configured.
+    #[cfg(feature = "hyper-server")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "hyper-server")))]
+    pub fn server_address(&self) -> Option<SocketAddr> {
+        self.server.as_ref().map(|server| server.local_addr())
+    }
+
+    /// Gracefully shut down the metrics server, if one was configured.
+    ///
+    /// In-flight scrapes are completed before the server stops, and this
+    /// returns once the listener is closed.
+    #[cfg(feature = "hyper-server")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "hyper-server")))]
+    pub fn shutdown_server(&self) {
+        if let Some(server) = self.server.as_ref() {
+            server.shutdown();
+        }
+    }
+
     /// Get this exporter's provider.
     pub fn provider(&self) -> Result<RegistryMeterProvider, MetricsError> {
         self.controller
@@ -259,24 +584,69 @@ impl PrometheusExporter {
 
     /// Determine the export kind this exporter should use for a given instrument
     /// and descriptor.
-    pub fn export_kind_for(&self, _descriptor: &Descriptor, _kind: &InstrumentKind) -> ExportKind {
-        // NOTE: Summary values should use Delta aggregation, then be
-        // combined into a sliding window, see the TODO below.
+    pub fn export_kind_for(&self, descriptor: &Descriptor, _kind: &InstrumentKind) -> ExportKind {
         // NOTE: Prometheus also supports a "GaugeDelta" exposition format,
         // which is expressed as a delta histogram.  Need to understand if this
         // should be a default behavior for ValueRecorder/ValueObserver.
-        EXPORT_KIND
+        self.export_kind.export_kind_for(descriptor)
     }
 }
 
+/// Prometheus expects cumulative values, except for summaries which are
+/// computed from deltas combined into a sliding window.
+#[derive(Clone, Copy, Debug)]
+struct PrometheusExportKind {
+    summaries: bool,
+}
+
+impl ExportKindSelector for PrometheusExportKind {
+    fn export_kind_for(&self, descriptor: &Descriptor) -> ExportKind {
+        if self.summaries && descriptor.instrument_kind() == &InstrumentKind::ValueRecorder {
+            ExportKind::Delta
+        } else {
+            ExportKind::Cumulative
+        }
+    }
+}
+
+/// The resource exported as a `target_info` metric, along with the resource
+/// keys still added to the labels of every series.
+#[derive(Debug)]
+struct TargetInfo {
+    resource: Resource,
+    resource_labels: HashSet<Key>,
+}
+
+/// Optional behaviors of the collector.
+#[derive(Debug, Default)]
+struct CollectorConfig {
+    summaries: Option<Summaries>,
+    target_info: Option<TargetInfo>,
+    naming: MetricNaming,
+}
+
 #[derive(Debug)]
 struct Collector {
     controller: Arc<Mutex<PullController>>,
+    export_kind: PrometheusExportKind,
+    summaries: Option<Mutex<Summaries>>,
+    target_info: Option<TargetInfo>,
+    naming: MetricNaming,
 }
 
 impl Collector {
-    fn with_controller(controller: Arc<Mutex<PullController>>) -> Self {
-        Collector { controller }
+    fn with_controller(
+        controller: Arc<Mutex<PullController>>,
+        export_kind: PrometheusExportKind,
+        config: CollectorConfig,
+    ) -> Self {
+        Collector {
+            controller,
+            export_kind,
+            summaries: config.summaries.map(Mutex::new),
+            target_info: config.target_info,
+            naming: config.naming,
+        }
     }
 }
 
@@ -296,20 +666,64 @@ impl prometheus::core::Collector for Collector {
                 return metrics;
             }
 
-            if let Err(err) = controller.try_for_each(&EXPORT_KIND, &mut |record| {
+            let mut summaries = match self.summaries.as_ref().map(|summaries| summaries.lock()) {
+                Some(Ok(summaries)) => Some(summaries),
+                Some(Err(err)) => {
+                    global::handle_error(MetricsError::from(err));
+                    return metrics;
+                }
+                None => None,
+            };
+            let mut exemplars = SeriesExemplars::default();
+
+            if let Err(err) = controller.try_for_each(&self.export_kind, &mut |record| {
                 let agg = record.aggregator().ok_or(MetricsError::NoDataCollected)?;
                 let number_kind = record.descriptor().number_kind();
+                let instrument_kind = record.descriptor().instrument_kind();
 
                 let mut label_keys = Vec::new();
                 let mut label_values = Vec::new();
-                merge_labels(record, &mut label_keys, Some(&mut label_values));
-
-                let desc = to_desc(&record, label_keys);
+                merge_labels(
+                    record,
+                    self.target_info
+                        .as_ref()
+                        .map(|target_info| &target_info.resource_labels),
+                    &mut label_keys,
+                    Some(&mut label_values),
+                );
+
+                let desc = to_desc(&record, self.naming, label_keys);
+
+                if let Some(summaries) = summaries.as_mut() {
+                    if summaries.update(record, &desc, &label_values)? {
+                        return Ok(());
+                    }
+                }
 
                 if let Some(hist) = agg.as_any().downcast_ref::<HistogramAggregator>() {
-                    metrics.push(build_histogram(hist, number_kind, desc, label_values)?);
+                    metrics.push(build_histogram(
+                        hist,
+                        number_kind,
+                        desc,
+                        label_values,
+                        &mut exemplars,
+                    )?);
                 } else if let Some(sum) = agg.as_any().downcast_ref::<SumAggregator>() {
-                    metrics.push(build_counter(sum, number_kind, desc, label_values)?);
+                    let counter = if instrument_kind.monotonic() {
+                        build_monotonic_counter(
+                            sum,
+                            number_kind,
+                            desc,
+                            label_values,
+                            &mut exemplars,
+                        )?
+                    } else {
+                        build_non_monotonic_counter(sum, number_kind, desc, label_values)?
+                    };
+
+                    metrics.push(counter);
+                } else if let Some(last) = agg.as_any().downcast_ref::<LastValueAggregator>() {
+                    metrics.push(build_last_value(last, number_kind, desc, label_values)?);
                 }
 
                 Ok(())
@@ -317,6 +731,21 @@ impl prometheus::core::Collector for Collector {
                 global::handle_error(err);
             }
 
+            exemplars.collected();
+
+            if let Some(summaries) = summaries.as_mut() {
+                match summaries.collect(SystemTime::now()) {
+                    Ok(mut summary_metrics) => metrics.append(&mut summary_metrics),
+                    Err(err) => global::handle_error(err),
+                }
+            }
+
+            if let Some(target_info) = self.target_info.as_ref() {
+                if !target_info.resource.is_empty() {
+                    metrics.push(build_target_info(&target_info.resource));
+                }
+            }
+
             metrics
         } else {
             Vec::new()
@@ -324,7 +753,55 @@ impl prometheus::core::Collector for Collector {
     }
 }
 
-fn build_counter(
+fn build_last_value(
+    lv: &LastValueAggregator,
+    kind: &NumberKind,
+    desc: prometheus::core::Desc,
+    labels: Vec<KeyValue>,
+) -> Result<prometheus::proto::MetricFamily, MetricsError> {
+    let (last_value, _) = lv.last_value()?;
+
+    let mut g = prometheus::proto::Gauge::default();
+    g.set_value(last_value.to_f64(kind));
+
+    let mut m = prometheus::proto::Metric::default();
+    m.set_label(protobuf::RepeatedField::from_vec(
+        labels.into_iter().map(build_label_pair).collect(),
+    ));
+    m.set_gauge(g);
+
+    let mut mf = prometheus::proto::MetricFamily::default();
+    mf.set_name(desc.fq_name);
+    mf.set_help(desc.help);
+    mf.set_field_type(prometheus::proto::MetricType::GAUGE);
+    mf.set_metric(protobuf::RepeatedField::from_vec(vec![m]));
+
+    Ok(mf)
+}
+
+fn build_target_info(resource: &Resource) -> prometheus::proto::MetricFamily {
+    let mut g = prometheus::proto::Gauge::default();
+    g.set_value(1.0);
+
+    let mut m = prometheus::proto::Metric::default();
+    m.set_label(protobuf::RepeatedField::from_vec(
+        resource
+            .iter()
+            .map(|(key, value)| build_label_pair(KeyValue::new(key.clone(), value.clone())))
+            .collect(),
+    ));
+    m.set_gauge(g);
+
+    let mut mf = prometheus::proto::MetricFamily::default();
+    mf.set_name(TARGET_INFO_NAME.to_string());
+    mf.set_help(TARGET_INFO_DESCRIPTION.to_string());
+    mf.set_field_type(prometheus::proto::MetricType::GAUGE);
+    mf.set_metric(protobuf::RepeatedField::from_vec(vec![m]));
+
+    mf
+}
+
+fn build_non_monotonic_counter(
     sum: &SumAggregator,
     kind: &NumberKind,
     desc: prometheus::core::Desc,
@@ -332,8 +809,35 @@ fn build_counter(
 ) -> Result<prometheus::proto::MetricFamily, MetricsError> {
     let sum = sum.sum()?;
 
+    let mut g = prometheus::proto::Gauge::default();
+    g.set_value(sum.to_f64(kind));
+
+    let mut m = prometheus::proto::Metric::default();
+    m.set_label(protobuf::RepeatedField::from_vec(
+        labels.into_iter().map(build_label_pair).collect(),
+    ));
+    m.set_gauge(g);
+
+    let mut mf = prometheus::proto::MetricFamily::default();
+    mf.set_name(desc.fq_name);
+    mf.set_help(desc.help);
+    mf.set_field_type(prometheus::proto::MetricType::GAUGE);
+    mf.set_metric(protobuf::RepeatedField::from_vec(vec![m]));
+
+    Ok(mf)
+}
+
+fn build_monotonic_counter(
+    sum: &SumAggregator,
+    kind: &NumberKind,
+    desc: prometheus::core::Desc,
+    labels: Vec<KeyValue>,
+    exemplars: &mut SeriesExemplars,
+) -> Result<prometheus::proto::MetricFamily, MetricsError> {
+    let value = sum.sum()?;
+
     let mut c = prometheus::proto::Counter::default();
-    c.set_value(sum.to_f64(kind));
+    c.set_value(value.to_f64(kind));
 
     let mut m = prometheus::proto::Metric::default();
     m.set_label(protobuf::RepeatedField::from_vec(
@@ -341,6 +845,10 @@ fn build_counter(
     ));
     m.set_counter(c);
 
+    for exemplar in sum.exemplars()? {
+        exemplars.insert(&desc.fq_name, m.get_label(), None, &exemplar, kind);
+    }
+
     let mut mf = prometheus::proto::MetricFamily::default();
     mf.set_name(desc.fq_name);
     mf.set_help(desc.help);
@@ -355,6 +863,7 @@ fn build_histogram(
     kind: &NumberKind,
     desc: prometheus::core::Desc,
     labels: Vec<KeyValue>,
+    exemplars: &mut SeriesExemplars,
 ) -> Result<prometheus::proto::MetricFamily, MetricsError> {
     let raw_buckets = hist.histogram()?;
     let sum = hist.sum()?;
@@ -382,6 +891,23 @@ fn build_histogram(
     ));
     m.set_histogram(h);
 
+    for exemplar in hist.exemplars()? {
+        // The exemplar belongs to the bucket its value was counted in.
+        let value = exemplar.value().to_f64(kind);
+        let upper_bound = raw_buckets
+            .boundaries()
+            .iter()
+            .find(|boundary| value < **boundary)
+            .map_or("+Inf".into(), |boundary| format_float(*boundary));
+        exemplars.insert(
+            &desc.fq_name,
+            m.get_label(),
+            Some(("le", &*upper_bound)),
+            &exemplar,
+            kind,
+        );
+    }
+
     let mut mf = prometheus::proto::MetricFamily::default();
     mf.set_name(desc.fq_name);
     mf.set_help(desc.help);
@@ -391,23 +917,29 @@ fn build_histogram(
     Ok(mf)
 }
 
-fn build_label_pair(label: KeyValue) -> prometheus::proto::LabelPair {
+pub(crate) fn build_label_pair(label: KeyValue) -> prometheus::proto::LabelPair {
     let mut lp = prometheus::proto::LabelPair::new();
-    lp.set_name(label.key.into());
+    lp.set_name(sanitize(label.key.as_str()));
     lp.set_value(label.value.into());
 
     lp
 }
 
+/// Merge the record labels with the resource attributes, restricted to
+/// `resource_labels` if given.
 fn merge_labels(
     record: &Record<'_>,
+    resource_labels: Option<&HashSet<Key>>,
     keys: &mut Vec<String>,
     mut values: Option<&mut Vec<KeyValue>>,
 ) {
     // Duplicate keys are resolved by taking the record label value over
     // the resource value.
 
-    let iter = labels::merge_iters(record.labels().iter(), record.resource().iter());
+    let resource = record.resource().iter().filter(|(key, _)| {
+        resource_labels.map_or(true, |resource_labels| resource_labels.contains(key))
+    });
+    let iter = labels::merge_iters(record.labels().iter(), resource);
     for (key, value) in iter {
         keys.push(sanitize(key.as_str()));
         if let Some(ref mut values) = values {
@@ -416,10 +948,14 @@ fn merge_labels(
     }
 }
 
-fn to_desc(record: &Record<'_>, label_keys: Vec<String>) -> prometheus::core::Desc {
+fn to_desc(
+    record: &Record<'_>,
+    naming: MetricNaming,
+    label_keys: Vec<String>,
+) -> prometheus::core::Desc {
     let desc = record.descriptor();
     prometheus::core::Desc::new(
-        sanitize(desc.name()),
+        naming.name(desc),
         desc.description()
             .cloned()
             .unwrap_or_else(|| desc.name().to_string()),
@@ -0,0 +1,241 @@
+//! Prometheus metric naming conventions.
+use crate::sanitize;
+use opentelemetry::metrics::Descriptor;
+
+/// Suffix of monotonic counters.
+const COUNTER_SUFFIX: &str = "total";
+
+/// Builds metric names from instrument descriptors following the Prometheus
+/// naming conventions, e.g. `http_request_duration_seconds` and
+/// `http_requests_total`.
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct MetricNaming {
+    /// Append the unit of the instrument to the metric name.
+    pub(crate) units: bool,
+    /// Append `_total` to the name of monotonic counters.
+    pub(crate) counter_suffixes: bool,
+}
+
+impl Default for MetricNaming {
+    fn default() -> Self {
+        MetricNaming {
+            units: true,
+            counter_suffixes: true,
+        }
+    }
+}
+
+impl MetricNaming {
+    /// The name of the metric exporting the given instrument.
+    pub(crate) fn name(&self, descriptor: &Descriptor) -> String {
+        let mut name = sanitize(descriptor.name());
+
+        if self.units {
+            if let Some(unit) = descriptor.unit().and_then(unit_suffix) {
+                append_suffix(&mut name, &unit);
+            }
+        }
+
+        if self.counter_suffixes && descriptor.instrument_kind().monotonic() {
+            append_suffix(&mut name, COUNTER_SUFFIX);
+        }
+
+        name
+    }
+}
+
+/// Appends `_suffix` to the name unless it already ends with it.
+fn append_suffix(name: &mut String, suffix: &str) {
+    let has_suffix = name
+        .strip_suffix(suffix)
+        .map_or(false, |prefix| prefix.ends_with('_'));
+    if !has_suffix {
+        name.push('_');
+        name.push_str(suffix);
+    }
+}
+
+/// Converts a [UCUM] unit into the suffix of a Prometheus metric name, e.g.
+/// `By/s` into `bytes_per_second`.
+///
+/// Annotations in curly braces are dropped and unknown units are kept as is,
+/// with invalid characters replaced by underscores.
+///
+/// [UCUM]: https://ucum.org/ucum.html
+fn unit_suffix(unit: &str) -> Option<String> {
+    let unit = strip_annotations(unit);
+    let mut parts = unit.splitn(2, '/');
+    let numerator = parts.next().map(str::trim).unwrap_or("");
+    let denominator = parts.next().map(str::trim).unwrap_or("");
+
+    let suffix = match (numerator, denominator) {
+        ("", "") | ("1", "") => return None,
+        (numerator, "") => unit_name(numerator, false),
+        ("", denominator) | ("1", denominator) => {
+            format!("per_{}", unit_name(denominator, true))
+        }
+        (numerator, denominator) => format!(
+            "{}_per_{}",
+            unit_name(numerator, false),
+            unit_name(denominator, true)
+        ),
+    };
+
+    let suffix: String = suffix
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+        .collect();
+    let suffix = suffix.trim_matches('_');
+    if suffix.is_empty() {
+        None
+    } else {
+        Some(suffix.to_string())
+    }
+}
+
+fn strip_annotations(unit: &str) -> String {
+    let mut stripped = String::with_capacity(unit.len());
+    let mut depth = 0;
+    for c in unit.chars() {
+        match c {
+            '{' => depth += 1,
+            '}' if depth > 0 => depth -= 1,
+            _ if depth == 0 => stripped.push(c),
+            _ => {}
+        }
+    }
+    stripped
+}
+
+fn unit_name(unit: &str, singular: bool) -> String {
+    let (plural, one) = match unit {
+        // Time
+        "d" => ("days", "day"),
+        "h" => ("hours", "hour"),
+        "min" => ("minutes", "minute"),
+        "s" => ("seconds", "second"),
+        "ms" => ("milliseconds", "millisecond"),
+        "us" => ("microseconds", "microsecond"),
+        "ns" => ("nanoseconds", "nanosecond"),
+
+        // Bytes
+        "By" => ("bytes", "byte"),
+        "KiBy" => ("kibibytes", "kibibyte"),
+        "MiBy" => ("mebibytes", "mebibyte"),
+        "GiBy" => ("gibibytes", "gibibyte"),
+        "TiBy" => ("tibibytes", "tibibyte"),
+        "KBy" => ("kilobytes", "kilobyte"),
+        "MBy" => ("megabytes", "megabyte"),
+        "GBy" => ("gigabytes", "gigabyte"),
+        "TBy" => ("terabytes", "terabyte"),
+
+        // SI
+        "m" => ("meters", "meter"),
+        "V" => ("volts", "volt"),
+        "A" => ("amperes", "ampere"),
+        "J" => ("joules", "joule"),
+        "W" => ("watts", "watt"),
+        "g" => ("grams", "gram"),
+
+        // Misc
+        "Cel" => ("celsius", "celsius"),
+        "Hz" => ("hertz", "hertz"),
+        "%" => ("percent", "percent"),
+        "1" => ("ratio", "ratio"),
+
+        unit => return unit.to_string(),
+    };
+
+    if singular { one } else { plural }.to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use opentelemetry::metrics::{InstrumentKind, NumberKind};
+    use opentelemetry::Unit;
+
+    fn descriptor(name: &str, kind: InstrumentKind, unit: Option<&str>) -> Descriptor {
+        let mut descriptor =
+            Descriptor::new(name.to_string(), "test".to_string(), kind, NumberKind::F64);
+        if let Some(unit) = unit {
+            descriptor.set_unit(Unit::new(unit));
+        }
+        descriptor
+    }
+
+    #[test]
+    fn unit_suffixes() {
+        let data = vec![
+            ("s", Some("seconds")),
+            ("By", Some("bytes")),
+            ("By/s", Some("bytes_per_second")),
+            ("1/s", Some("per_second")),
+            ("{requests}/min", Some("per_minute")),
+            ("%", Some("percent")),
+            ("1", None),
+            ("{requests}", None),
+            ("packets", Some("packets")),
+        ];
+
+        for (unit, expected) in data {
+            assert_eq!(
+                unit_suffix(unit).as_deref(),
+                expected,
+                "unexpected suffix for {}",
+                unit
+            );
+        }
+    }
+
+    #[test]
+    fn metric_names() {
+        let naming = MetricNaming::default();
+        let data = vec![
+            (
+                "http.server.duration",
+                InstrumentKind::ValueRecorder,
+                Some("s"),
+                "http_server_duration_seconds",
+            ),
+            ("requests", InstrumentKind::Counter, None, "requests_total"),
+            (
+                "requests_total",
+                InstrumentKind::Counter,
+                None,
+                "requests_total",
+            ),
+            (
+                "sent",
+                InstrumentKind::SumObserver,
+                Some("By"),
+                "sent_bytes_total",
+            ),
+            (
+                "queue.size",
+                InstrumentKind::UpDownCounter,
+                Some("{items}"),
+                "queue_size",
+            ),
+            (
+                "latency_seconds",
+                InstrumentKind::ValueRecorder,
+                Some("s"),
+                "latency_seconds",
+            ),
+        ];
+
+        for (name, kind, unit, expected) in data {
+            assert_eq!(naming.name(&descriptor(name, kind, unit)), expected);
+        }
+
+        let naming = MetricNaming {
+            units: false,
+            counter_suffixes: false,
+        };
+        assert_eq!(
+            naming.name(&descriptor("sent", InstrumentKind::Counter, Some("By"))),
+            "sent"
+        );
+    }
+}
@@ -0,0 +1,333 @@
+//! OpenMetrics text exposition format.
+use crate::exemplars::{SeriesExemplar, SeriesExemplars};
+use prometheus::proto::{self, MetricFamily, MetricType};
+use prometheus::Encoder;
+use std::borrow::Cow;
+use std::io::Write;
+
+/// The content type of the OpenMetrics text format.
+pub const OPENMETRICS_FORMAT: &str = "application/openmetrics-text; version=1.0.0; charset=utf-8";
+
+/// An implementation of `prometheus::Encoder` that converts metric families
+/// into the [OpenMetrics] text format.
+///
+/// Encoders returned by [`PrometheusExporter::gather_openmetrics`] also
+/// write the exemplars of counters and histogram buckets collected with the
+/// gathered metric families.
+///
+/// [OpenMetrics]: https://github.com/OpenObservability/OpenMetrics/blob/main/specification/OpenMetrics.md
+/// [`PrometheusExporter::gather_openmetrics`]: crate::PrometheusExporter::gather_openmetrics
+#[derive(Debug, Default)]
+pub struct OpenMetricsEncoder {
+    exemplars: Option<SeriesExemplars>,
+}
+
+impl OpenMetricsEncoder {
+    /// Create a new OpenMetrics encoder.
+    pub fn new() -> Self {
+        OpenMetricsEncoder::default()
+    }
+
+    pub(crate) fn with_exemplars(exemplars: SeriesExemplars) -> Self {
+        OpenMetricsEncoder {
+            exemplars: Some(exemplars),
+        }
+    }
+}
+
+impl Encoder for OpenMetricsEncoder {
+    fn encode<W: Write>(
+        &self,
+        metric_families: &[MetricFamily],
+        writer: &mut W,
+    ) -> prometheus::Result<()> {
+        let exemplar = |name: &str, m: &proto::Metric, additional_label: Option<(&str, &str)>| {
+            self.exemplars
+                .as_ref()
+                .and_then(|exemplars| exemplars.get(name, m.get_label(), additional_label))
+        };
+
+        for mf in metric_families {
+            let metric_type = mf.get_field_type();
+            let name = mf.get_name();
+            // Counter families are named without the `_total` suffix of their
+            // samples.
+            let family_name = match metric_type {
+                MetricType::COUNTER => name.strip_suffix("_total").unwrap_or(name),
+                _ => name,
+            };
+
+            if !mf.get_help().is_empty() {
+                writeln!(
+                    writer,
+                    "# HELP {} {}",
+                    family_name,
+                    escape_string(mf.get_help())
+                )?;
+            }
+            let type_name = match metric_type {
+                MetricType::COUNTER => "counter",
+                MetricType::GAUGE => "gauge",
+                MetricType::SUMMARY => "summary",
+                MetricType::HISTOGRAM => "histogram",
+                MetricType::UNTYPED => "unknown",
+            };
+            writeln!(writer, "# TYPE {} {}", family_name, type_name)?;
+
+            for m in mf.get_metric() {
+                match metric_type {
+                    MetricType::COUNTER => {
+                        let value = m.get_counter().get_value();
+                        let exemplar = exemplar(name, m, None);
+                        write_sample(writer, family_name, "_total", m, None, value, exemplar)?;
+                    }
+                    MetricType::GAUGE => {
+                        let value = m.get_gauge().get_value();
+                        write_sample(writer, name, "", m, None, value, None)?;
+                    }
+                    MetricType::UNTYPED => {
+                        let value = m.get_untyped().get_value();
+                        write_sample(writer, name, "", m, None, value, None)?;
+                    }
+                    MetricType::HISTOGRAM => {
+                        let h = m.get_histogram();
+
+                        let mut inf_seen = false;
+                        for b in h.get_bucket() {
+                            let upper_bound = b.get_upper_bound();
+                            inf_seen |= upper_bound.is_infinite() && upper_bound.is_sign_positive();
+                            let upper_bound = format_float(upper_bound);
+                            let le = Some(("le", &*upper_bound));
+                            write_sample(
+                                writer,
+                                name,
+                                "_bucket",
+                                m,
+                                le,
+                                b.get_cumulative_count() as f64,
+                                exemplar(name, m, le),
+                            )?;
+                        }
+                        if !inf_seen {
+                            let le = Some(("le", "+Inf"));
+                            write_sample(
+                                writer,
+                                name,
+                                "_bucket",
+                                m,
+                                le,
+                                h.get_sample_count() as f64,
+                                exemplar(name, m, le),
+                            )?;
+                        }
+                        let count = h.get_sample_count() as f64;
+                        write_sample(writer, name, "_count", m, None, count, None)?;
+                        write_sample(writer, name, "_sum", m, None, h.get_sample_sum(), None)?;
+                    }
+                    MetricType::SUMMARY => {
+                        let s = m.get_summary();
+
+                        for q in s.get_quantile() {
+                            write_sample(
+                                writer,
+                                name,
+                                "",
+                                m,
+                                Some(("quantile", &format_float(q.get_quantile()))),
+                                q.get_value(),
+                                None,
+                            )?;
+                        }
+                        let count = s.get_sample_count() as f64;
+                        write_sample(writer, name, "_count", m, None, count, None)?;
+                        write_sample(writer, name, "_sum", m, None, s.get_sample_sum(), None)?;
+                    }
+                }
+            }
+        }
+
+        writer.write_all(b"# EOF\n")?;
+
+        Ok(())
+    }
+
+    fn format_type(&self) -> &str {
+        OPENMETRICS_FORMAT
+    }
+}
+
+fn write_sample<W: Write>(
+    writer: &mut W,
+    name: &str,
+    suffix: &str,
+    m: &proto::Metric,
+    additional_label: Option<(&str, &str)>,
+    value: f64,
+    exemplar: Option<&SeriesExemplar>,
+) -> prometheus::Result<()> {
+    writer.write_all(name.as_bytes())?;
+    writer.write_all(suffix.as_bytes())?;
+
+    let mut separator = "{";
+    for lp in m.get_label() {
+        write!(
+            writer,
+            "{}{}=\"{}\"",
+            separator,
+            lp.get_name(),
+            escape_string(lp.get_value())
+        )?;
+        separator = ",";
+    }
+    if let Some((name, value)) = additional_label {
+        write!(writer, "{}{}=\"{}\"", separator, name, escape_string(value))?;
+        separator = ",";
+    }
+    if separator == "," {
+        writer.write_all(b"}")?;
+    }
+
+    write!(writer, " {}", format_float(value))?;
+
+    // OpenMetrics timestamps are expressed in seconds.
+    let timestamp = m.get_timestamp_ms();
+    if timestamp != 0 {
+        write!(writer, " {}", format_float(timestamp as f64 / 1000.0))?;
+    }
+
+    if let Some(exemplar) = exemplar {
+        writer.write_all(b" # ")?;
+        let mut separator = "{";
+        for (name, value) in &exemplar.labels {
+            write!(writer, "{}{}=\"{}\"", separator, name, escape_string(value))?;
+            separator = ",";
+        }
+        if separator == "{" {
+            writer.write_all(b"{")?;
+        }
+        write!(
+            writer,
+            "}} {} {}",
+            format_float(exemplar.value),
+            format_float(exemplar.timestamp)
+        )?;
+    }
+
+    writer.write_all(b"\n")?;
+
+    Ok(())
+}
+
+pub(crate) fn format_float(value: f64) -> Cow<'static, str> {
+    if value.is_nan() {
+        "NaN".into()
+    } else if value.is_infinite() {
+        if value.is_sign_positive() {
+            "+Inf".into()
+        } else {
+            "-Inf".into()
+        }
+    } else {
+        value.to_string().into()
+    }
+}
+
+/// Escapes `\`, new lines and `"` in label values and help texts.
+fn escape_string(v: &str) -> Cow<'_, str> {
+    if !v.contains(&['\\', '\n', '"'][..]) {
+        return v.into();
+    }
+
+    let mut escaped = String::with_capacity(v.len() * 2);
+    for c in v.chars() {
+        match c {
+            '\\' => escaped.push_str("\\\\"),
+            '\n' => escaped.push_str("\\n"),
+            '"' => escaped.push_str("\\\""),
+            _ => escaped.push(c),
+        }
+    }
+    escaped.into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn label(name: &str, value: &str) -> proto::LabelPair {
+        let mut lp = proto::LabelPair::new();
+        lp.set_name(name.to_string());
+        lp.set_value(value.to_string());
+        lp
+    }
+
+    #[test]
+    fn encode_counter_and_histogram() {
+        let mut c = proto::Counter::default();
+        c.set_value(3.0);
+        let mut m = proto::Metric::default();
+        m.set_label(protobuf::RepeatedField::from_vec(vec![label("a", "x\"y")]));
+        m.set_counter(c);
+        let mut counter = MetricFamily::default();
+        counter.set_name("requests_total".to_string());
+        counter.set_help("Requests\nserved".to_string());
+        counter.set_field_type(MetricType::COUNTER);
+        counter.set_metric(protobuf::RepeatedField::from_vec(vec![m]));
+
+        let mut b = proto::Bucket::default();
+        b.set_upper_bound(0.5);
+        b.set_cumulative_count(1);
+        let mut h = proto::Histogram::default();
+        h.set_bucket(protobuf::RepeatedField::from_vec(vec![b]));
+        h.set_sample_count(2);
+        h.set_sample_sum(1.25);
+        let mut m = proto::Metric::default();
+        m.set_histogram(h);
+        let mut histogram = MetricFamily::default();
+        histogram.set_name("latency".to_string());
+        histogram.set_field_type(MetricType::HISTOGRAM);
+        histogram.set_metric(protobuf::RepeatedField::from_vec(vec![m]));
+
+        let mut output = Vec::new();
+        OpenMetricsEncoder::new()
+            .encode(&[counter, histogram], &mut output)
+            .unwrap();
+
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            "# HELP requests Requests\\nserved\n\
+             # TYPE requests counter\n\
+             requests_total{a=\"x\\\"y\"} 3\n\
+             # TYPE latency histogram\n\
+             latency_bucket{le=\"0.5\"} 1\n\
+             latency_bucket{le=\"+Inf\"} 2\n\
+             latency_count 2\n\
+             latency_sum 1.25\n\
+             # EOF\n"
+        );
+    }
+
+    #[test]
+    fn strip_one_total_suffix() {
+        let mut c = proto::Counter::default();
+        c.set_value(1.0);
+        let mut m = proto::Metric::default();
+        m.set_counter(c);
+        let mut counter = MetricFamily::default();
+        counter.set_name("retries_total_total".to_string());
+        counter.set_field_type(MetricType::COUNTER);
+        counter.set_metric(protobuf::RepeatedField::from_vec(vec![m]));
+
+        let mut output = Vec::new();
+        OpenMetricsEncoder::new()
+            .encode(&[counter], &mut output)
+            .unwrap();
+
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            "# TYPE retries_total counter\n\
+             retries_total_total 1\n\
+             # EOF\n"
+        );
+    }
+}
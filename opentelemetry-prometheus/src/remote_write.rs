@@ -0,0 +1,545 @@
+//! # Prometheus Remote Write Exporter
+//!
+//! Pushes metrics to an endpoint implementing the Prometheus [remote write]
+//! protocol, for processes that cannot be scraped such as batch jobs.
+//!
+//! Requests are sent by a [`RemoteWriteClient`]. With the `remote-write-reqwest`
+//! feature, an async `reqwest` client running on a tokio runtime of its own is
+//! used by default.
+//!
+//! [remote write]: https://prometheus.io/docs/prometheus/latest/storage/#remote-storage-integrations
+use crate::{merge_labels, openmetrics::format_float, MetricNaming};
+use futures::Stream;
+use http::{header, HeaderMap, HeaderValue, Method, Request, Uri};
+use opentelemetry::global;
+use opentelemetry::metrics::{Descriptor, MetricsError, NumberKind};
+use opentelemetry::sdk::{
+    export::metrics::{
+        CheckpointSet, ExportKind, ExportKindSelector, Exporter, Histogram, LastValue, Record, Sum,
+    },
+    metrics::{
+        aggregators::{HistogramAggregator, LastValueAggregator, SumAggregator},
+        controllers::{self, PushController, PushControllerWorker},
+        selectors::simple::Selector,
+    },
+    Resource,
+};
+use protobuf::{CodedOutputStream, ProtobufResult};
+use std::error::Error;
+use std::fmt;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Default remote write endpoint of a local Prometheus server.
+const DEFAULT_ENDPOINT: &str = "http://localhost:9090/api/v1/write";
+/// Version of the remote write protocol implemented by this exporter.
+const REMOTE_WRITE_VERSION: &str = "0.1.0";
+
+/// Create a new remote write exporter builder.
+pub fn remote_write<S, SO, I, IS, ISI>(spawn: S, interval: I) -> RemoteWriteExporterBuilder<S, I>
+where
+    S: Fn(PushControllerWorker) -> SO,
+    I: Fn(Duration) -> IS,
+    IS: Stream<Item = ISI> + Send + 'static,
+{
+    RemoteWriteExporterBuilder {
+        spawn,
+        interval,
+        endpoint: None,
+        headers: Vec::new(),
+        basic_auth: None,
+        client: None,
+        resource: None,
+        period: None,
+        default_histogram_boundaries: None,
+    }
+}
+
+/// A minimal interface necessary to send remote write requests.
+///
+/// Metrics are exported synchronously on the push controller's export thread,
+/// so the request must be sent before this call returns. Users can bring their
+/// own http client by implementing this trait.
+pub trait RemoteWriteClient: fmt::Debug + Send + Sync {
+    /// Send a write request, returning an error if the request fails or is not
+    /// accepted by the server.
+    fn send(&self, request: Request<Vec<u8>>)
+        -> Result<(), Box<dyn Error + Send + Sync + 'static>>;
+}
+
+/// Sends requests with an async `reqwest` client, waiting for their responses
+/// on the export thread.
+///
+/// The client runs on its own thread and single threaded runtime, so requests
+/// complete even when the application's runtime is blocked or shutting down,
+/// e.g. by a final push on `PushController::shutdown`.
+#[cfg(feature = "remote-write-reqwest")]
+#[derive(Debug)]
+struct ReqwestClient {
+    client: reqwest::Client,
+    runtime: tokio::runtime::Handle,
+    shutdown: Option<tokio::sync::oneshot::Sender<()>>,
+    thread: Option<std::thread::JoinHandle<()>>,
+}
+
+#[cfg(feature = "remote-write-reqwest")]
+impl ReqwestClient {
+    fn new() -> Result<Self, MetricsError> {
+        let mut runtime = tokio::runtime::Builder::new()
+            .basic_scheduler()
+            .enable_all()
+            .build()
+            .map_err(|err| MetricsError::Other(err.to_string()))?;
+        let handle = runtime.handle().clone();
+        // Built in the runtime, as the client starts its connection pool.
+        let client = runtime.enter(reqwest::Client::new);
+
+        let (tx, rx) = tokio::sync::oneshot::channel::<()>();
+        let thread = std::thread::Builder::new()
+            .name("opentelemetry-prometheus-remote-write".to_string())
+            .spawn(move || {
+                // Also shuts down if the sender is dropped.
+                let _ = runtime.block_on(rx);
+            })
+            .map_err(|err| MetricsError::Other(err.to_string()))?;
+
+        Ok(ReqwestClient {
+            client,
+            runtime: handle,
+            shutdown: Some(tx),
+            thread: Some(thread),
+        })
+    }
+}
+
+#[cfg(feature = "remote-write-reqwest")]
+impl RemoteWriteClient for ReqwestClient {
+    fn send(
+        &self,
+        request: Request<Vec<u8>>,
+    ) -> Result<(), Box<dyn Error + Send + Sync + 'static>> {
+        use std::convert::TryInto;
+
+        let request = request.try_into()?;
+        let client = self.client.clone();
+        let response = self.runtime.spawn(async move {
+            client
+                .execute(request)
+                .await
+                .and_then(reqwest::Response::error_for_status)
+        });
+        futures::executor::block_on(response).map_err(|err| err.to_string())??;
+
+        Ok(())
+    }
+}
+
+#[cfg(feature = "remote-write-reqwest")]
+impl Drop for ReqwestClient {
+    fn drop(&mut self) {
+        if let Some(tx) = self.shutdown.take() {
+            let _ = tx.send(());
+        }
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+/// Configuration for the remote write exporter.
+#[derive(Debug)]
+pub struct RemoteWriteExporterBuilder<S, I> {
+    spawn: S,
+    interval: I,
+    endpoint: Option<String>,
+    headers: Vec<(String, String)>,
+    basic_auth: Option<(String, String)>,
+    client: Option<Box<dyn RemoteWriteClient>>,
+    resource: Option<Resource>,
+    period: Option<Duration>,
+    default_histogram_boundaries: Option<Vec<f64>>,
+}
+
+impl<S, SO, I, IS, ISI> RemoteWriteExporterBuilder<S, I>
+where
+    S: Fn(PushControllerWorker) -> SO,
+    I: Fn(Duration) -> IS,
+    IS: Stream<Item = ISI> + Send + 'static,
+{
+    /// Set the url of the remote write endpoint.
+    ///
+    /// Defaults to `http://localhost:9090/api/v1/write`.
+    pub fn with_endpoint<T: Into<String>>(self, endpoint: T) -> Self {
+        RemoteWriteExporterBuilder {
+            endpoint: Some(endpoint.into()),
+            ..self
+        }
+    }
+
+    /// Add a header sent with every write request, e.g. a tenant id.
+    pub fn with_header<K: Into<String>, V: Into<String>>(mut self, name: K, value: V) -> Self {
+        self.headers.push((name.into(), value.into()));
+        self
+    }
+
+    /// Authenticate write requests with the given basic auth credentials.
+    pub fn with_basic_auth<U: Into<String>, P: Into<String>>(
+        self,
+        username: U,
+        password: P,
+    ) -> Self {
+        RemoteWriteExporterBuilder {
+            basic_auth: Some((username.into(), password.into())),
+            ..self
+        }
+    }
+
+    /// Choose the http client used to send write requests.
+    ///
+    /// Defaults to an async `reqwest` client running on a thread of its own if
+    /// the `remote-write-reqwest` feature is enabled.
+    pub fn with_http_client<T: RemoteWriteClient + 'static>(self, client: T) -> Self {
+        RemoteWriteExporterBuilder {
+            client: Some(Box::new(client)),
+            ..self
+        }
+    }
+
+    /// Set the resource to be associated with all `Meter`s for this exporter
+    pub fn with_resource(self, resource: Resource) -> Self {
+        RemoteWriteExporterBuilder {
+            resource: Some(resource),
+            ..self
+        }
+    }
+
+    /// Set the frequency in which metrics are exported.
+    pub fn with_period(self, period: Duration) -> Self {
+        RemoteWriteExporterBuilder {
+            period: Some(period),
+            ..self
+        }
+    }
+
+    /// Set the default boundaries to be used by exported histograms
+    pub fn with_default_histogram_boundaries(self, boundaries: Vec<f64>) -> Self {
+        RemoteWriteExporterBuilder {
+            default_histogram_boundaries: Some(boundaries),
+            ..self
+        }
+    }
+
+    /// Build a new push controller, returning errors if they arise.
+    pub fn try_init(mut self) -> Result<PushController, MetricsError> {
+        let period = self.period.take();
+        let resource = self.resource.take();
+        let boundaries = self
+            .default_histogram_boundaries
+            .take()
+            .unwrap_or_else(|| vec![0.5, 0.9, 0.99]);
+        let (spawn, interval, exporter) = self.try_build()?;
+
+        let mut push_builder = controllers::push(
+            Selector::Histogram(boundaries),
+            ExportKind::Cumulative,
+            exporter,
+            spawn,
+            interval,
+        );
+        if let Some(period) = period {
+            push_builder = push_builder.with_period(period);
+        }
+        if let Some(resource) = resource {
+            push_builder = push_builder.with_resource(resource);
+        }
+
+        let controller = push_builder.build();
+        global::set_meter_provider(controller.provider());
+        Ok(controller)
+    }
+
+    /// Build a new push controller.
+    ///
+    /// # Panics
+    ///
+    /// This panics if the endpoint, headers or http client are invalid or
+    /// missing.
+    pub fn init(self) -> PushController {
+        self.try_init().unwrap()
+    }
+
+    fn try_build(self) -> Result<(S, I, RemoteWriteExporter), MetricsError> {
+        let endpoint = self
+            .endpoint
+            .as_deref()
+            .unwrap_or(DEFAULT_ENDPOINT)
+            .parse::<Uri>()
+            .map_err(|err| MetricsError::Other(err.to_string()))?;
+
+        let mut headers = HeaderMap::new();
+        for (name, value) in self.headers {
+            headers.append(
+                header::HeaderName::from_bytes(name.as_bytes())
+                    .map_err(|err| MetricsError::Other(err.to_string()))?,
+                HeaderValue::from_str(&value)
+                    .map_err(|err| MetricsError::Other(err.to_string()))?,
+            );
+        }
+        if let Some((username, password)) = self.basic_auth {
+            let credentials = base64::encode(format!("{}:{}", username, password));
+            headers.insert(
+                header::AUTHORIZATION,
+                HeaderValue::from_str(&format!("Basic {}", credentials))
+                    .map_err(|err| MetricsError::Other(err.to_string()))?,
+            );
+        }
+
+        #[cfg(feature = "remote-write-reqwest")]
+        let client = match self.client {
+            Some(client) => client,
+            None => Box::new(ReqwestClient::new()?),
+        };
+        #[cfg(not(feature = "remote-write-reqwest"))]
+        let client = self.client.ok_or_else(|| {
+            MetricsError::Other(
+                "http client must be set, users can enable the remote-write-reqwest feature \
+                 to use the http client implementation within the crate"
+                    .to_string(),
+            )
+        })?;
+
+        Ok((
+            self.spawn,
+            self.interval,
+            RemoteWriteExporter {
+                endpoint,
+                headers,
+                client,
+            },
+        ))
+    }
+}
+
+/// An implementation of `metrics::Exporter` that pushes metrics to a
+/// Prometheus remote write endpoint.
+#[derive(Debug)]
+pub struct RemoteWriteExporter {
+    endpoint: Uri,
+    headers: HeaderMap,
+    client: Box<dyn RemoteWriteClient>,
+}
+
+impl ExportKindSelector for RemoteWriteExporter {
+    fn export_kind_for(&self, _descriptor: &Descriptor) -> ExportKind {
+        // Prometheus expects cumulative values.
+        ExportKind::Cumulative
+    }
+}
+
+impl Exporter for RemoteWriteExporter {
+    fn export(&self, checkpoint_set: &mut dyn CheckpointSet) -> Result<(), MetricsError> {
+        let mut series = Vec::new();
+        checkpoint_set.try_for_each(self, &mut |record| append_series(record, &mut series))?;
+
+        if series.is_empty() {
+            return Ok(());
+        }
+
+        let body = snap::raw::Encoder::new()
+            .compress_vec(
+                &encode_write_request(&series)
+                    .map_err(|err| MetricsError::Other(err.to_string()))?,
+            )
+            .map_err(|err| MetricsError::Other(err.to_string()))?;
+
+        let mut request = Request::builder()
+            .method(Method::POST)
+            .uri(self.endpoint.clone())
+            .header(header::CONTENT_TYPE, "application/x-protobuf")
+            .header(header::CONTENT_ENCODING, "snappy")
+            .header("X-Prometheus-Remote-Write-Version", REMOTE_WRITE_VERSION)
+            .body(body)
+            .map_err(|err| MetricsError::Other(err.to_string()))?;
+        for (name, value) in self.headers.iter() {
+            request.headers_mut().append(name, value.clone());
+        }
+
+        self.client
+            .send(request)
+            .map_err(|err| MetricsError::Other(err.to_string()))
+    }
+}
+
+/// A remote write time series, with labels sorted by name.
+#[derive(Debug)]
+struct TimeSeries {
+    labels: Vec<(String, String)>,
+    value: f64,
+    timestamp: i64,
+}
+
+impl TimeSeries {
+    fn new(name: String, mut labels: Vec<(String, String)>, value: f64, time: &SystemTime) -> Self {
+        labels.push(("__name__".to_string(), name));
+        labels.sort_by(|a, b| a.0.cmp(&b.0));
+
+        TimeSeries {
+            labels,
+            value,
+            timestamp: to_millis(time),
+        }
+    }
+}
+
+fn append_series(record: &Record<'_>, series: &mut Vec<TimeSeries>) -> Result<(), MetricsError> {
+    let agg = record.aggregator().ok_or(MetricsError::NoDataCollected)?;
+    let descriptor = record.descriptor();
+    let kind: &NumberKind = descriptor.number_kind();
+    let name = MetricNaming::default().name(descriptor);
+
+    let mut label_keys = Vec::new();
+    let mut label_values = Vec::new();
+    merge_labels(record, None, &mut label_keys, Some(&mut label_values));
+    let labels: Vec<(String, String)> = label_keys
+        .into_iter()
+        .zip(label_values.into_iter().map(|kv| kv.value.into()))
+        .collect();
+
+    if let Some(hist) = agg.as_any().downcast_ref::<HistogramAggregator>() {
+        let buckets = hist.histogram()?;
+        let end = record.end_time();
+
+        let mut count = 0.0;
+        for (upper_bound, bucket_count) in buckets.boundaries().iter().zip(buckets.counts()) {
+            count += bucket_count;
+            series.push(bucket(
+                &name,
+                &labels,
+                &format_float(*upper_bound),
+                count,
+                end,
+            ));
+        }
+        // Include the +inf bucket in the total count.
+        count += buckets.counts()[buckets.counts().len() - 1];
+        series.push(bucket(&name, &labels, "+Inf", count, end));
+        series.push(TimeSeries::new(
+            format!("{}_count", name),
+            labels.clone(),
+            count,
+            end,
+        ));
+        series.push(TimeSeries::new(
+            format!("{}_sum", name),
+            labels,
+            hist.sum()?.to_f64(kind),
+            end,
+        ));
+    } else if let Some(sum) = agg.as_any().downcast_ref::<SumAggregator>() {
+        series.push(TimeSeries::new(
+            name,
+            labels,
+            sum.sum()?.to_f64(kind),
+            record.end_time(),
+        ));
+    } else if let Some(last) = agg.as_any().downcast_ref::<LastValueAggregator>() {
+        let (value, time) = last.last_value()?;
+        series.push(TimeSeries::new(name, labels, value.to_f64(kind), &time));
+    }
+
+    Ok(())
+}
+
+fn bucket(
+    name: &str,
+    labels: &[(String, String)],
+    upper_bound: &str,
+    count: f64,
+    time: &SystemTime,
+) -> TimeSeries {
+    let mut labels = labels.to_vec();
+    labels.push(("le".to_string(), upper_bound.to_string()));
+    TimeSeries::new(format!("{}_bucket", name), labels, count, time)
+}
+
+fn to_millis(time: &SystemTime) -> i64 {
+    time.duration_since(UNIX_EPOCH)
+        .map(|since_epoch| since_epoch.as_millis() as i64)
+        .unwrap_or(0)
+}
+
+/// Encode the series as a remote write `WriteRequest` protobuf message.
+///
+/// ```text
+/// message WriteRequest { repeated TimeSeries timeseries = 1; }
+/// message TimeSeries { repeated Label labels = 1; repeated Sample samples = 2; }
+/// message Label { string name = 1; string value = 2; }
+/// message Sample { double value = 1; int64 timestamp = 2; }
+/// ```
+fn encode_write_request(series: &[TimeSeries]) -> ProtobufResult<Vec<u8>> {
+    let mut request = Vec::new();
+    {
+        let mut request_stream = CodedOutputStream::vec(&mut request);
+        for ts in series {
+            let mut time_series = Vec::new();
+            {
+                let mut ts_stream = CodedOutputStream::vec(&mut time_series);
+                for (name, value) in ts.labels.iter() {
+                    let mut label = Vec::new();
+                    {
+                        let mut label_stream = CodedOutputStream::vec(&mut label);
+                        label_stream.write_string(1, name)?;
+                        label_stream.write_string(2, value)?;
+                        label_stream.flush()?;
+                    }
+                    ts_stream.write_bytes(1, &label)?;
+                }
+
+                let mut sample = Vec::new();
+                {
+                    let mut sample_stream = CodedOutputStream::vec(&mut sample);
+                    sample_stream.write_double(1, ts.value)?;
+                    sample_stream.write_int64(2, ts.timestamp)?;
+                    sample_stream.flush()?;
+                }
+                ts_stream.write_bytes(2, &sample)?;
+                ts_stream.flush()?;
+            }
+            request_stream.write_bytes(1, &time_series)?;
+        }
+        request_stream.flush()?;
+    }
+
+    Ok(request)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn encode_sorted_series() {
+        let series = TimeSeries::new(
+            "up".to_string(),
+            vec![("job".to_string(), "a".to_string())],
+            1.0,
+            &(UNIX_EPOCH + Duration::from_millis(1000)),
+        );
+        assert_eq!(series.labels[0].0, "__name__");
+
+        assert_eq!(
+            encode_write_request(&[series]).unwrap(),
+            vec![
+                0x0a, 0x28, // timeseries, 40 bytes
+                0x0a, 0x0e, // label, 14 bytes
+                0x0a, 0x08, b'_', b'_', b'n', b'a', b'm', b'e', b'_', b'_', // name
+                0x12, 0x02, b'u', b'p', // value
+                0x0a, 0x08, // label, 8 bytes
+                0x0a, 0x03, b'j', b'o', b'b', // name
+                0x12, 0x01, b'a', // value
+                0x12, 0x0c, // sample, 12 bytes
+                0x09, 0, 0, 0, 0, 0, 0, 0xf0, 0x3f, // value 1.0
+                0x10, 0xe8, 0x07, // timestamp 1000
+            ]
+        );
+    }
+}
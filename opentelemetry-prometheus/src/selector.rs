@@ -0,0 +1,47 @@
+//! Histogram aggregator selection with per-instrument bucket boundaries.
+use opentelemetry::metrics::{Descriptor, InstrumentKind};
+use opentelemetry::sdk::{
+    export::metrics::{Aggregator, AggregatorSelector},
+    metrics::{aggregators, selectors::simple::Selector, views::View},
+};
+use std::sync::Arc;
+
+/// Selects histogram aggregators for `ValueRecorder`s, with the boundaries of
+/// the first pattern matching the instrument name or the default boundaries.
+///
+/// Patterns are matched like the instrument names of views.
+#[derive(Debug)]
+pub(crate) struct HistogramSelector {
+    default: Selector,
+    boundaries: Vec<(View, Vec<f64>)>,
+}
+
+impl HistogramSelector {
+    pub(crate) fn new(default_boundaries: Vec<f64>, boundaries: Vec<(String, Vec<f64>)>) -> Self {
+        HistogramSelector {
+            default: Selector::Histogram(default_boundaries),
+            boundaries: boundaries
+                .into_iter()
+                .map(|(pattern, boundaries)| {
+                    (View::new().with_instrument_name(pattern), boundaries)
+                })
+                .collect(),
+        }
+    }
+}
+
+impl AggregatorSelector for HistogramSelector {
+    fn aggregator_for(&self, descriptor: &Descriptor) -> Option<Arc<dyn Aggregator + Send + Sync>> {
+        if descriptor.instrument_kind() == &InstrumentKind::ValueRecorder {
+            if let Some((_, boundaries)) = self
+                .boundaries
+                .iter()
+                .find(|(view, _)| view.matches(descriptor))
+            {
+                return Some(Arc::new(aggregators::histogram(descriptor, boundaries)));
+            }
+        }
+
+        self.default.aggregator_for(descriptor)
+    }
+}
@@ -0,0 +1,159 @@
+//! Built-in HTTP endpoint serving the metrics of a prometheus registry.
+use crate::exemplars;
+use crate::openmetrics::OpenMetricsEncoder;
+use hyper::{
+    header::{ACCEPT, CONTENT_TYPE},
+    service::{make_service_fn, service_fn},
+    Body, Request, Response, Server, StatusCode,
+};
+use opentelemetry::{global, metrics::MetricsError};
+use prometheus::{Encoder, TextEncoder};
+use std::convert::Infallible;
+use std::net::{SocketAddr, TcpListener};
+use std::sync::Mutex;
+use std::thread;
+use tokio::sync::oneshot;
+
+/// The path metrics are served on.
+const METRICS_PATH: &str = "/metrics";
+
+/// An HTTP server exposing the metrics of a registry on `/metrics`.
+///
+/// The server runs on its own thread and single threaded runtime, so it does
+/// not depend on the runtime of the application. It is gracefully shut down
+/// when dropped.
+#[derive(Debug)]
+pub(crate) struct MetricsServer {
+    local_addr: SocketAddr,
+    shutdown: Mutex<Option<oneshot::Sender<()>>>,
+    thread: Mutex<Option<thread::JoinHandle<()>>>,
+}
+
+impl MetricsServer {
+    /// Bind the given address and start serving the registry's metrics, along
+    /// with their exemplars in the OpenMetrics format.
+    pub(crate) fn start(
+        registry: prometheus::Registry,
+        addr: SocketAddr,
+    ) -> Result<Self, MetricsError> {
+        let listener =
+            TcpListener::bind(addr).map_err(|err| MetricsError::Other(err.to_string()))?;
+        let local_addr = listener
+            .local_addr()
+            .map_err(|err| MetricsError::Other(err.to_string()))?;
+
+        let mut runtime = tokio::runtime::Builder::new()
+            .basic_scheduler()
+            .enable_io()
+            .build()
+            .map_err(|err| MetricsError::Other(err.to_string()))?;
+        let builder = runtime
+            .enter(|| Server::from_tcp(listener))
+            .map_err(|err| MetricsError::Other(err.to_string()))?;
+
+        let make_svc = make_service_fn(move |_conn| {
+            let registry = registry.clone();
+            async move {
+                Ok::<_, Infallible>(service_fn(move |req| {
+                    let response = serve_req(req, &registry);
+                    async move { Ok::<_, Infallible>(response) }
+                }))
+            }
+        });
+
+        let (tx, rx) = oneshot::channel();
+        let server = builder.serve(make_svc).with_graceful_shutdown(async {
+            // Also shuts down if the sender is dropped.
+            let _ = rx.await;
+        });
+
+        let thread = thread::Builder::new()
+            .name("opentelemetry-prometheus-server".to_string())
+            .spawn(move || {
+                if let Err(err) = runtime.block_on(server) {
+                    global::handle_error(MetricsError::Other(err.to_string()));
+                }
+            })
+            .map_err(|err| MetricsError::Other(err.to_string()))?;
+
+        Ok(MetricsServer {
+            local_addr,
+            shutdown: Mutex::new(Some(tx)),
+            thread: Mutex::new(Some(thread)),
+        })
+    }
+
+    /// The address the server is listening on.
+    pub(crate) fn local_addr(&self) -> SocketAddr {
+        self.local_addr
+    }
+
+    /// Stop accepting connections and wait until in-flight requests are
+    /// complete and the listener is closed.
+    pub(crate) fn shutdown(&self) {
+        if let Some(tx) = self.shutdown.lock().ok().and_then(|mut tx| tx.take()) {
+            let _ = tx.send(());
+        }
+        if let Some(thread) = self.thread.lock().ok().and_then(|mut t| t.take()) {
+            let _ = thread.join();
+        }
+    }
+}
+
+impl Drop for MetricsServer {
+    fn drop(&mut self) {
+        self.shutdown()
+    }
+}
+
+fn serve_req(req: Request<Body>, registry: &prometheus::Registry) -> Response<Body> {
+    if req.uri().path() != METRICS_PATH {
+        return Response::builder()
+            .status(StatusCode::NOT_FOUND)
+            .body(Body::empty())
+            .unwrap();
+    }
+
+    let (metric_families, exemplars) = exemplars::gather(registry);
+    let mut buffer = Vec::new();
+    let encoded = if accepts_openmetrics(&req) {
+        let encoder = OpenMetricsEncoder::with_exemplars(exemplars);
+        encoder
+            .encode(&metric_families, &mut buffer)
+            .map(|_| encoder.format_type().to_string())
+    } else {
+        let encoder = TextEncoder::new();
+        encoder
+            .encode(&metric_families, &mut buffer)
+            .map(|_| encoder.format_type().to_string())
+    };
+
+    match encoded {
+        Ok(content_type) => Response::builder()
+            .status(StatusCode::OK)
+            .header(CONTENT_TYPE, content_type)
+            .body(Body::from(buffer))
+            .unwrap(),
+        Err(err) => {
+            global::handle_error(MetricsError::Other(err.to_string()));
+            Response::builder()
+                .status(StatusCode::INTERNAL_SERVER_ERROR)
+                .body(Body::empty())
+                .unwrap()
+        }
+    }
+}
+
+/// Whether the scraper asked for the OpenMetrics text format.
+fn accepts_openmetrics(req: &Request<Body>) -> bool {
+    req.headers()
+        .get_all(ACCEPT)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .any(|media_range| {
+            media_range.split(';').next().map_or(false, |media_type| {
+                media_type.trim() == "application/openmetrics-text"
+            })
+        })
+}
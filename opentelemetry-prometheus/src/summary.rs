@@ -0,0 +1,231 @@
+//! Sliding window state for exporting `ValueRecorder`s as Prometheus summaries.
+use opentelemetry::labels;
+use opentelemetry::metrics::{Descriptor, MetricsError, NumberKind};
+use opentelemetry::sdk::{
+    export::metrics::{Aggregator, Distribution, Record},
+    metrics::aggregators::{self, ArrayAggregator, DDSKetchAggregator, DDSketchConfig},
+};
+use opentelemetry::KeyValue;
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{HashMap, VecDeque};
+use std::hash::{Hash, Hasher};
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+
+/// Aggregation used to compute the quantiles of exported summaries.
+#[derive(Clone, Debug)]
+pub enum SummaryAggregation {
+    /// Estimate quantiles with a DDSketch, which bounds the memory used by each
+    /// series.
+    Sketch(DDSketchConfig),
+    /// Compute exact quantiles from every value recorded within the window.
+    Exact,
+}
+
+impl SummaryAggregation {
+    fn aggregator_for(&self, descriptor: &Descriptor) -> Arc<dyn Aggregator + Send + Sync> {
+        match self {
+            SummaryAggregation::Sketch(config) => Arc::new(aggregators::ddsketch(
+                config,
+                descriptor.number_kind().clone(),
+            )),
+            SummaryAggregation::Exact => Arc::new(aggregators::array()),
+        }
+    }
+}
+
+/// Returns the aggregator as a `Distribution` if it can back a summary.
+fn as_distribution(aggregator: &dyn Aggregator) -> Option<&dyn Distribution> {
+    let any = aggregator.as_any();
+    if let Some(sketch) = any.downcast_ref::<DDSKetchAggregator>() {
+        Some(sketch)
+    } else if let Some(array) = any.downcast_ref::<ArrayAggregator>() {
+        Some(array)
+    } else {
+        None
+    }
+}
+
+/// Delta aggregations of each summary series over the configured window.
+///
+/// Quantiles are computed over the deltas still inside the window, while the
+/// sample count and sum are cumulative since the series was first seen, as is
+/// customary for Prometheus summaries. Series are forgotten once their window
+/// is empty, restarting their count and sum if they are recorded again.
+#[derive(Debug)]
+pub(crate) struct Summaries {
+    aggregation: SummaryAggregation,
+    quantiles: Vec<f64>,
+    window: Duration,
+    series: HashMap<u64, Series>,
+}
+
+#[derive(Debug)]
+struct Series {
+    descriptor: Descriptor,
+    desc: prometheus::core::Desc,
+    labels: Vec<KeyValue>,
+    /// End time of the last collection interval added to this series.
+    last_end: SystemTime,
+    count: u64,
+    sum: f64,
+    deltas: VecDeque<(SystemTime, Arc<dyn Aggregator + Send + Sync>)>,
+}
+
+impl Summaries {
+    pub(crate) fn new(
+        aggregation: SummaryAggregation,
+        quantiles: Vec<f64>,
+        window: Duration,
+    ) -> Self {
+        Summaries {
+            aggregation,
+            quantiles,
+            window,
+            series: HashMap::new(),
+        }
+    }
+
+    /// Add the delta aggregation of the given record to its series.
+    ///
+    /// Returns `false` if the record's aggregator cannot back a summary.
+    pub(crate) fn update(
+        &mut self,
+        record: &Record<'_>,
+        desc: &prometheus::core::Desc,
+        labels: &[KeyValue],
+    ) -> Result<bool, MetricsError> {
+        let agg = match record.aggregator() {
+            Some(agg) => agg,
+            None => return Ok(false),
+        };
+        let distribution = match as_distribution(agg.as_ref()) {
+            Some(distribution) => distribution,
+            None => return Ok(false),
+        };
+
+        let descriptor = record.descriptor();
+        let mut hasher = DefaultHasher::new();
+        descriptor.attribute_hash().hash(&mut hasher);
+        labels::hash_labels(&mut hasher, record.labels().iter());
+        labels::hash_labels(&mut hasher, record.resource().iter());
+
+        let key = hasher.finish();
+        let end = *record.end_time();
+        let count = distribution.count()?;
+        if count == 0 {
+            // Intervals without values don't keep a series alive.
+            if let Some(series) = self.series.get_mut(&key) {
+                series.last_end = end;
+            }
+            return Ok(true);
+        }
+
+        let series = self.series.entry(key).or_insert_with(|| Series {
+            descriptor: descriptor.clone(),
+            desc: desc.clone(),
+            labels: labels.to_vec(),
+            last_end: SystemTime::UNIX_EPOCH,
+            count: 0,
+            sum: 0.0,
+            deltas: VecDeque::new(),
+        });
+
+        // The same checkpoint is visited again while the pull controller
+        // serves cached results.
+        if series.last_end == end {
+            return Ok(true);
+        }
+        series.last_end = end;
+        series.count += count;
+        series.sum += distribution.sum()?.to_f64(descriptor.number_kind());
+
+        // The checkpointed aggregator is reused by the SDK, keep a copy of it.
+        let delta = self.aggregation.aggregator_for(descriptor);
+        delta.merge(agg.as_ref(), descriptor)?;
+        series.deltas.push_back((end, delta));
+
+        Ok(true)
+    }
+
+    /// Build the summaries of all known series, discarding deltas that fell
+    /// out of the window and series without deltas left.
+    pub(crate) fn collect(
+        &mut self,
+        now: SystemTime,
+    ) -> Result<Vec<prometheus::proto::MetricFamily>, MetricsError> {
+        let window = self.window;
+        self.series.retain(|_, series| {
+            while let Some((end, _)) = series.deltas.front() {
+                if now.duration_since(*end).map_or(false, |age| age > window) {
+                    series.deltas.pop_front();
+                } else {
+                    break;
+                }
+            }
+            !series.deltas.is_empty()
+        });
+
+        let mut metrics = Vec::with_capacity(self.series.len());
+        for series in self.series.values() {
+            let merged = self.aggregation.aggregator_for(&series.descriptor);
+            for (_, delta) in series.deltas.iter() {
+                merged.merge(delta.as_ref(), &series.descriptor)?;
+            }
+
+            metrics.push(build_summary(
+                series,
+                as_distribution(merged.as_ref()),
+                &self.quantiles,
+            )?);
+        }
+
+        Ok(metrics)
+    }
+}
+
+fn build_summary(
+    series: &Series,
+    window: Option<&dyn Distribution>,
+    quantiles: &[f64],
+) -> Result<prometheus::proto::MetricFamily, MetricsError> {
+    let kind: &NumberKind = series.descriptor.number_kind();
+
+    let mut s = prometheus::proto::Summary::default();
+    s.set_sample_count(series.count);
+    s.set_sample_sum(series.sum);
+
+    let mut qs = Vec::with_capacity(quantiles.len());
+    for q in quantiles {
+        let value = match window {
+            Some(window) if window.count()? > 0 => window.quantile(*q)?.to_f64(kind),
+            // Prometheus reports quantiles of an empty window as NaN.
+            _ => f64::NAN,
+        };
+
+        let mut quantile = prometheus::proto::Quantile::default();
+        quantile.set_quantile(*q);
+        quantile.set_value(value);
+        qs.push(quantile);
+    }
+    s.set_quantile(protobuf::RepeatedField::from_vec(qs));
+
+    let mut m = prometheus::proto::Metric::default();
+    m.set_label(protobuf::RepeatedField::from_vec(
+        series
+            .labels
+            .iter()
+            .cloned()
+            .map(crate::build_label_pair)
+            .collect(),
+    ));
+    m.set_summary(s);
+
+    let mut mf = prometheus::proto::MetricFamily::default();
+    mf.set_name(series.desc.fq_name.clone());
+    mf.set_help(series.desc.help.clone());
+    mf.set_field_type(prometheus::proto::MetricType::SUMMARY);
+    mf.set_metric(protobuf::RepeatedField::from_vec(vec![m]));
+
+    Ok(mf)
+}
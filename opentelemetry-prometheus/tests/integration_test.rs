@@ -1,6 +1,10 @@
-use opentelemetry::sdk::Resource;
-use opentelemetry::{metrics::MeterProvider, KeyValue};
-use opentelemetry_prometheus::PrometheusExporter;
+use opentelemetry::sdk::{
+    metrics::{aggregators::DDSketchConfig, controllers, selectors::simple::Selector, views::View},
+    trace as sdktrace, Resource,
+};
+use opentelemetry::trace::{Span, TraceContextExt, Tracer, TracerProvider};
+use opentelemetry::{metrics::MeterProvider, Context, Key, KeyValue, Unit};
+use opentelemetry_prometheus::{PrometheusExporter, SummaryAggregation};
 use prometheus::{Encoder, TextEncoder};
 
 #[test]
@@ -22,7 +26,7 @@ fn test_add() {
     counter.add(10.0, &labels);
     counter.add(5.3, &labels);
 
-    expected.push("counter{A=\"B\",C=\"D\",R=\"V\"} 15.3");
+    expected.push("counter_total{A=\"B\",C=\"D\",R=\"V\"} 15.3");
 
     value_recorder.record(-0.6, &labels);
     value_recorder.record(-0.4, &labels);
@@ -38,12 +42,544 @@ fn test_add() {
     compare_export(&exporter, expected)
 }
 
-fn compare_export(exporter: &PrometheusExporter, mut expected: Vec<&'static str>) {
+#[test]
+fn test_histogram_boundaries_and_units() {
+    let exporter = opentelemetry_prometheus::exporter()
+        .with_default_histogram_boundaries(vec![1.0])
+        .with_histogram_boundaries("http.*.duration", vec![0.1, 0.5])
+        .init();
+
+    let meter = exporter.provider().unwrap().meter("test");
+
+    let duration = meter
+        .f64_value_recorder("http.server.duration")
+        .with_unit(Unit::new("s"))
+        .init();
+    let size = meter
+        .u64_value_recorder("http.server.request.size")
+        .with_unit(Unit::new("By"))
+        .init();
+    let sent = meter.u64_counter("sent").with_unit(Unit::new("By")).init();
+
+    duration.record(0.3, &[]);
+    size.record(100, &[]);
+    sent.add(100, &[]);
+
+    let expected = vec![
+        "http_server_duration_seconds_bucket{le=\"0.1\"} 0",
+        "http_server_duration_seconds_bucket{le=\"0.5\"} 1",
+        "http_server_duration_seconds_bucket{le=\"+Inf\"} 1",
+        "http_server_request_size_bytes_bucket{le=\"1\"} 0",
+        "http_server_request_size_bytes_bucket{le=\"+Inf\"} 1",
+        "sent_bytes_total 100",
+    ];
+
+    let output = encode(&exporter);
+    for line in expected {
+        assert!(output.contains(line), "{} not found in {}", line, output);
+    }
+}
+
+#[test]
+fn test_without_suffixes() {
+    let exporter = opentelemetry_prometheus::exporter()
+        .without_units()
+        .without_counter_suffixes()
+        .init();
+
+    let meter = exporter.provider().unwrap().meter("test");
+
+    let sent = meter.u64_counter("sent").with_unit(Unit::new("By")).init();
+    sent.add(100, &[]);
+
+    compare_export(&exporter, vec!["sent 100"])
+}
+
+#[test]
+fn test_gauges() {
+    let exporter = opentelemetry_prometheus::exporter()
+        .with_resource(Resource::new(vec![KeyValue::new("R", "V")]))
+        .init();
+
+    let meter = exporter.provider().unwrap().meter("test");
+
+    let labels = vec![KeyValue::new("A", "B"), KeyValue::new("C", "D")];
+    let observer_labels = labels.clone();
+
+    let up_down_counter = meter.i64_up_down_counter("up_down_counter").init();
+    let _value_observer = meter
+        .f64_value_observer("value_observer", move |result| {
+            result.observe(1.5, &observer_labels)
+        })
+        .init();
+
+    up_down_counter.add(10, &labels);
+    up_down_counter.add(-3, &labels);
+
+    let expected = vec![
+        "# TYPE up_down_counter gauge",
+        "up_down_counter{A=\"B\",C=\"D\",R=\"V\"} 7",
+        "# TYPE value_observer gauge",
+        "value_observer{A=\"B\",C=\"D\",R=\"V\"} 1.5",
+    ];
+
+    let output = encode(&exporter);
+    for line in expected {
+        assert!(output.contains(line), "{} not found in {}", line, output);
+    }
+}
+
+#[test]
+fn test_target_info() {
+    let exporter = opentelemetry_prometheus::exporter()
+        .with_resource(Resource::new(vec![
+            KeyValue::new("service.name", "svc"),
+            KeyValue::new("R", "V"),
+        ]))
+        .with_target_info(vec![Key::new("service.name")])
+        .init();
+
+    let meter = exporter.provider().unwrap().meter("test");
+
+    let counter = meter.u64_counter("counter").init();
+    counter.add(1, &[KeyValue::new("A", "B")]);
+
+    let expected = vec![
+        "counter_total{A=\"B\",service_name=\"svc\"} 1",
+        "# HELP target_info Target metadata",
+        "# TYPE target_info gauge",
+        "target_info{R=\"V\",service_name=\"svc\"} 1",
+    ];
+
+    let output = encode(&exporter);
+    for line in expected {
+        assert!(output.contains(line), "{} not found in {}", line, output);
+    }
+    assert!(!output.contains("counter_total{A=\"B\",R=\"V\""));
+}
+
+#[test]
+fn test_summaries() {
+    let exporter = opentelemetry_prometheus::exporter()
+        .with_default_summary_quantiles(vec![0.5, 1.0])
+        .with_summaries(SummaryAggregation::Exact)
+        .with_resource(Resource::new(vec![KeyValue::new("R", "V")]))
+        .init();
+
+    let meter = exporter.provider().unwrap().meter("test");
+
+    let value_recorder = meter.f64_value_recorder("value_recorder").init();
+
+    let labels = vec![KeyValue::new("A", "B"), KeyValue::new("C", "D")];
+
+    let mut expected = Vec::new();
+
+    value_recorder.record(1.0, &labels);
+    value_recorder.record(2.0, &labels);
+    value_recorder.record(3.0, &labels);
+    value_recorder.record(4.0, &labels);
+
+    expected.push("value_recorder{A=\"B\",C=\"D\",R=\"V\",quantile=\"0.5\"} 3");
+    expected.push("value_recorder{A=\"B\",C=\"D\",R=\"V\",quantile=\"1\"} 4");
+    expected.push("value_recorder_count{A=\"B\",C=\"D\",R=\"V\"} 4");
+    expected.push("value_recorder_sum{A=\"B\",C=\"D\",R=\"V\"} 10");
+
+    compare_export(&exporter, expected)
+}
+
+#[test]
+fn test_summary_eviction() {
+    let exporter = opentelemetry_prometheus::exporter()
+        .with_summaries(SummaryAggregation::Exact)
+        .with_summary_window(std::time::Duration::from_millis(100))
+        .with_cache_period(std::time::Duration::from_secs(0))
+        .init();
+
+    let meter = exporter.provider().unwrap().meter("test");
+    let value_recorder = meter.f64_value_recorder("value_recorder").init();
+
+    value_recorder.record(1.0, &[KeyValue::new("A", "B")]);
+    assert!(encode(&exporter).contains("value_recorder_count{A=\"B\"} 1"));
+
+    // Series are forgotten once their window holds no values.
+    std::thread::sleep(std::time::Duration::from_millis(200));
+    assert!(!encode(&exporter).contains("value_recorder"));
+}
+
+#[test]
+fn test_sketch_summaries() {
+    let exporter = opentelemetry_prometheus::exporter()
+        .with_default_summary_quantiles(vec![0.99])
+        .with_summaries(SummaryAggregation::Sketch(DDSketchConfig::new(
+            0.01, 2048, 1e-9,
+        )))
+        .init();
+
+    let meter = exporter.provider().unwrap().meter("test");
+
+    let value_recorder = meter.u64_value_recorder("value_recorder").init();
+
+    let mut expected = Vec::new();
+
+    value_recorder.record(5, &[]);
+
+    expected.push("value_recorder{quantile=\"0.99\"} 5");
+    expected.push("value_recorder_count 1");
+    expected.push("value_recorder_sum 5");
+
+    compare_export(&exporter, expected)
+}
+
+#[cfg(feature = "hyper-server")]
+#[tokio::test]
+async fn test_server() {
+    use hyper::{body, header, Body, Client, Request};
+
+    let exporter = opentelemetry_prometheus::exporter()
+        .with_server_address(([127, 0, 0, 1], 0).into())
+        .init();
+    let addr = exporter.server_address().unwrap();
+
+    let meter = exporter.provider().unwrap().meter("test");
+    let counter = meter.u64_counter("requests").init();
+    counter.add(1, &[KeyValue::new("A", "B")]);
+
+    let client = Client::new();
+    let scrape = |accept: &'static str| {
+        let request = Request::get(format!("http://{}/metrics", addr))
+            .header(header::ACCEPT, accept)
+            .body(Body::empty())
+            .unwrap();
+        client.request(request)
+    };
+
+    let response = scrape("text/plain").await.unwrap();
+    assert_eq!(
+        response.headers()[header::CONTENT_TYPE],
+        "text/plain; version=0.0.4"
+    );
+    let text = body::to_bytes(response.into_body()).await.unwrap();
+    assert!(String::from_utf8_lossy(&text).contains("requests_total{A=\"B\"} 1\n"));
+
+    counter.add(1, &[KeyValue::new("A", "B")]);
+
+    let response = scrape("application/openmetrics-text;version=1.0.0,text/plain;q=0.5")
+        .await
+        .unwrap();
+    assert_eq!(
+        response.headers()[header::CONTENT_TYPE],
+        opentelemetry_prometheus::OPENMETRICS_FORMAT
+    );
+    let text = body::to_bytes(response.into_body()).await.unwrap();
+    let text = String::from_utf8_lossy(&text);
+    assert!(text.contains("# TYPE requests counter\nrequests_total{A=\"B\"} 2\n"));
+    assert!(text.ends_with("# EOF\n"));
+
+    let response = client
+        .get(format!("http://{}/other", addr).parse().unwrap())
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 404);
+
+    drop(client);
+    exporter.shutdown_server();
+    assert!(std::net::TcpStream::connect(addr).is_err());
+}
+
+/// The lowercased headers and the body of a request.
+#[cfg(feature = "remote-write-reqwest")]
+type ReceivedRequest = (Vec<String>, Vec<u8>);
+
+/// A local stand-in for a remote write endpoint, accepting one request.
+#[cfg(feature = "remote-write-reqwest")]
+fn remote_write_endpoint() -> (
+    std::net::SocketAddr,
+    std::sync::mpsc::Receiver<ReceivedRequest>,
+) {
+    use std::io::{BufRead, BufReader, Read, Write};
+
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    let (tx, rx) = std::sync::mpsc::channel();
+    std::thread::spawn(move || {
+        let (stream, _) = listener.accept().unwrap();
+        let mut reader = BufReader::new(stream);
+        let mut headers = Vec::new();
+        let mut content_length = 0;
+        loop {
+            let mut line = String::new();
+            reader.read_line(&mut line).unwrap();
+            let line = line.trim_end().to_lowercase();
+            if line.is_empty() {
+                break;
+            }
+            if let Some(length) = line.strip_prefix("content-length: ") {
+                content_length = length.parse().unwrap();
+            }
+            headers.push(line);
+        }
+        let mut body = vec![0; content_length];
+        reader.read_exact(&mut body).unwrap();
+        reader
+            .get_mut()
+            .write_all(b"HTTP/1.1 204 No Content\r\ncontent-length: 0\r\n\r\n")
+            .unwrap();
+        tx.send((headers, body)).unwrap();
+    });
+
+    (addr, rx)
+}
+
+/// `#[tokio::test]` runs on a single threaded runtime, which the final push
+/// must not depend on.
+#[cfg(feature = "remote-write-reqwest")]
+#[tokio::test]
+async fn test_remote_write() {
+    use std::time::Duration;
+
+    let (addr, rx) = remote_write_endpoint();
+    let controller = opentelemetry_prometheus::remote_write::remote_write(tokio::spawn, |_| {
+        futures::stream::pending::<()>()
+    })
+    .with_endpoint(format!("http://{}/api/v1/write", addr))
+    .with_header("X-Scope-OrgID", "tenant")
+    .with_basic_auth("user", "pass")
+    .with_resource(Resource::new(vec![KeyValue::new("R", "V")]))
+    .init();
+
+    let meter = controller.provider().meter("test");
+    let counter = meter.u64_counter("requests").init();
+    counter.add(3, &[KeyValue::new("A", "B")]);
+
+    // Blocks the only thread of the runtime until the request completed.
+    controller.shutdown().unwrap();
+
+    let (headers, body) = rx.recv_timeout(Duration::from_secs(10)).unwrap();
+    assert!(headers.contains(&"content-encoding: snappy".to_string()));
+    assert!(headers.contains(&"content-type: application/x-protobuf".to_string()));
+    assert!(headers.contains(&"x-prometheus-remote-write-version: 0.1.0".to_string()));
+    assert!(headers.contains(&"x-scope-orgid: tenant".to_string()));
+    // base64("user:pass")
+    assert!(headers.contains(&"authorization: basic dxnlcjpwyxnz".to_string()));
+
+    let request = snap::raw::Decoder::new().decompress_vec(&body).unwrap();
+    let contains = |needle: &[u8]| request.windows(needle.len()).any(|w| w == needle);
+    assert!(contains(b"\x0a\x08__name__\x12\x0erequests_total"));
+    assert!(contains(b"\x0a\x01A\x12\x01B"));
+    assert!(contains(b"\x0a\x01R\x12\x01V"));
+    // Sample value 3.0
+    assert!(contains(&[0x09, 0, 0, 0, 0, 0, 0, 0x08, 0x40]));
+}
+
+#[cfg(feature = "remote-write-reqwest")]
+#[tokio::test]
+async fn test_remote_write_on_drop() {
+    use std::time::Duration;
+
+    let (addr, rx) = remote_write_endpoint();
+    let controller = opentelemetry_prometheus::remote_write::remote_write(tokio::spawn, |_| {
+        futures::stream::pending::<()>()
+    })
+    .with_endpoint(format!("http://{}/api/v1/write", addr))
+    .init();
+
+    let counter = controller
+        .provider()
+        .meter("test")
+        .u64_counter("requests")
+        .init();
+    counter.add(3, &[KeyValue::new("A", "B")]);
+
+    // The worker pushes one last time once it is dropped on this runtime.
+    drop(controller);
+    let (_headers, body) =
+        tokio::task::spawn_blocking(move || rx.recv_timeout(Duration::from_secs(10)))
+            .await
+            .unwrap()
+            .unwrap();
+
+    let request = snap::raw::Decoder::new().decompress_vec(&body).unwrap();
+    let contains = |needle: &[u8]| request.windows(needle.len()).any(|w| w == needle);
+    assert!(contains(b"\x0a\x08__name__\x12\x0erequests_total"));
+}
+
+#[test]
+fn test_exemplars() {
+    let exporter = opentelemetry_prometheus::exporter()
+        .with_default_histogram_boundaries(vec![1.0])
+        .init();
+
+    let meter = exporter.provider().unwrap().meter("test");
+    let counter = meter.u64_counter("requests").init();
+    let value_recorder = meter.f64_value_recorder("latency").init();
+
+    let provider = sdktrace::TracerProvider::builder().build();
+    let tracer = provider.get_tracer("test", None);
+    let span = tracer.start("request");
+    let span_context = span.span_context();
+    let cx = Context::current_with_span(span);
+
+    {
+        let _guard = cx.clone().attach();
+        counter.add(1, &[KeyValue::new("A", "B")]);
+        value_recorder.record(0.5, &[]);
+    }
+    meter.record_batch_with_context(&cx, &[], vec![value_recorder.measurement(2.0)]);
+    // Measurements outside of a span have no exemplars.
+    counter.add(2, &[KeyValue::new("A", "C")]);
+
+    let ids = format!(
+        "{{trace_id=\"{}\",span_id=\"{}\"}}",
+        span_context.trace_id().to_hex(),
+        span_context.span_id().to_hex()
+    );
+    let expected = vec![
+        format!("requests_total{{A=\"B\"}} 1 # {} 1 ", ids),
+        "requests_total{A=\"C\"} 2\n".to_string(),
+        format!("latency_bucket{{le=\"1\"}} 1 # {} 0.5 ", ids),
+        format!("latency_bucket{{le=\"+Inf\"}} 2 # {} 2 ", ids),
+        "latency_count 2\n".to_string(),
+    ];
+
+    let mut output = Vec::new();
+    let (metric_families, encoder) = exporter.gather_openmetrics();
+    // Another scrape collecting in the meantime keeps its own exemplars.
+    exporter.registry().gather();
+    encoder.encode(&metric_families, &mut output).unwrap();
+    let output = String::from_utf8(output).unwrap();
+
+    for sample in expected {
+        assert!(output.contains(&sample), "{} not in {}", sample, output);
+    }
+}
+
+#[test]
+fn test_views() {
+    let exporter = opentelemetry_prometheus::exporter()
+        .with_views(vec![View::new()
+            .with_meter_name("third.party")
+            .with_instrument_name("req*")
+            .with_name("http.requests")
+            .with_description("HTTP requests")
+            .with_label_keys(vec![Key::new("method")])])
+        .init();
+
+    let provider = exporter.provider().unwrap();
+    let third_party = provider.meter("third.party").u64_counter("requests").init();
+    let own = provider.meter("test").u64_counter("requests").init();
+
+    third_party.add(
+        1,
+        &[KeyValue::new("method", "GET"), KeyValue::new("id", "1")],
+    );
+    third_party.add(
+        2,
+        &[KeyValue::new("method", "GET"), KeyValue::new("id", "2")],
+    );
+    own.add(
+        4,
+        &[KeyValue::new("method", "GET"), KeyValue::new("id", "3")],
+    );
+
+    let expected = vec![
+        "# HELP http_requests_total HTTP requests\n",
+        "\nhttp_requests_total{method=\"GET\"} 3\n",
+        "\nrequests_total{id=\"3\",method=\"GET\"} 4\n",
+    ];
+
+    let output = encode(&exporter);
+    for line in expected {
+        assert!(output.contains(line), "{} not found in {}", line, output);
+    }
+}
+
+#[test]
+fn test_cardinality_limit() {
+    let exporter = opentelemetry_prometheus::exporter()
+        .with_cardinality_limit(2)
+        .init();
+
+    let meter = exporter.provider().unwrap().meter("test");
+    let counter = meter.u64_counter("requests").init();
+
+    for (id, value) in &[("1", 1), ("2", 2), ("3", 4), ("4", 8), ("1", 16)] {
+        counter.add(*value, &[KeyValue::new("id", *id)]);
+    }
+
+    let expected = vec![
+        "requests_total{id=\"1\"} 17",
+        "requests_total{id=\"2\"} 2",
+        "requests_total{otel_metric_overflow=\"true\"} 12",
+    ];
+
+    compare_export(&exporter, expected)
+}
+
+#[test]
+fn test_shared_accumulator() {
+    let shared = controllers::shared(Box::new(Selector::Histogram(vec![1.0]))).build();
+    let first = opentelemetry_prometheus::exporter()
+        .with_shared_accumulator(&shared)
+        .init();
+    let second = opentelemetry_prometheus::exporter()
+        .with_shared_accumulator(&shared)
+        .init();
+
+    let meter = shared.provider().meter("test");
+    let counter = meter.u64_counter("requests").init();
+
+    counter.add(1, &[KeyValue::new("A", "B")]);
+    compare_export(&first, vec!["requests_total{A=\"B\"} 1"]);
+
+    counter.add(2, &[KeyValue::new("A", "B")]);
+    compare_export(&second, vec!["requests_total{A=\"B\"} 3"]);
+    compare_export(&first, vec!["requests_total{A=\"B\"} 3"]);
+}
+
+#[test]
+fn test_shared_target_info() {
+    let shared = controllers::shared(Box::new(Selector::Exact))
+        .with_resource(Resource::new(vec![KeyValue::new("R", "V")]))
+        .build();
+    let exporter = opentelemetry_prometheus::exporter()
+        .with_shared_accumulator(&shared)
+        .with_target_info(Vec::new())
+        .init();
+
+    let counter = shared
+        .provider()
+        .meter("test")
+        .u64_counter("requests")
+        .init();
+    counter.add(1, &[KeyValue::new("A", "B")]);
+
+    let output = encode(&exporter);
+    for line in &["requests_total{A=\"B\"} 1", "target_info{R=\"V\"} 1"] {
+        assert!(output.contains(line), "{} not found in {}", line, output);
+    }
+}
+
+#[test]
+fn test_shared_accumulator_options() {
+    let shared = controllers::shared(Box::new(Selector::Exact)).build();
+    let exporter = opentelemetry_prometheus::exporter()
+        .with_shared_accumulator(&shared)
+        .with_cardinality_limit(2)
+        .try_init();
+
+    assert!(exporter.is_err());
+}
+
+fn encode(exporter: &PrometheusExporter) -> String {
     let mut output = Vec::new();
     let encoder = TextEncoder::new();
     let metric_families = exporter.registry().gather();
     encoder.encode(&metric_families, &mut output).unwrap();
-    let output_string = String::from_utf8(output).unwrap();
+    String::from_utf8(output).unwrap()
+}
+
+fn compare_export(exporter: &PrometheusExporter, mut expected: Vec<&'static str>) {
+    let output_string = encode(exporter);
 
     let mut metrics_only = output_string
         .split_terminator('\n')
@@ -0,0 +1,523 @@
+//! # OpenTelemetry StatsD Exporter
+//!
+//! Sends the delta aggregations of the metric instruments over UDP to a StatsD
+//! agent. Counters and `SumObserver`s are sent as counters (`c`), up-down
+//! counters and last values as gauges (`g`), and value recorders either as
+//! histogram (`h`) or distribution (`d`) samples, or expanded into their min,
+//! max, sum and count. Several lines are packed into each datagram.
+//!
+//! ### StatsD Exporter Example
+//!
+//! ```no_run
+//! use futures::stream::{Stream, StreamExt as _};
+//! use opentelemetry::{global, KeyValue};
+//! use opentelemetry_statsd::Flavor;
+//! use std::time::Duration;
+//!
+//! // Skip first immediate tick from tokio, not needed for async_std.
+//! fn delayed_interval(duration: Duration) -> impl Stream<Item = tokio::time::Instant> {
+//!     tokio::time::interval(duration).skip(1)
+//! }
+//!
+//! # #[tokio::main] async fn main() {
+//! let _controller = opentelemetry_statsd::exporter(tokio::spawn, delayed_interval)
+//!     .with_endpoint("127.0.0.1:8125")
+//!     .with_prefix("my_app")
+//!     .with_flavor(Flavor::DogStatsd)
+//!     .init();
+//!
+//! let meter = global::meter("my-app");
+//! let counter = meter.u64_counter("requests").init();
+//!
+//! // Sent as `my_app.requests:1|c|#method:GET`
+//! counter.add(1, &[KeyValue::new("method", "GET")]);
+//! # }
+//! ```
+#![warn(
+    future_incompatible,
+    missing_debug_implementations,
+    missing_docs,
+    nonstandard_style,
+    rust_2018_idioms,
+    unreachable_pub,
+    unused
+)]
+#![cfg_attr(docsrs, feature(doc_cfg), deny(broken_intra_doc_links))]
+#![doc(
+    html_logo_url = "https://raw.githubusercontent.com/open-telemetry/opentelemetry-rust/master/assets/logo.svg"
+)]
+#![cfg_attr(test, deny(warnings))]
+
+use futures::Stream;
+use opentelemetry::global;
+use opentelemetry::metrics::{Descriptor, MetricsError, Number, NumberKind, Result};
+use opentelemetry::sdk::{
+    export::metrics::{
+        CheckpointSet, Count, ExportKind, ExportKindSelector, Exporter, LastValue, Max, Min,
+        Points, Record, Sum,
+    },
+    metrics::{
+        aggregators::{
+            ArrayAggregator, DDSKetchAggregator, LastValueAggregator, MinMaxSumCountAggregator,
+            SumAggregator,
+        },
+        controllers::{self, PushController, PushControllerWorker},
+        selectors::{export_kind, simple},
+    },
+    Resource,
+};
+use std::io;
+use std::net::{Ipv4Addr, Ipv6Addr, SocketAddr, ToSocketAddrs, UdpSocket};
+use std::time::Duration;
+
+/// The default address of the StatsD agent.
+const DEFAULT_ENDPOINT: &str = "127.0.0.1:8125";
+
+/// The default maximum size of a datagram, fitting in the MTU of most
+/// networks.
+const DEFAULT_MAX_PACKET_SIZE: usize = 1432;
+
+/// Create a new StatsD exporter builder, pushing the metrics with the given
+/// spawn and interval functions.
+pub fn exporter<S, SO, I, IS, ISI>(spawn: S, interval: I) -> ExporterBuilder<S, I>
+where
+    S: Fn(PushControllerWorker) -> SO,
+    I: Fn(Duration) -> IS,
+    IS: Stream<Item = ISI> + Send + 'static,
+{
+    ExporterBuilder {
+        spawn,
+        interval,
+        endpoint: DEFAULT_ENDPOINT.to_string(),
+        prefix: None,
+        flavor: Flavor::Statsd,
+        recorder_format: RecorderFormat::Histogram,
+        max_packet_size: DEFAULT_MAX_PACKET_SIZE,
+        period: None,
+        resource: None,
+    }
+}
+
+/// The dialect of the exported lines.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Flavor {
+    /// Plain StatsD, which has no tags. The labels and resource are dropped.
+    Statsd,
+    /// DogStatsD, exporting the resource and labels as tags.
+    DogStatsd,
+}
+
+/// How the values of value recorders are exported.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum RecorderFormat {
+    /// Each recorded value as a histogram sample, `h`.
+    Histogram,
+    /// Each recorded value as a distribution sample, `d`, supported by
+    /// DogStatsD.
+    Distribution,
+    /// The minimum and maximum of the recorded values as `.min` and `.max`
+    /// gauges, their sum and number as `.sum` and `.count` counters.
+    Expanded,
+}
+
+impl RecorderFormat {
+    fn aggregator_selector(&self) -> simple::Selector {
+        match self {
+            // The individual values are kept for the samples.
+            RecorderFormat::Histogram | RecorderFormat::Distribution => simple::Selector::Exact,
+            RecorderFormat::Expanded => simple::Selector::Inexpensive,
+        }
+    }
+}
+
+/// Configuration for the StatsD exporter.
+#[derive(Debug)]
+pub struct ExporterBuilder<S, I> {
+    spawn: S,
+    interval: I,
+    endpoint: String,
+    prefix: Option<String>,
+    flavor: Flavor,
+    recorder_format: RecorderFormat,
+    max_packet_size: usize,
+    period: Option<Duration>,
+    resource: Option<Resource>,
+}
+
+impl<S, SO, I, IS, ISI> ExporterBuilder<S, I>
+where
+    S: Fn(PushControllerWorker) -> SO,
+    I: Fn(Duration) -> IS,
+    IS: Stream<Item = ISI> + Send + 'static,
+{
+    /// Set the address of the StatsD agent, `127.0.0.1:8125` by default.
+    pub fn with_endpoint<T: Into<String>>(self, endpoint: T) -> Self {
+        ExporterBuilder {
+            endpoint: endpoint.into(),
+            ..self
+        }
+    }
+
+    /// Set a prefix prepended to all metric names, separated by a dot.
+    pub fn with_prefix<T: Into<String>>(self, prefix: T) -> Self {
+        ExporterBuilder {
+            prefix: Some(prefix.into()),
+            ..self
+        }
+    }
+
+    /// Set the dialect of the exported lines, plain StatsD by default.
+    pub fn with_flavor(self, flavor: Flavor) -> Self {
+        ExporterBuilder { flavor, ..self }
+    }
+
+    /// Set how the values of value recorders are exported, as histogram
+    /// samples by default.
+    pub fn with_recorder_format(self, recorder_format: RecorderFormat) -> Self {
+        ExporterBuilder {
+            recorder_format,
+            ..self
+        }
+    }
+
+    /// Set the maximum size in bytes of the sent datagrams, 1432 by default.
+    ///
+    /// Lines longer than the maximum size are sent in a datagram of their own.
+    pub fn with_max_packet_size(self, max_packet_size: usize) -> Self {
+        ExporterBuilder {
+            max_packet_size,
+            ..self
+        }
+    }
+
+    /// Set the frequency in which metrics are exported.
+    pub fn with_period(self, period: Duration) -> Self {
+        ExporterBuilder {
+            period: Some(period),
+            ..self
+        }
+    }
+
+    /// Set the resource associated with all meters, exported as tags of
+    /// DogStatsD lines.
+    pub fn with_resource(self, resource: Resource) -> Self {
+        ExporterBuilder {
+            resource: Some(resource),
+            ..self
+        }
+    }
+
+    /// Build a new push controller, returning errors if they arise.
+    pub fn try_init(self) -> Result<PushController> {
+        let exporter = StatsdExporter::new(
+            &self.endpoint,
+            self.prefix,
+            self.flavor,
+            self.recorder_format,
+            self.max_packet_size,
+        )
+        .map_err(|err| MetricsError::Other(err.to_string()))?;
+
+        let mut push_builder = controllers::push(
+            self.recorder_format.aggregator_selector(),
+            export_kind::Selector::DeltaCounters,
+            exporter,
+            self.spawn,
+            self.interval,
+        );
+        if let Some(period) = self.period {
+            push_builder = push_builder.with_period(period);
+        }
+        if let Some(resource) = self.resource {
+            push_builder = push_builder.with_resource(resource);
+        }
+
+        let controller = push_builder.build();
+        global::set_meter_provider(controller.provider());
+        Ok(controller)
+    }
+
+    /// Build a new push controller.
+    ///
+    /// # Panics
+    ///
+    /// This panics if the endpoint cannot be resolved or the socket cannot be
+    /// bound.
+    pub fn init(self) -> PushController {
+        self.try_init().unwrap()
+    }
+}
+
+/// An exporter sending delta aggregations to a StatsD agent.
+///
+/// Use with a push controller and the `DeltaCounters` export kind selector,
+/// which the exporter also exports with.
+#[derive(Debug)]
+pub struct StatsdExporter {
+    socket: UdpSocket,
+    endpoint: SocketAddr,
+    prefix: Option<String>,
+    flavor: Flavor,
+    recorder_format: RecorderFormat,
+    max_packet_size: usize,
+}
+
+impl StatsdExporter {
+    /// Create a new exporter sending to the given endpoint.
+    pub fn new(
+        endpoint: &str,
+        prefix: Option<String>,
+        flavor: Flavor,
+        recorder_format: RecorderFormat,
+        max_packet_size: usize,
+    ) -> io::Result<Self> {
+        let endpoint = endpoint.to_socket_addrs()?.next().ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("could not resolve {}", endpoint),
+            )
+        })?;
+        let local: SocketAddr = if endpoint.is_ipv4() {
+            (Ipv4Addr::UNSPECIFIED, 0).into()
+        } else {
+            (Ipv6Addr::UNSPECIFIED, 0).into()
+        };
+
+        Ok(StatsdExporter {
+            socket: UdpSocket::bind(local)?,
+            endpoint,
+            prefix,
+            flavor,
+            recorder_format,
+            max_packet_size,
+        })
+    }
+
+    fn write_record(&self, record: &Record<'_>, packets: &mut Packets) -> Result<()> {
+        let agg = record.aggregator().ok_or(MetricsError::NoDataCollected)?;
+        let desc = record.descriptor();
+        let kind = desc.number_kind();
+        let line = Line {
+            name: self.metric_name(desc.name()),
+            tags: self.tags(record),
+        };
+
+        if let Some(sum) = agg.as_any().downcast_ref::<SumAggregator>() {
+            let sum = sum.sum()?;
+            match self.export_kind_for(desc) {
+                ExportKind::Delta => packets.push(line.format("", &sum, kind, "c")),
+                _ => self.write_gauge(&line, "", &sum, kind, packets),
+            }
+        } else if let Some(last_value) = agg.as_any().downcast_ref::<LastValueAggregator>() {
+            let (value, _timestamp) = last_value.last_value()?;
+            self.write_gauge(&line, "", &value, kind, packets);
+        } else if let Some(array) = agg.as_any().downcast_ref::<ArrayAggregator>() {
+            match self.recorder_format {
+                RecorderFormat::Histogram | RecorderFormat::Distribution => {
+                    let metric_type = if self.recorder_format == RecorderFormat::Histogram {
+                        "h"
+                    } else {
+                        "d"
+                    };
+                    for point in array.points()? {
+                        packets.push(line.format("", &point, kind, metric_type));
+                    }
+                }
+                RecorderFormat::Expanded => self.write_expanded(&line, array, kind, packets)?,
+            }
+        } else if let Some(mmsc) = agg.as_any().downcast_ref::<MinMaxSumCountAggregator>() {
+            self.write_expanded(&line, mmsc, kind, packets)?;
+        } else if let Some(sketch) = agg.as_any().downcast_ref::<DDSKetchAggregator>() {
+            self.write_expanded(&line, sketch, kind, packets)?;
+        }
+
+        Ok(())
+    }
+
+    fn write_gauge(
+        &self,
+        line: &Line,
+        suffix: &str,
+        value: &Number,
+        kind: &NumberKind,
+        packets: &mut Packets,
+    ) {
+        // StatsD reads signed gauge values as changes of the current value,
+        // a negative value is only set after resetting the gauge to zero.
+        if self.flavor == Flavor::Statsd && value.is_negative(kind) {
+            packets.push(line.format(suffix, &Number::from(0u64), &NumberKind::U64, "g"));
+        }
+        packets.push(line.format(suffix, value, kind, "g"));
+    }
+
+    fn write_expanded<A>(
+        &self,
+        line: &Line,
+        agg: &A,
+        kind: &NumberKind,
+        packets: &mut Packets,
+    ) -> Result<()>
+    where
+        A: Min + Max + Sum + Count,
+    {
+        self.write_gauge(line, ".min", &agg.min()?, kind, packets);
+        self.write_gauge(line, ".max", &agg.max()?, kind, packets);
+        packets.push(line.format(".sum", &agg.sum()?, kind, "c"));
+        packets.push(line.format(".count", &Number::from(agg.count()?), &NumberKind::U64, "c"));
+        Ok(())
+    }
+
+    fn metric_name(&self, name: &str) -> String {
+        let name = sanitize(name, &[':', '|', '@', '#']);
+        match &self.prefix {
+            Some(prefix) => format!("{}.{}", prefix, name),
+            None => name,
+        }
+    }
+
+    fn tags(&self, record: &Record<'_>) -> String {
+        if self.flavor == Flavor::Statsd {
+            return String::new();
+        }
+
+        let tags = record
+            .resource()
+            .iter()
+            .chain(record.labels().iter())
+            .map(|(key, value)| {
+                format!(
+                    "{}:{}",
+                    sanitize(key.as_str(), &[':', '|', ',', '#']),
+                    sanitize(&String::from(value.clone()), &['|', ',', '#'])
+                )
+            })
+            .collect::<Vec<_>>();
+        if tags.is_empty() {
+            String::new()
+        } else {
+            format!("|#{}", tags.join(","))
+        }
+    }
+}
+
+impl ExportKindSelector for StatsdExporter {
+    fn export_kind_for(&self, descriptor: &Descriptor) -> ExportKind {
+        export_kind::Selector::DeltaCounters.export_kind_for(descriptor)
+    }
+}
+
+impl Exporter for StatsdExporter {
+    fn export(&self, checkpoint_set: &mut dyn CheckpointSet) -> Result<()> {
+        let mut packets = Packets::new(self.max_packet_size);
+        checkpoint_set.try_for_each(self, &mut |record| self.write_record(record, &mut packets))?;
+
+        for packet in packets.finish() {
+            self.socket
+                .send_to(packet.as_bytes(), self.endpoint)
+                .map_err(|err| MetricsError::Other(err.to_string()))?;
+        }
+        Ok(())
+    }
+}
+
+/// The metric name and tags shared by the lines of a record.
+struct Line {
+    name: String,
+    tags: String,
+}
+
+impl Line {
+    fn format(&self, suffix: &str, value: &Number, kind: &NumberKind, metric_type: &str) -> String {
+        let value = match kind {
+            NumberKind::F64 => value.to_f64(kind).to_string(),
+            NumberKind::I64 => value.to_i64(kind).to_string(),
+            NumberKind::U64 => value.to_u64(kind).to_string(),
+        };
+        format!(
+            "{}{}:{}|{}{}",
+            self.name, suffix, value, metric_type, self.tags
+        )
+    }
+}
+
+/// Packs lines into datagrams of at most a maximum size.
+#[derive(Debug)]
+struct Packets {
+    max_size: usize,
+    current: String,
+    finished: Vec<String>,
+}
+
+impl Packets {
+    fn new(max_size: usize) -> Self {
+        Packets {
+            max_size,
+            current: String::new(),
+            finished: Vec::new(),
+        }
+    }
+
+    fn push(&mut self, line: String) {
+        if !self.current.is_empty() && self.current.len() + 1 + line.len() > self.max_size {
+            self.finished.push(std::mem::take(&mut self.current));
+        }
+        if !self.current.is_empty() {
+            self.current.push('\n');
+        }
+        self.current.push_str(&line);
+    }
+
+    fn finish(mut self) -> Vec<String> {
+        if !self.current.is_empty() {
+            self.finished.push(self.current);
+        }
+        self.finished
+    }
+}
+
+/// Replaces the characters reserved by the line format with underscores.
+fn sanitize(s: &str, reserved: &[char]) -> String {
+    s.chars()
+        .map(|c| {
+            if reserved.contains(&c) || c.is_whitespace() {
+                '_'
+            } else {
+                c
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pack_lines() {
+        let mut packets = Packets::new(11);
+        packets.push("a:1|c".to_string());
+        packets.push("b:2|c".to_string());
+        packets.push("c:3|c".to_string());
+        packets.push("long.name:4|c".to_string());
+        packets.push("d:5|c".to_string());
+
+        assert_eq!(
+            packets.finish(),
+            vec![
+                "a:1|c\nb:2|c".to_string(),
+                "c:3|c".to_string(),
+                "long.name:4|c".to_string(),
+                "d:5|c".to_string(),
+            ]
+        );
+    }
+
+    #[test]
+    fn sanitize_reserved_characters() {
+        assert_eq!(
+            sanitize("http:requests|total", &[':', '|']),
+            "http_requests_total"
+        );
+        assert_eq!(sanitize("a b\nc", &[]), "a_b_c");
+    }
+}
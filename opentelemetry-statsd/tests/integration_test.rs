@@ -0,0 +1,138 @@
+use opentelemetry::metrics::MeterProvider;
+use opentelemetry::sdk::{metrics::PushController, Resource};
+use opentelemetry::KeyValue;
+use opentelemetry_statsd::{Flavor, RecorderFormat};
+use std::net::UdpSocket;
+use std::time::Duration;
+
+fn agent() -> UdpSocket {
+    let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+    socket
+        .set_read_timeout(Some(Duration::from_secs(5)))
+        .unwrap();
+    socket
+}
+
+fn controller(
+    agent: &UdpSocket,
+    flavor: Flavor,
+    recorder_format: RecorderFormat,
+    max_packet_size: usize,
+) -> PushController {
+    opentelemetry_statsd::exporter(
+        // The worker is never polled, the tests export with `force_flush`.
+        |_worker| (),
+        |_period| futures::stream::pending::<()>(),
+    )
+    .with_endpoint(agent.local_addr().unwrap().to_string())
+    .with_prefix("app")
+    .with_flavor(flavor)
+    .with_recorder_format(recorder_format)
+    .with_max_packet_size(max_packet_size)
+    .with_resource(Resource::new(vec![KeyValue::new("R", "V")]))
+    .init()
+}
+
+fn receive(agent: &UdpSocket) -> Vec<String> {
+    let mut buf = [0; 65536];
+    let len = agent.recv(&mut buf).unwrap();
+    let mut lines = String::from_utf8_lossy(&buf[..len])
+        .split('\n')
+        .map(str::to_string)
+        .collect::<Vec<_>>();
+    lines.sort();
+    lines
+}
+
+#[test]
+fn test_dogstatsd() {
+    let agent = agent();
+    let controller = controller(
+        &agent,
+        Flavor::DogStatsd,
+        RecorderFormat::Distribution,
+        1432,
+    );
+    let meter = controller.provider().meter("test");
+
+    let counter = meter.u64_counter("requests").init();
+    let up_down_counter = meter.i64_up_down_counter("connections").init();
+    let recorder = meter.f64_value_recorder("latency").init();
+
+    counter.add(3, &[KeyValue::new("method", "GET")]);
+    up_down_counter.add(-2, &[]);
+    recorder.record(0.5, &[]);
+    recorder.record(1.5, &[]);
+    controller.force_flush().unwrap();
+
+    assert_eq!(
+        receive(&agent),
+        vec![
+            "app.connections:-2|g|#R:V",
+            "app.latency:0.5|d|#R:V",
+            "app.latency:1.5|d|#R:V",
+            "app.requests:3|c|#R:V,method:GET",
+        ]
+    );
+
+    // Counters are sent as deltas.
+    counter.add(4, &[KeyValue::new("method", "GET")]);
+    controller.force_flush().unwrap();
+
+    assert_eq!(receive(&agent), vec!["app.requests:4|c|#R:V,method:GET"]);
+}
+
+#[test]
+fn test_statsd_expanded() {
+    let agent = agent();
+    let controller = controller(&agent, Flavor::Statsd, RecorderFormat::Expanded, 1432);
+    let meter = controller.provider().meter("test");
+
+    let up_down_counter = meter.i64_up_down_counter("connections").init();
+    let recorder = meter.i64_value_recorder("size").init();
+
+    up_down_counter.add(-2, &[KeyValue::new("dropped", "label")]);
+    recorder.record(1, &[]);
+    recorder.record(5, &[]);
+    controller.force_flush().unwrap();
+
+    assert_eq!(
+        receive(&agent),
+        vec![
+            "app.connections:-2|g",
+            "app.connections:0|g",
+            "app.size.count:2|c",
+            "app.size.max:5|g",
+            "app.size.min:1|g",
+            "app.size.sum:6|c",
+        ]
+    );
+}
+
+#[test]
+fn test_max_packet_size() {
+    let agent = agent();
+    let controller = controller(&agent, Flavor::Statsd, RecorderFormat::Histogram, 20);
+    let recorder = controller
+        .provider()
+        .meter("test")
+        .u64_value_recorder("a")
+        .init();
+
+    for value in 1..=4 {
+        recorder.record(value, &[]);
+    }
+    controller.force_flush().unwrap();
+
+    let mut lines = Vec::new();
+    for _ in 0..2 {
+        let packet = receive(&agent);
+        assert_eq!(packet.len(), 2);
+        lines.extend(packet);
+    }
+    lines.sort();
+    assert_eq!(
+        lines,
+        vec!["app.a:1|h", "app.a:2|h", "app.a:3|h", "app.a:4|h"]
+    );
+}
@@ -112,6 +112,14 @@ impl Context {
         get_current(|cx| cx.clone())
     }
 
+    /// Applies a function to the current thread's context without cloning it.
+    ///
+    /// The function must not attach another context.
+    #[cfg(feature = "metrics")]
+    pub(crate) fn map_current<F: FnMut(&Context) -> T, T>(f: F) -> T {
+        get_current(f)
+    }
+
     /// Returns a clone of the current thread's context with the given value.
     ///
     /// This is a more efficient form of `Context::current().with_value(value)`
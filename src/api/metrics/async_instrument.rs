@@ -1,6 +1,7 @@
 //! Async metrics
 use crate::metrics::{sdk_api, Number};
 use crate::KeyValue;
+use futures::future::BoxFuture;
 use std::fmt;
 use std::marker;
 use std::sync::Arc;
@@ -13,12 +14,15 @@ use std::sync::Arc;
 #[derive(Debug)]
 pub struct Observation {
     number: Number,
-    instrument: Arc<dyn sdk_api::AsyncInstrumentCore>,
+    instrument: Arc<dyn sdk_api::AsyncInstrumentCore + Send + Sync>,
 }
 
 impl Observation {
     /// Create a new observation for an instrument
-    pub(crate) fn new(number: Number, instrument: Arc<dyn sdk_api::AsyncInstrumentCore>) -> Self {
+    pub(crate) fn new(
+        number: Number,
+        instrument: Arc<dyn sdk_api::AsyncInstrumentCore + Send + Sync>,
+    ) -> Self {
         Observation { number, instrument }
     }
 
@@ -27,7 +31,7 @@ impl Observation {
         &self.number
     }
     /// The instrument used to record this observation
-    pub fn instrument(&self) -> &Arc<dyn sdk_api::AsyncInstrumentCore> {
+    pub fn instrument(&self) -> &Arc<dyn sdk_api::AsyncInstrumentCore + Send + Sync> {
         &self.instrument
     }
 }
@@ -45,10 +49,31 @@ type I64ObserverCallback = Box<dyn Fn(ObserverResult<i64>) + Send + Sync + 'stat
 /// reported as a batch of observations.
 pub type BatchObserverCallback = Box<dyn Fn(BatchObserverResult) + Send + Sync>;
 
+/// The future returned by asynchronous observer callbacks, capturing
+/// observations until it completes.
+pub type ObserverFuture = BoxFuture<'static, ()>;
+
+/// A type of asynchronous callback that `f64` observers run.
+type F64ObserverFutureCallback =
+    Box<dyn Fn(ObserverResult<f64>) -> ObserverFuture + Send + Sync + 'static>;
+
+/// A type of asynchronous callback that `u64` observers run.
+type U64ObserverFutureCallback =
+    Box<dyn Fn(ObserverResult<u64>) -> ObserverFuture + Send + Sync + 'static>;
+
+/// A type of asynchronous callback that `i64` observers run.
+type I64ObserverFutureCallback =
+    Box<dyn Fn(ObserverResult<i64>) -> ObserverFuture + Send + Sync + 'static>;
+
+/// An asynchronous callback argument for use with any Observer instrument
+/// that will be reported as a batch of observations.
+pub type BatchObserverFutureCallback =
+    Box<dyn Fn(BatchObserverResult) -> ObserverFuture + Send + Sync>;
+
 /// Data passed to an observer callback to capture observations for one
 /// asynchronous metric instrument.
 pub struct ObserverResult<T> {
-    instrument: Arc<dyn sdk_api::AsyncInstrumentCore>,
+    instrument: Arc<dyn sdk_api::AsyncInstrumentCore + Send + Sync>,
     f: fn(&[KeyValue], &[Observation]),
     _marker: marker::PhantomData<T>,
 }
@@ -59,7 +84,7 @@ where
 {
     /// New observer result for a given metric instrument
     fn new(
-        instrument: Arc<dyn sdk_api::AsyncInstrumentCore>,
+        instrument: Arc<dyn sdk_api::AsyncInstrumentCore + Send + Sync>,
         f: fn(&[KeyValue], &[Observation]),
     ) -> Self {
         ObserverResult {
@@ -119,6 +144,11 @@ impl BatchObserverResult {
 }
 
 /// Called when collecting async instruments
+///
+/// The `*Future` variants hold the callbacks of the observers created by the
+/// `*_async` meter methods. More variants may be added, so matches outside of
+/// this crate must handle unknown runners.
+#[non_exhaustive]
 pub enum AsyncRunner {
     /// Callback for `f64` observed values
     F64(F64ObserverCallback),
@@ -128,6 +158,14 @@ pub enum AsyncRunner {
     U64(U64ObserverCallback),
     /// Callback for batch observed values
     Batch(BatchObserverCallback),
+    /// Asynchronous callback for `f64` observed values
+    F64Future(F64ObserverFutureCallback),
+    /// Asynchronous callback for `i64` observed values
+    I64Future(I64ObserverFutureCallback),
+    /// Asynchronous callback for `u64` observed values
+    U64Future(U64ObserverFutureCallback),
+    /// Asynchronous callback for batch observed values
+    BatchFuture(BatchObserverFutureCallback),
 }
 
 impl AsyncRunner {
@@ -135,11 +173,26 @@ impl AsyncRunner {
     /// of that instrument. Each call to the function receives one captured
     /// observation. (The function accepts multiple observations so the same
     /// implementation can be used for batch runners.)
+    ///
+    /// The futures of asynchronous callbacks are driven to completion on the
+    /// current thread, see `start` to drive them elsewhere.
     pub fn run(
         &self,
-        instrument: Arc<dyn sdk_api::AsyncInstrumentCore>,
+        instrument: Arc<dyn sdk_api::AsyncInstrumentCore + Send + Sync>,
         f: fn(&[KeyValue], &[Observation]),
     ) {
+        if let Some(future) = self.start(instrument, f) {
+            futures::executor::block_on(future)
+        }
+    }
+
+    /// Like `run`, but returns the future of asynchronous callbacks instead of
+    /// driving it. The future captures observations while it is polled.
+    pub fn start(
+        &self,
+        instrument: Arc<dyn sdk_api::AsyncInstrumentCore + Send + Sync>,
+        f: fn(&[KeyValue], &[Observation]),
+    ) -> Option<ObserverFuture> {
         match self {
             AsyncRunner::F64(run) => run(ObserverResult::new(instrument, f)),
             AsyncRunner::I64(run) => run(ObserverResult::new(instrument, f)),
@@ -147,7 +200,26 @@ impl AsyncRunner {
             // TODO: this should not require an instrument to call. consider
             // moving to separate struct
             AsyncRunner::Batch(run) => run(BatchObserverResult::new(f)),
+            AsyncRunner::F64Future(run) => return Some(run(ObserverResult::new(instrument, f))),
+            AsyncRunner::I64Future(run) => return Some(run(ObserverResult::new(instrument, f))),
+            AsyncRunner::U64Future(run) => return Some(run(ObserverResult::new(instrument, f))),
+            AsyncRunner::BatchFuture(run) => return Some(run(BatchObserverResult::new(f))),
         }
+
+        None
+    }
+
+    /// Like `start`, for the callbacks of batch observers, which observe the
+    /// instruments of their batch rather than a single instrument. Other
+    /// callbacks are not run.
+    pub(crate) fn start_batch(&self, f: fn(&[KeyValue], &[Observation])) -> Option<ObserverFuture> {
+        match self {
+            AsyncRunner::Batch(run) => run(BatchObserverResult::new(f)),
+            AsyncRunner::BatchFuture(run) => return Some(run(BatchObserverResult::new(f))),
+            _ => {}
+        }
+
+        None
     }
 }
 
@@ -170,6 +242,22 @@ impl fmt::Debug for AsyncRunner {
                 .debug_struct("AsyncRunner::Batch")
                 .field("closure", &"Fn(BatchObserverResult)")
                 .finish(),
+            AsyncRunner::F64Future(_) => f
+                .debug_struct("AsyncRunner::F64Future")
+                .field("closure", &"Fn(ObserverResult) -> ObserverFuture")
+                .finish(),
+            AsyncRunner::I64Future(_) => f
+                .debug_struct("AsyncRunner::I64Future")
+                .field("closure", &"Fn(ObserverResult) -> ObserverFuture")
+                .finish(),
+            AsyncRunner::U64Future(_) => f
+                .debug_struct("AsyncRunner::U64Future")
+                .field("closure", &"Fn(ObserverResult) -> ObserverFuture")
+                .finish(),
+            AsyncRunner::BatchFuture(_) => f
+                .debug_struct("AsyncRunner::BatchFuture")
+                .field("closure", &"Fn(BatchObserverResult) -> ObserverFuture")
+                .finish(),
         }
     }
 }
@@ -1,4 +1,5 @@
 use crate::metrics::{InstrumentConfig, InstrumentKind, NumberKind};
+use crate::Unit;
 use fnv::FnvHasher;
 use std::hash::{Hash, Hasher};
 
@@ -67,6 +68,11 @@ impl Descriptor {
         self.config.unit.as_ref().map(|unit| unit.as_ref())
     }
 
+    /// Assign a new unit
+    pub fn set_unit(&mut self, unit: Unit) {
+        self.config.unit = Some(unit);
+    }
+
     /// The name of the library that provided instrumentation for this instrument.
     pub fn instrumentation_name(&self) -> &str {
         self.config.instrumentation_name.as_str()
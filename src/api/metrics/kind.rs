@@ -1,3 +1,6 @@
+#[cfg(feature = "serialize")]
+use serde::{Deserialize, Serialize};
+
 /// Kinds of OpenTelemetry metric instruments
 ///
 /// | **Name** | Instrument kind | Function(argument) | Default aggregation | Notes |
@@ -8,6 +11,7 @@
 /// | **UpDownCounter**       | Synchronous additive | Add(increment) | Sum | Per-request, part of a non-monotonic sum |
 /// | **SumObserver**         | Asynchronous additive monotonic | Observe(sum) | Sum | Per-interval, reporting a monotonic sum |
 /// | **UpDownSumObserver**   | Asynchronous additive | Observe(sum) | Sum | Per-interval, reporting a non-monotonic sum |
+#[cfg_attr(feature = "serialize", derive(Deserialize, Serialize))]
 #[derive(Clone, Debug, PartialEq, Hash)]
 pub enum InstrumentKind {
     /// A synchronous per-request recorder of non-additive measurements.
@@ -60,6 +64,11 @@ impl InstrumentKind {
         )
     }
 
+    /// Whether this kind of instrument exposes a non-decreasing sum.
+    pub fn monotonic(&self) -> bool {
+        matches!(self, InstrumentKind::Counter | InstrumentKind::SumObserver)
+    }
+
     /// Whether this kind of instrument receives precomputed sums.
     pub fn precomputed_sum(&self) -> bool {
         self.adding() && self.asynchronous()
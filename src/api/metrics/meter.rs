@@ -1,11 +1,14 @@
 use crate::{
+    global,
     metrics::{
-        sdk_api, AsyncRunner, BatchObserver, BatchObserverCallback, CounterBuilder, Descriptor,
-        Measurement, NumberKind, ObserverResult, Result, SumObserverBuilder, UpDownCounterBuilder,
-        UpDownSumObserverBuilder, ValueObserverBuilder, ValueRecorderBuilder,
+        sdk_api, AsyncRunner, BatchObserver, BatchObserverCallback, BatchObserverResult,
+        CounterBuilder, Descriptor, Measurement, NumberKind, ObserverResult, Result,
+        SumObserverBuilder, UpDownCounterBuilder, UpDownSumObserverBuilder, ValueObserverBuilder,
+        ValueRecorderBuilder,
     },
     Context, KeyValue,
 };
+use futures::Future;
 use std::fmt;
 use std::sync::Arc;
 
@@ -158,6 +161,146 @@ impl Meter {
         )
     }
 
+    /// Creates a new floating point `ValueObserverBuilder` instrument with the given name
+    /// and asynchronous callback, whose future is driven on each collection.
+    pub fn f64_value_observer_async<T, F, Fut>(
+        &self,
+        name: T,
+        callback: F,
+    ) -> ValueObserverBuilder<'_, f64>
+    where
+        T: Into<String>,
+        F: Fn(ObserverResult<f64>) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = ()> + Send + 'static,
+    {
+        ValueObserverBuilder::new(
+            self,
+            name.into(),
+            AsyncRunner::F64Future(Box::new(move |result| Box::pin(callback(result)))),
+            NumberKind::F64,
+        )
+    }
+
+    /// Creates a new integral `ValueObserverBuilder` instrument with the given name
+    /// and asynchronous callback, whose future is driven on each collection.
+    pub fn u64_value_observer_async<T, F, Fut>(
+        &self,
+        name: T,
+        callback: F,
+    ) -> ValueObserverBuilder<'_, u64>
+    where
+        T: Into<String>,
+        F: Fn(ObserverResult<u64>) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = ()> + Send + 'static,
+    {
+        ValueObserverBuilder::new(
+            self,
+            name.into(),
+            AsyncRunner::U64Future(Box::new(move |result| Box::pin(callback(result)))),
+            NumberKind::U64,
+        )
+    }
+
+    /// Creates a new integral `ValueObserverBuilder` instrument with the given name
+    /// and asynchronous callback, whose future is driven on each collection.
+    pub fn i64_value_observer_async<T, F, Fut>(
+        &self,
+        name: T,
+        callback: F,
+    ) -> ValueObserverBuilder<'_, i64>
+    where
+        T: Into<String>,
+        F: Fn(ObserverResult<i64>) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = ()> + Send + 'static,
+    {
+        ValueObserverBuilder::new(
+            self,
+            name.into(),
+            AsyncRunner::I64Future(Box::new(move |result| Box::pin(callback(result)))),
+            NumberKind::I64,
+        )
+    }
+
+    /// Creates a new floating point `SumObserverBuilder` instrument with the given name
+    /// and asynchronous callback, whose future is driven on each collection.
+    pub fn f64_sum_observer_async<T, F, Fut>(
+        &self,
+        name: T,
+        callback: F,
+    ) -> SumObserverBuilder<'_, f64>
+    where
+        T: Into<String>,
+        F: Fn(ObserverResult<f64>) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = ()> + Send + 'static,
+    {
+        SumObserverBuilder::new(
+            self,
+            name.into(),
+            AsyncRunner::F64Future(Box::new(move |result| Box::pin(callback(result)))),
+            NumberKind::F64,
+        )
+    }
+
+    /// Creates a new integral `SumObserverBuilder` instrument with the given name
+    /// and asynchronous callback, whose future is driven on each collection.
+    pub fn u64_sum_observer_async<T, F, Fut>(
+        &self,
+        name: T,
+        callback: F,
+    ) -> SumObserverBuilder<'_, u64>
+    where
+        T: Into<String>,
+        F: Fn(ObserverResult<u64>) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = ()> + Send + 'static,
+    {
+        SumObserverBuilder::new(
+            self,
+            name.into(),
+            AsyncRunner::U64Future(Box::new(move |result| Box::pin(callback(result)))),
+            NumberKind::U64,
+        )
+    }
+
+    /// Creates a new floating point `UpDownSumObserverBuilder` instrument with the given name
+    /// and asynchronous callback, whose future is driven on each collection.
+    pub fn f64_up_down_sum_observer_async<T, F, Fut>(
+        &self,
+        name: T,
+        callback: F,
+    ) -> UpDownSumObserverBuilder<'_, f64>
+    where
+        T: Into<String>,
+        F: Fn(ObserverResult<f64>) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = ()> + Send + 'static,
+    {
+        UpDownSumObserverBuilder::new(
+            self,
+            name.into(),
+            AsyncRunner::F64Future(Box::new(move |result| Box::pin(callback(result)))),
+            NumberKind::F64,
+        )
+    }
+
+    /// Creates a new integral `UpDownSumObserverBuilder` instrument with the given name
+    /// and asynchronous callback, whose future is driven on each collection.
+    pub fn i64_up_down_sum_observer_async<T, F, Fut>(
+        &self,
+        name: T,
+        callback: F,
+    ) -> UpDownSumObserverBuilder<'_, i64>
+    where
+        T: Into<String>,
+        F: Fn(ObserverResult<i64>) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = ()> + Send + 'static,
+    {
+        UpDownSumObserverBuilder::new(
+            self,
+            name.into(),
+            AsyncRunner::I64Future(Box::new(move |result| Box::pin(callback(result)))),
+            NumberKind::I64,
+        )
+    }
+
     /// Creates a new `ValueRecorderBuilder` for `f64` values with the given name.
     pub fn f64_value_recorder<T>(&self, name: T) -> ValueRecorderBuilder<'_, f64>
     where
@@ -216,8 +359,78 @@ impl Meter {
 
     /// Creates a new `BatchObserver` that supports making batches of observations for
     /// multiple instruments.
+    ///
+    /// The callback is registered right away and observes the instruments
+    /// created by the returned `BatchObserver` once they are shared with it,
+    /// errors registering it are reported to the global error handler. Prefer
+    /// `build_batch_observer`, which passes the instruments to the callback.
     pub fn batch_observer(&self, callback: BatchObserverCallback) -> BatchObserver<'_> {
-        BatchObserver::new(self, AsyncRunner::Batch(callback))
+        if let Err(err) = self.core.new_batch_observer(AsyncRunner::Batch(callback)) {
+            global::handle_error(err);
+        }
+        BatchObserver::new(self)
+    }
+
+    /// Creates a batch observer, whose callback observes multiple asynchronous
+    /// instruments at once on each collection.
+    ///
+    /// `builder` creates the instruments of the batch with the given
+    /// `BatchObserver`, and returns the callback observing them.
+    ///
+    /// ```
+    /// use opentelemetry::{global, metrics::BatchObserverResult};
+    ///
+    /// let meter = global::meter("my-service");
+    /// meter
+    ///     .build_batch_observer(|batch| {
+    ///         let resident = batch.u64_value_observer("memory.resident").try_init()?;
+    ///         let virt = batch.u64_value_observer("memory.virtual").try_init()?;
+    ///
+    ///         Ok(move |result: BatchObserverResult| {
+    ///             result.observe(&[], &[resident.observation(1024), virt.observation(4096)])
+    ///         })
+    ///     })
+    ///     .unwrap();
+    /// ```
+    pub fn build_batch_observer<B, F>(&self, builder: B) -> Result<()>
+    where
+        B: FnOnce(BatchObserver<'_>) -> Result<F>,
+        F: Fn(BatchObserverResult) + Send + Sync + 'static,
+    {
+        let callback = builder(BatchObserver::new(self))?;
+        self.core
+            .new_batch_observer(AsyncRunner::Batch(Box::new(callback)))
+    }
+
+    /// Creates a batch observer like `build_batch_observer`, whose asynchronous
+    /// callback returns a future driven on each collection.
+    ///
+    /// ```
+    /// use opentelemetry::{global, metrics::BatchObserverResult};
+    ///
+    /// let meter = global::meter("my-service");
+    /// meter
+    ///     .build_batch_observer_async(|batch| {
+    ///         let connections = batch.u64_value_observer("db.connections").try_init()?;
+    ///
+    ///         Ok(move |result: BatchObserverResult| {
+    ///             let observation = connections.observation(8);
+    ///             async move { result.observe(&[], &[observation]) }
+    ///         })
+    ///     })
+    ///     .unwrap();
+    /// ```
+    pub fn build_batch_observer_async<B, F, Fut>(&self, builder: B) -> Result<()>
+    where
+        B: FnOnce(BatchObserver<'_>) -> Result<F>,
+        F: Fn(BatchObserverResult) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = ()> + Send + 'static,
+    {
+        let callback = builder(BatchObserver::new(self))?;
+        self.core
+            .new_batch_observer(AsyncRunner::BatchFuture(Box::new(move |result| {
+                Box::pin(callback(result))
+            })))
     }
 
     /// Atomically record a batch of measurements.
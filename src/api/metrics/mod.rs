@@ -2,6 +2,7 @@
 
 use std::result;
 use std::sync::PoisonError;
+use std::time::Duration;
 use thiserror::Error;
 
 mod async_instrument;
@@ -19,7 +20,10 @@ mod sync_instrument;
 mod up_down_counter;
 mod value_recorder;
 
-pub use async_instrument::{AsyncRunner, BatchObserverCallback, Observation, ObserverResult};
+pub use async_instrument::{
+    AsyncRunner, BatchObserverCallback, BatchObserverFutureCallback, BatchObserverResult,
+    Observation, ObserverFuture, ObserverResult,
+};
 pub use config::InstrumentConfig;
 pub use counter::{BoundCounter, Counter, CounterBuilder};
 pub use descriptor::Descriptor;
@@ -62,12 +66,34 @@ pub enum MetricsError {
     /// Errors when registering to instruments with the same name and kind
     #[error("A metric was already registered by this name with another kind or number type: {0}")]
     MetricKindMismatch(String),
+    /// Errors when an instrument name, unit or description is not valid.
+    #[error("Invalid instrument: {0}")]
+    InvalidInstrument(String),
+    /// Errors when instruments are registered by the same name with another
+    /// unit, description, kind or number type.
+    #[error("Duplicate instrument: {0}")]
+    DuplicateInstrument(String),
     /// Errors when processor logic is incorrect
     #[error("Inconsistent processor state")]
     InconsistentState,
     /// Errors when aggregator cannot subtract
     #[error("Aggregator does not subtract")]
     NoSubtraction,
+    /// Errors when an instrument records more distinct label sets than the
+    /// configured cardinality limit.
+    #[error("Cardinality limit exceeded: {0}")]
+    CardinalityLimitExceeded(String),
+    /// Errors when the processor did not keep the state required by the
+    /// requested export kind
+    #[error("Inconsistent export kind: {0}")]
+    InconsistentExportKind(String),
+    /// Errors when an export takes longer than the configured timeout
+    #[error("Metrics export timed out after {0:?}")]
+    ExportTimedOut(Duration),
+    /// Errors when asynchronous observer callbacks do not complete within the
+    /// configured timeout
+    #[error("Metric observers timed out after {0:?}")]
+    ObserverTimedOut(Duration),
 }
 
 impl<T> From<PoisonError<T>> for MetricsError {
@@ -1,3 +1,5 @@
+#[cfg(feature = "serialize")]
+use serde::{Deserialize, Serialize};
 use std::cmp;
 use std::fmt;
 use std::sync::atomic::{AtomicU64, Ordering};
@@ -5,6 +7,7 @@ use std::sync::atomic::{AtomicU64, Ordering};
 /// Number represents either an integral or a floating point value. It
 /// needs to be accompanied with a source of NumberKind that describes
 /// the actual type of the value stored within Number.
+#[cfg_attr(feature = "serialize", derive(Deserialize, Serialize))]
 #[derive(Clone, Debug, Default)]
 pub struct Number(u64);
 
@@ -77,6 +80,7 @@ impl Number {
 }
 
 /// An atomic version of `Number`
+#[cfg_attr(feature = "serialize", derive(Deserialize, Serialize))]
 #[derive(Debug, Default)]
 pub struct AtomicNumber(AtomicU64);
 
@@ -150,6 +154,35 @@ impl AtomicNumber {
     pub fn load(&self) -> Number {
         Number(self.0.load(Ordering::Relaxed))
     }
+
+    /// Stores the given number if it is less than the current number. Both
+    /// numbers must be of the same kind.
+    pub fn fetch_min(&self, number_kind: &NumberKind, val: &Number) {
+        self.store_if(val, |current| {
+            val.partial_cmp(number_kind, current) == Some(cmp::Ordering::Less)
+        })
+    }
+
+    /// Stores the given number if it is greater than the current number. Both
+    /// numbers must be of the same kind.
+    pub fn fetch_max(&self, number_kind: &NumberKind, val: &Number) {
+        self.store_if(val, |current| {
+            val.partial_cmp(number_kind, current) == Some(cmp::Ordering::Greater)
+        })
+    }
+
+    fn store_if<F: Fn(&Number) -> bool>(&self, val: &Number, predicate: F) {
+        let mut current = self.0.load(Ordering::Acquire);
+        while predicate(&Number(current)) {
+            match self
+                .0
+                .compare_exchange_weak(current, val.0, Ordering::AcqRel, Ordering::Acquire)
+            {
+                Ok(_) => return,
+                Err(actual) => current = actual,
+            }
+        }
+    }
 }
 
 impl Clone for AtomicNumber {
@@ -177,6 +210,7 @@ impl From<u64> for Number {
 }
 
 /// A descriptor for the encoded data type of a `Number`
+#[cfg_attr(feature = "serialize", derive(Deserialize, Serialize))]
 #[derive(Clone, Debug, PartialEq, Hash)]
 pub enum NumberKind {
     /// A Number that stores `i64` values.
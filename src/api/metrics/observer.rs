@@ -1,26 +1,100 @@
-use crate::metrics::{
-    sdk_api, AsyncRunner, Descriptor, InstrumentKind, Meter, Number, NumberKind, Observation,
-    Result,
+use crate::{
+    metrics::{
+        sdk_api, AsyncRunner, Descriptor, InstrumentKind, Meter, Number, NumberKind, Observation,
+        Result,
+    },
+    Unit,
 };
 use std::sync::Arc;
 
 /// An Observer callback that can report observations for multiple instruments.
+///
+/// The instruments created by a batch observer are observed by its callback,
+/// see `Meter::build_batch_observer`.
 #[derive(Debug)]
 pub struct BatchObserver<'a> {
     meter: &'a Meter,
-    runner: AsyncRunner,
 }
 
 impl<'a> BatchObserver<'a> {
-    pub(crate) fn new(meter: &'a Meter, runner: AsyncRunner) -> Self {
-        BatchObserver { meter, runner }
+    pub(crate) fn new(meter: &'a Meter) -> Self {
+        BatchObserver { meter }
+    }
+
+    /// Creates a new integral `SumObserverBuilder` instrument with the given name,
+    /// observed by the callback of the batch.
+    pub fn u64_sum_observer<T>(&self, name: T) -> SumObserverBuilder<'a, u64>
+    where
+        T: Into<String>,
+    {
+        SumObserverBuilder::new(self.meter, name.into(), batch_instrument(), NumberKind::U64)
+    }
+
+    /// Creates a new floating point `SumObserverBuilder` instrument with the given name,
+    /// observed by the callback of the batch.
+    pub fn f64_sum_observer<T>(&self, name: T) -> SumObserverBuilder<'a, f64>
+    where
+        T: Into<String>,
+    {
+        SumObserverBuilder::new(self.meter, name.into(), batch_instrument(), NumberKind::F64)
+    }
+
+    /// Creates a new integral `UpDownSumObserverBuilder` instrument with the given name,
+    /// observed by the callback of the batch.
+    pub fn i64_up_down_sum_observer<T>(&self, name: T) -> UpDownSumObserverBuilder<'a, i64>
+    where
+        T: Into<String>,
+    {
+        UpDownSumObserverBuilder::new(self.meter, name.into(), batch_instrument(), NumberKind::I64)
+    }
+
+    /// Creates a new floating point `UpDownSumObserverBuilder` instrument with the given name,
+    /// observed by the callback of the batch.
+    pub fn f64_up_down_sum_observer<T>(&self, name: T) -> UpDownSumObserverBuilder<'a, f64>
+    where
+        T: Into<String>,
+    {
+        UpDownSumObserverBuilder::new(self.meter, name.into(), batch_instrument(), NumberKind::F64)
+    }
+
+    /// Creates a new integral `ValueObserverBuilder` instrument with the given name,
+    /// observed by the callback of the batch.
+    pub fn i64_value_observer<T>(&self, name: T) -> ValueObserverBuilder<'a, i64>
+    where
+        T: Into<String>,
+    {
+        ValueObserverBuilder::new(self.meter, name.into(), batch_instrument(), NumberKind::I64)
+    }
+
+    /// Creates a new integral `ValueObserverBuilder` instrument with the given name,
+    /// observed by the callback of the batch.
+    pub fn u64_value_observer<T>(&self, name: T) -> ValueObserverBuilder<'a, u64>
+    where
+        T: Into<String>,
+    {
+        ValueObserverBuilder::new(self.meter, name.into(), batch_instrument(), NumberKind::U64)
     }
+
+    /// Creates a new floating point `ValueObserverBuilder` instrument with the given name,
+    /// observed by the callback of the batch.
+    pub fn f64_value_observer<T>(&self, name: T) -> ValueObserverBuilder<'a, f64>
+    where
+        T: Into<String>,
+    {
+        ValueObserverBuilder::new(self.meter, name.into(), batch_instrument(), NumberKind::F64)
+    }
+}
+
+/// The runner of the instruments of a batch observer, which are observed by the
+/// callback of the batch instead.
+fn batch_instrument() -> AsyncRunner {
+    AsyncRunner::Batch(Box::new(|_| {}))
 }
 
 /// A metric that captures a precomputed sum of values at a point in time.
 #[derive(Debug)]
 pub struct SumObserver<T> {
-    instrument: Arc<dyn sdk_api::AsyncInstrumentCore>,
+    instrument: Arc<dyn sdk_api::AsyncInstrumentCore + Send + Sync>,
     _marker: std::marker::PhantomData<T>,
 }
 
@@ -71,6 +145,12 @@ impl<'a, T> SumObserverBuilder<'a, T> {
         self
     }
 
+    /// Set the unit of this `SumObserver`
+    pub fn with_unit(mut self, unit: Unit) -> Self {
+        self.descriptor.set_unit(unit);
+        self
+    }
+
     /// Create a `SumObserver` from this configuration.
     pub fn try_init(self) -> Result<SumObserver<T>> {
         let instrument = self
@@ -106,7 +186,7 @@ impl<'a, T> SumObserverBuilder<'a, T> {
 /// in time.
 #[derive(Debug)]
 pub struct UpDownSumObserver<T> {
-    instrument: Arc<dyn sdk_api::AsyncInstrumentCore>,
+    instrument: Arc<dyn sdk_api::AsyncInstrumentCore + Send + Sync>,
     _marker: std::marker::PhantomData<T>,
 }
 
@@ -157,6 +237,12 @@ impl<'a, T> UpDownSumObserverBuilder<'a, T> {
         self
     }
 
+    /// Set the unit of this `UpDownSumObserver`
+    pub fn with_unit(mut self, unit: Unit) -> Self {
+        self.descriptor.set_unit(unit);
+        self
+    }
+
     /// Create a `UpDownSumObserver` from this configuration.
     pub fn try_init(self) -> Result<UpDownSumObserver<T>> {
         let instrument = self
@@ -191,7 +277,7 @@ impl<'a, T> UpDownSumObserverBuilder<'a, T> {
 /// A metric that captures a set of values at a point in time.
 #[derive(Debug)]
 pub struct ValueObserver<T> {
-    instrument: Arc<dyn sdk_api::AsyncInstrumentCore>,
+    instrument: Arc<dyn sdk_api::AsyncInstrumentCore + Send + Sync>,
     _marker: std::marker::PhantomData<T>,
 }
 
@@ -241,6 +327,12 @@ impl<'a, T> ValueObserverBuilder<'a, T> {
         self
     }
 
+    /// Set the unit of this `ValueObserver`
+    pub fn with_unit(mut self, unit: Unit) -> Self {
+        self.descriptor.set_unit(unit);
+        self
+    }
+
     /// Create a `ValueObserver` from this configuration.
     pub fn try_init(self) -> Result<ValueObserver<T>> {
         let instrument = self
@@ -1,5 +1,6 @@
 //! Metrics Registry API
 use crate::{
+    global,
     metrics::{
         sdk_api::{AsyncInstrumentCore, MeterCore, SyncInstrumentCore},
         Meter, MeterProvider,
@@ -10,9 +11,28 @@ use crate::{
 use std::collections::HashMap;
 use std::sync::{Arc, Mutex};
 
+/// The maximum length of instrument names and units.
+const MAX_NAME_LENGTH: usize = 63;
+
+/// The maximum length of instrument descriptions.
+const MAX_DESCRIPTION_LENGTH: usize = 1023;
+
 /// Create a new `RegistryMeterProvider` from a `MeterCore`.
+///
+/// Instruments with an invalid name, unit or description, or registered by the
+/// same name as another instrument with another unit or description, are
+/// reported to the global error handler and created anyway.
 pub fn meter_provider(core: Arc<dyn MeterCore + Send + Sync>) -> RegistryMeterProvider {
-    RegistryMeterProvider(Arc::new(UniqueInstrumentMeterCore::wrap(core)))
+    RegistryMeterProvider(Arc::new(UniqueInstrumentMeterCore::wrap(core, false)))
+}
+
+/// Create a new `RegistryMeterProvider` from a `MeterCore` that fails to create
+/// invalid or duplicate instruments.
+///
+/// Unlike with `meter_provider`, the `try_init` methods of instrument builders
+/// return the errors, and `init` panics.
+pub fn strict_meter_provider(core: Arc<dyn MeterCore + Send + Sync>) -> RegistryMeterProvider {
+    RegistryMeterProvider(Arc::new(UniqueInstrumentMeterCore::wrap(core, true)))
 }
 
 /// A standard `MeterProvider` for wrapping a `MeterCore`.
@@ -30,14 +50,52 @@ struct UniqueInstrumentMeterCore {
     inner: Arc<dyn MeterCore + Send + Sync>,
     sync_state: Mutex<HashMap<UniqueInstrumentKey, UniqueSyncInstrument>>,
     async_state: Mutex<HashMap<UniqueInstrumentKey, UniqueAsyncInstrument>>,
+    /// The descriptor first registered for each instrument name, by any meter.
+    descriptors: Mutex<HashMap<String, Descriptor>>,
+    /// Whether invalid or duplicate instruments fail to be created.
+    strict: bool,
 }
 
 impl UniqueInstrumentMeterCore {
-    fn wrap(inner: Arc<dyn MeterCore + Send + Sync>) -> Self {
+    fn wrap(inner: Arc<dyn MeterCore + Send + Sync>, strict: bool) -> Self {
         UniqueInstrumentMeterCore {
             inner,
             sync_state: Mutex::new(HashMap::default()),
             async_state: Mutex::new(HashMap::default()),
+            descriptors: Mutex::new(HashMap::default()),
+            strict,
+        }
+    }
+
+    /// Validate the descriptor of a new instrument, and check it against the
+    /// instruments registered by the same name.
+    fn check_new(&self, desc: &Descriptor) -> Result<()> {
+        self.report(validate(desc))?;
+        self.check_duplicate(desc)
+    }
+
+    /// Check the descriptor of an instrument against the one first registered
+    /// by the same name.
+    fn check_duplicate(&self, desc: &Descriptor) -> Result<()> {
+        let mut descriptors = self.descriptors.lock()?;
+        let result = match descriptors.get(desc.name()) {
+            Some(registered) => check_duplicate(registered, desc),
+            None => {
+                descriptors.insert(desc.name().to_string(), desc.clone());
+                Ok(())
+            }
+        };
+        self.report(result)
+    }
+
+    /// Return the error in strict mode, otherwise report it.
+    fn report(&self, result: Result<()>) -> Result<()> {
+        match result {
+            Err(err) if !self.strict => {
+                global::handle_error(err);
+                Ok(())
+            }
+            result => result,
         }
     }
 }
@@ -61,8 +119,12 @@ impl MeterCore for UniqueInstrumentMeterCore {
                 let key = UniqueInstrumentKey::from(&descriptor);
                 check_sync_uniqueness(&state, &descriptor, &key).and_then(|instrument| {
                     match instrument {
-                        Some(instrument) => Ok(instrument),
+                        Some(instrument) => {
+                            self.check_duplicate(&descriptor)?;
+                            Ok(instrument)
+                        }
                         None => {
+                            self.check_new(&descriptor)?;
                             let instrument = self.inner.new_sync_instrument(descriptor)?;
                             state.insert(key, instrument.clone());
 
@@ -85,8 +147,12 @@ impl MeterCore for UniqueInstrumentMeterCore {
                 let key = UniqueInstrumentKey::from(&descriptor);
                 check_async_uniqueness(&state, &descriptor, &key).and_then(|instrument| {
                     match instrument {
-                        Some(instrument) => Ok(instrument),
+                        Some(instrument) => {
+                            self.check_duplicate(&descriptor)?;
+                            Ok(instrument)
+                        }
                         None => {
+                            self.check_new(&descriptor)?;
                             let instrument = self.inner.new_async_instrument(descriptor, runner)?;
                             state.insert(key, instrument.clone());
 
@@ -96,6 +162,10 @@ impl MeterCore for UniqueInstrumentMeterCore {
                 })
             })
     }
+
+    fn new_batch_observer(&self, runner: AsyncRunner) -> Result<()> {
+        self.inner.new_batch_observer(runner)
+    }
 }
 
 fn check_sync_uniqueness(
@@ -146,6 +216,76 @@ fn is_equal(a: &Descriptor, b: &Descriptor) -> bool {
     a.instrument_kind() == b.instrument_kind() && a.number_kind() == b.number_kind()
 }
 
+/// Validate the name, unit and description of an instrument.
+///
+/// Names start with a letter, followed by at most 62 letters, digits, `_`, `.`
+/// or `-`. Units are at most 63 printable ASCII characters, and descriptions
+/// at most 1023 characters.
+fn validate(desc: &Descriptor) -> Result<()> {
+    let name = desc.name();
+    let valid_name = name.len() <= MAX_NAME_LENGTH
+        && name
+            .chars()
+            .next()
+            .map_or(false, |first| first.is_ascii_alphabetic())
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '.' || c == '-');
+    if !valid_name {
+        return Err(MetricsError::InvalidInstrument(format!(
+            "name {:?} ({}) must start with a letter, followed by at most {} letters, digits, '_', '.' or '-'",
+            name,
+            desc.instrumentation_name(),
+            MAX_NAME_LENGTH - 1
+        )));
+    }
+
+    if let Some(unit) = desc.unit() {
+        if unit.len() > MAX_NAME_LENGTH || !unit.chars().all(|c| c.is_ascii_graphic() || c == ' ') {
+            return Err(MetricsError::InvalidInstrument(format!(
+                "unit {:?} of {} must be at most {} printable ASCII characters",
+                unit, name, MAX_NAME_LENGTH
+            )));
+        }
+    }
+
+    if let Some(description) = desc.description() {
+        if description.chars().count() > MAX_DESCRIPTION_LENGTH {
+            return Err(MetricsError::InvalidInstrument(format!(
+                "description of {} must be at most {} characters",
+                name, MAX_DESCRIPTION_LENGTH
+            )));
+        }
+    }
+
+    Ok(())
+}
+
+/// Check that an instrument registered by the name of another one, possibly
+/// by another meter, describes the same metric.
+fn check_duplicate(registered: &Descriptor, desc: &Descriptor) -> Result<()> {
+    let conflict = if !is_equal(registered, desc) {
+        Some("kind or number type")
+    } else if registered.unit() != desc.unit() {
+        Some("unit")
+    } else if registered.description() != desc.description() {
+        Some("description")
+    } else {
+        None
+    };
+
+    match conflict {
+        Some(conflict) => Err(MetricsError::DuplicateInstrument(format!(
+            "{} was registered by {:?} and {:?} with another {}",
+            desc.name(),
+            registered.instrumentation_name(),
+            desc.instrumentation_name(),
+            conflict
+        ))),
+        None => Ok(()),
+    }
+}
+
 #[derive(Debug, PartialEq, Eq, Hash)]
 struct UniqueInstrumentKey {
     instrument_name: String,
@@ -163,3 +303,109 @@ impl From<&Descriptor> for UniqueInstrumentKey {
 
 type UniqueSyncInstrument = Arc<dyn SyncInstrumentCore + Send + Sync>;
 type UniqueAsyncInstrument = Arc<dyn AsyncInstrumentCore + Send + Sync>;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sdk::{
+        export::metrics::ExportKind,
+        metrics::{controllers, selectors::simple::Selector},
+    };
+    use crate::Unit;
+
+    fn provider(strict: bool) -> RegistryMeterProvider {
+        controllers::pull(Box::new(Selector::Exact), Box::new(ExportKind::Cumulative))
+            .with_strict_validation(strict)
+            .build()
+            .provider()
+    }
+
+    #[test]
+    fn strict_validation() {
+        let meter = provider(true).meter("test");
+
+        for name in &["", "1requests", "requests/sec", &"r".repeat(64)] {
+            let result = meter.u64_counter(*name).try_init();
+            assert!(
+                matches!(result, Err(MetricsError::InvalidInstrument(_))),
+                "{:?}",
+                name
+            );
+        }
+        let result = meter
+            .u64_counter("latency")
+            .with_unit(Unit::new("µs"))
+            .try_init();
+        assert!(matches!(result, Err(MetricsError::InvalidInstrument(_))));
+        assert!(meter
+            .f64_value_recorder("http.server.duration-ms_2")
+            .with_unit(Unit::new("ms"))
+            .try_init()
+            .is_ok());
+    }
+
+    #[test]
+    fn duplicate_instruments() {
+        let provider = provider(true);
+        let (a, b) = (provider.meter("a"), provider.meter("b"));
+
+        let counter = |meter: &Meter, unit: &str| {
+            meter
+                .u64_counter("requests")
+                .with_unit(Unit::new(unit))
+                .try_init()
+                .map(drop)
+        };
+        assert_eq!(counter(&a, "1"), Ok(()));
+        assert_eq!(counter(&a, "1"), Ok(()));
+        assert_eq!(counter(&b, "1"), Ok(()));
+        assert!(matches!(
+            counter(&b, "{request}"),
+            Err(MetricsError::DuplicateInstrument(_))
+        ));
+        assert!(matches!(
+            counter(&a, "{request}"),
+            Err(MetricsError::DuplicateInstrument(_))
+        ));
+        assert!(matches!(
+            b.u64_counter("requests")
+                .with_unit(Unit::new("1"))
+                .with_description("Requests")
+                .try_init(),
+            Err(MetricsError::DuplicateInstrument(_))
+        ));
+        // Kinds are checked by meter first.
+        let up_down_counter = |meter: &Meter| {
+            meter
+                .i64_up_down_counter("requests")
+                .with_unit(Unit::new("1"))
+                .try_init()
+                .map(drop)
+        };
+        assert!(matches!(
+            up_down_counter(&b),
+            Err(MetricsError::MetricKindMismatch(_))
+        ));
+        assert!(matches!(
+            up_down_counter(&provider.meter("c")),
+            Err(MetricsError::DuplicateInstrument(_))
+        ));
+    }
+
+    #[test]
+    fn lenient_validation() {
+        let meter = provider(false).meter("test");
+
+        assert!(meter.u64_counter("requests/sec").try_init().is_ok());
+        assert!(meter
+            .u64_counter("requests")
+            .with_unit(Unit::new("1"))
+            .try_init()
+            .is_ok());
+        assert!(meter
+            .u64_counter("requests")
+            .with_unit(Unit::new("{request}"))
+            .try_init()
+            .is_ok());
+    }
+}
@@ -27,6 +27,14 @@ pub trait MeterCore: fmt::Debug {
         descriptor: Descriptor,
         runner: AsyncRunner,
     ) -> Result<Arc<dyn AsyncInstrumentCore + Send + Sync>>;
+
+    /// Register the callback of a batch observer, which observes the
+    /// asynchronous instruments created for the batch on each collection.
+    ///
+    /// Implementations without batch observers ignore the callback.
+    fn new_batch_observer(&self, _runner: AsyncRunner) -> Result<()> {
+        Ok(())
+    }
 }
 
 /// A common interface for synchronous and asynchronous instruments.
@@ -2,7 +2,7 @@ use crate::metrics::{
     sync_instrument::{SyncBoundInstrument, SyncInstrument},
     Descriptor, InstrumentKind, Measurement, Meter, Number, NumberKind, Result,
 };
-use crate::KeyValue;
+use crate::{KeyValue, Unit};
 use std::marker;
 
 /// ValueRecorder is a metric that records per-request non-additive values.
@@ -83,6 +83,12 @@ impl<'a, T> ValueRecorderBuilder<'a, T> {
         self
     }
 
+    /// Set the unit for this `ValueRecorder`
+    pub fn with_unit(mut self, unit: Unit) -> Self {
+        self.descriptor.set_unit(unit);
+        self
+    }
+
     /// Creates a new value recorder.
     pub fn try_init(self) -> Result<ValueRecorder<T>> {
         let instrument = self.meter.new_sync_instrument(self.descriptor)?;
@@ -2,13 +2,13 @@
 use crate::global;
 use crate::sdk::{
     export::metrics::{
-        CheckpointSet, Count, ExportKind, ExportKindSelector, Exporter, LastValue, Max, Min,
-        Quantile, Sum,
+        CheckpointSet, Count, Distribution, ExportKind, ExportKindSelector, Exporter, Histogram,
+        LastValue, Max, Min, Sum,
     },
     metrics::{
         aggregators::{
-            ArrayAggregator, HistogramAggregator, LastValueAggregator, MinMaxSumCountAggregator,
-            SumAggregator,
+            ArrayAggregator, DDSKetchAggregator, ExponentialHistogramAggregator,
+            HistogramAggregator, LastValueAggregator, MinMaxSumCountAggregator, SumAggregator,
         },
         controllers::{self, PushController, PushControllerWorker},
         selectors::simple,
@@ -17,7 +17,7 @@ use crate::sdk::{
 use crate::{
     labels::{default_encoder, Encoder, LabelSet},
     metrics,
-    metrics::{Descriptor, MetricsError, Result},
+    metrics::{Descriptor, MetricsError, Number, NumberKind, Result},
     KeyValue,
 };
 use futures::Stream;
@@ -46,6 +46,9 @@ pub struct StdoutExporter<W> {
     writer: Mutex<W>,
     /// Will pretty print the output sent to the writer. Default is false.
     pretty_print: bool,
+    /// Writes each record as a JSON object on its own line. Default is false.
+    #[cfg(feature = "serialize")]
+    json_lines: bool,
     /// Suppresses timestamp printing. This is useful to create deterministic test
     /// conditions.
     do_not_print_time: bool,
@@ -87,16 +90,19 @@ struct ExportLine {
     #[cfg_attr(feature = "serialize", serde(skip_serializing_if = "Option::is_none"))]
     quantiles: Option<Vec<ExporterQuantile>>,
 
+    #[cfg_attr(feature = "serialize", serde(skip_serializing_if = "Option::is_none"))]
+    buckets: Option<ExportBuckets>,
+
     #[cfg_attr(feature = "serialize", serde(skip_serializing_if = "Option::is_none"))]
     timestamp: Option<SystemTime>,
 }
 
-/// A number exported as debug for serialization
-pub struct ExportNumeric(Box<dyn fmt::Debug>);
+/// A number exported with its kind, serialized as a number of that kind.
+pub struct ExportNumeric(Number, NumberKind);
 
 impl fmt::Debug for ExportNumeric {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        self.0.fmt(f)
+        self.0.to_debug(&self.1).fmt(f)
     }
 }
 
@@ -106,8 +112,11 @@ impl Serialize for ExportNumeric {
     where
         S: Serializer,
     {
-        let s = format!("{:?}", self);
-        serializer.serialize_str(&s)
+        match self.1 {
+            NumberKind::I64 => serializer.serialize_i64(self.0.to_i64(&self.1)),
+            NumberKind::F64 => serializer.serialize_f64(self.0.to_f64(&self.1)),
+            NumberKind::U64 => serializer.serialize_u64(self.0.to_u64(&self.1)),
+        }
     }
 }
 
@@ -118,6 +127,106 @@ struct ExporterQuantile {
     v: ExportNumeric,
 }
 
+/// The buckets of a histogram, where `counts[i]` counts the values below
+/// `boundaries[i]` and the last count the values above every boundary.
+#[cfg_attr(feature = "serialize", derive(Serialize))]
+#[derive(Debug)]
+struct ExportBuckets {
+    boundaries: Vec<f64>,
+    counts: Vec<u64>,
+}
+
+/// A line of a batch, with the timestamp of the batch, written in the JSON
+/// lines mode.
+#[cfg(feature = "serialize")]
+#[derive(Serialize)]
+struct JsonLine<'a> {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    export_timestamp: Option<SystemTime>,
+    #[serde(flatten)]
+    line: &'a ExportLine,
+}
+
+impl<W> StdoutExporter<W> {
+    fn format(&self, batch: ExportBatch) -> Result<String> {
+        if let Some(formatter) = &self.formatter {
+            return formatter.0(batch);
+        }
+
+        #[cfg(feature = "serialize")]
+        {
+            let json = |result: serde_json::Result<String>| {
+                result.map_err(|err| MetricsError::Other(err.to_string()))
+            };
+            if self.json_lines {
+                batch.lines.iter().try_fold(String::new(), |mut out, line| {
+                    out.push_str(&json(serde_json::to_string(&JsonLine {
+                        export_timestamp: batch.timestamp,
+                        line,
+                    }))?);
+                    out.push('\n');
+                    Ok(out)
+                })
+            } else if self.pretty_print {
+                json(serde_json::to_string_pretty(&batch)).map(|out| out + "\n")
+            } else {
+                json(serde_json::to_string(&batch)).map(|out| out + "\n")
+            }
+        }
+
+        #[cfg(not(feature = "serialize"))]
+        {
+            if self.pretty_print {
+                Ok(format!("{:#?}\n", batch))
+            } else {
+                Ok(format!("{:?}\n", batch))
+            }
+        }
+    }
+
+    fn expose_distribution(
+        &self,
+        expose: &mut ExportLine,
+        distribution: &dyn Distribution,
+        kind: &NumberKind,
+    ) -> Result<()> {
+        expose.min = Some(ExportNumeric(distribution.min()?, kind.clone()));
+        expose.max = Some(ExportNumeric(distribution.max()?, kind.clone()));
+        expose.sum = Some(ExportNumeric(distribution.sum()?, kind.clone()));
+        expose.count = distribution.count()?;
+
+        let quantiles = self
+            .quantiles
+            .iter()
+            .map(|&q| {
+                Ok(ExporterQuantile {
+                    q,
+                    v: ExportNumeric(distribution.quantile(q)?, kind.clone()),
+                })
+            })
+            .collect::<Result<Vec<_>>>()?;
+        expose.quantiles = Some(quantiles);
+        Ok(())
+    }
+
+    fn expose_histogram(
+        &self,
+        expose: &mut ExportLine,
+        histogram: &dyn Histogram,
+        kind: &NumberKind,
+    ) -> Result<()> {
+        expose.sum = Some(ExportNumeric(histogram.sum()?, kind.clone()));
+        expose.count = histogram.count()?;
+
+        let buckets = histogram.histogram()?;
+        expose.buckets = Some(ExportBuckets {
+            boundaries: buckets.boundaries().clone(),
+            counts: buckets.counts().iter().map(|&count| count as u64).collect(),
+        });
+        Ok(())
+    }
+}
+
 impl<W> Exporter for StdoutExporter<W>
 where
     W: fmt::Debug + io::Write,
@@ -145,27 +254,16 @@ where
             let mut expose = ExportLine::default();
 
             if let Some(array) = agg.as_any().downcast_ref::<ArrayAggregator>() {
-                expose.min = Some(ExportNumeric(array.min()?.to_debug(kind)));
-                expose.max = Some(ExportNumeric(array.max()?.to_debug(kind)));
-                expose.sum = Some(ExportNumeric(array.sum()?.to_debug(kind)));
-                expose.count = array.count()?;
-
-                let quantiles = self
-                    .quantiles
-                    .iter()
-                    .map(|&q| {
-                        Ok(ExporterQuantile {
-                            q,
-                            v: ExportNumeric(array.quantile(q)?.to_debug(kind)),
-                        })
-                    })
-                    .collect::<Result<Vec<_>>>()?;
-                expose.quantiles = Some(quantiles);
+                self.expose_distribution(&mut expose, array, kind)?;
+            }
+
+            if let Some(sketch) = agg.as_any().downcast_ref::<DDSKetchAggregator>() {
+                self.expose_distribution(&mut expose, sketch, kind)?;
             }
 
             if let Some(last_value) = agg.as_any().downcast_ref::<LastValueAggregator>() {
                 let (value, timestamp) = last_value.last_value()?;
-                expose.last_value = Some(ExportNumeric(value.to_debug(kind)));
+                expose.last_value = Some(ExportNumeric(value, kind.clone()));
 
                 if !self.do_not_print_time {
                     expose.timestamp = Some(timestamp);
@@ -173,27 +271,28 @@ where
             }
 
             if let Some(histogram) = agg.as_any().downcast_ref::<HistogramAggregator>() {
-                expose.sum = Some(ExportNumeric(histogram.sum()?.to_debug(kind)));
-                expose.count = histogram.count()?;
-                // TODO expose buckets
+                self.expose_histogram(&mut expose, histogram, kind)?;
+            }
+
+            if let Some(histogram) = agg
+                .as_any()
+                .downcast_ref::<ExponentialHistogramAggregator>()
+            {
+                self.expose_histogram(&mut expose, histogram, kind)?;
             }
 
             if let Some(mmsc) = agg.as_any().downcast_ref::<MinMaxSumCountAggregator>() {
-                expose.min = Some(ExportNumeric(mmsc.min()?.to_debug(kind)));
-                expose.max = Some(ExportNumeric(mmsc.max()?.to_debug(kind)));
-                expose.sum = Some(ExportNumeric(mmsc.sum()?.to_debug(kind)));
+                expose.min = Some(ExportNumeric(mmsc.min()?, kind.clone()));
+                expose.max = Some(ExportNumeric(mmsc.max()?, kind.clone()));
+                expose.sum = Some(ExportNumeric(mmsc.sum()?, kind.clone()));
                 expose.count = mmsc.count()?;
             }
 
             if let Some(sum) = agg.as_any().downcast_ref::<SumAggregator>() {
-                expose.sum = Some(ExportNumeric(sum.sum()?.to_debug(kind)));
+                expose.sum = Some(ExportNumeric(sum.sum()?, kind.clone()));
             }
 
-            let mut encoded_labels = String::new();
-            let iter = record.labels().iter();
-            if let (0, _) = iter.size_hint() {
-                encoded_labels = record.labels().encoded(Some(self.label_encoder.as_ref()));
-            }
+            let encoded_labels = record.labels().encoded(Some(self.label_encoder.as_ref()));
 
             let mut sb = String::new();
 
@@ -224,11 +323,8 @@ where
             Ok(())
         })?;
 
+        let formatted = self.format(batch)?;
         self.writer.lock().map_err(From::from).and_then(|mut w| {
-            let formatted = match &self.formatter {
-                Some(formatter) => formatter.0(batch)?,
-                None => format!("{:?}\n", batch),
-            };
             w.write_all(formatted.as_bytes())
                 .map_err(|e| MetricsError::Other(e.to_string()))
         })
@@ -259,6 +355,8 @@ pub struct StdoutExporterBuilder<W, S, I> {
     interval: I,
     writer: Mutex<W>,
     pretty_print: bool,
+    #[cfg(feature = "serialize")]
+    json_lines: bool,
     do_not_print_time: bool,
     quantiles: Option<Vec<f64>>,
     label_encoder: Option<Box<dyn Encoder + Send + Sync>>,
@@ -279,6 +377,8 @@ where
             interval,
             writer: Mutex::new(io::stdout()),
             pretty_print: false,
+            #[cfg(feature = "serialize")]
+            json_lines: false,
             do_not_print_time: false,
             quantiles: None,
             label_encoder: None,
@@ -293,6 +393,8 @@ where
             interval: self.interval,
             writer: Mutex::new(writer),
             pretty_print: self.pretty_print,
+            #[cfg(feature = "serialize")]
+            json_lines: self.json_lines,
             do_not_print_time: self.do_not_print_time,
             quantiles: self.quantiles,
             label_encoder: self.label_encoder,
@@ -309,6 +411,15 @@ where
         }
     }
 
+    /// Write each record as a JSON object on its own line, instead of the
+    /// whole batch at once. The timestamp of the batch is written to each
+    /// record as `export_timestamp`.
+    #[cfg(feature = "serialize")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "serialize")))]
+    pub fn with_json_lines(self, json_lines: bool) -> Self {
+        StdoutExporterBuilder { json_lines, ..self }
+    }
+
     /// Hide the timestamps from exported results
     pub fn with_do_not_print_time(self, do_not_print_time: bool) -> Self {
         StdoutExporterBuilder {
@@ -391,6 +502,8 @@ where
             StdoutExporter {
                 writer: self.writer,
                 pretty_print: self.pretty_print,
+                #[cfg(feature = "serialize")]
+                json_lines: self.json_lines,
                 do_not_print_time: self.do_not_print_time,
                 quantiles: self.quantiles.unwrap_or_else(|| vec![0.5, 0.9, 0.99]),
                 label_encoder: self.label_encoder.unwrap_or_else(default_encoder),
@@ -399,3 +512,64 @@ where
         ))
     }
 }
+
+#[cfg(all(test, feature = "serialize"))]
+mod tests {
+    use super::*;
+    use crate::metrics::MeterProvider;
+    use crate::sdk::metrics::selectors::simple::Selector;
+    use std::sync::Arc;
+
+    #[derive(Clone, Debug, Default)]
+    struct SharedWriter(Arc<Mutex<Vec<u8>>>);
+
+    impl io::Write for SharedWriter {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.lock().unwrap().write(buf)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn json_lines() {
+        let writer = SharedWriter::default();
+        let (_, _, exporter) = stdout(|_| (), |_| futures::stream::empty::<()>())
+            .with_writer(writer.clone())
+            .with_do_not_print_time(true)
+            .with_json_lines(true)
+            .try_build()
+            .unwrap();
+
+        let mut controller = controllers::pull(
+            Box::new(Selector::Histogram(vec![1.0, 10.0])),
+            Box::new(ExportKind::PassThrough),
+        )
+        .with_cache_period(Duration::from_secs(0))
+        .build();
+        let meter = controller.provider().meter("");
+        meter
+            .i64_up_down_counter("connections")
+            .init()
+            .add(-2, &[KeyValue::new("host", "a")]);
+        let latency = meter.f64_value_recorder("latency").init();
+        for value in &[0.5, 2.0, 2.5, 20.0] {
+            latency.record(*value, &[]);
+        }
+        controller.collect().unwrap();
+        exporter.export(&mut controller).unwrap();
+
+        let output = String::from_utf8(writer.0.lock().unwrap().clone()).unwrap();
+        let mut lines = output.lines().collect::<Vec<_>>();
+        lines.sort_unstable();
+        assert_eq!(
+            lines,
+            vec![
+                r#"{"name":"connections{host=a}","sum":-2,"count":0}"#,
+                r#"{"name":"latency","sum":25.0,"count":4,"buckets":{"boundaries":[1.0,10.0],"counts":[1,2,1]}}"#,
+            ]
+        );
+    }
+}
@@ -127,7 +127,8 @@ pub mod exporter;
 pub mod global;
 pub mod sdk;
 
-#[cfg(test)]
+#[cfg(any(test, feature = "testing"))]
+#[cfg_attr(docsrs, doc(cfg(feature = "testing")))]
 pub mod testing;
 
 #[cfg(feature = "metrics")]
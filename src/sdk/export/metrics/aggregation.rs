@@ -1,5 +1,8 @@
 //! Metrics SDK Aggregator export API
 use crate::metrics::{Number, Result};
+use crate::KeyValue;
+#[cfg(feature = "serialize")]
+use serde::{Deserialize, Serialize};
 use std::time::SystemTime;
 
 /// Sum returns an aggregated sum.
@@ -84,6 +87,116 @@ pub trait Histogram: Sum + Count {
     fn histogram(&self) -> Result<Buckets>;
 }
 
+/// ExponentialBuckets represent a contiguous range of buckets of an
+/// exponential histogram.
+///
+/// The bucket with index `i` counts the values in `(base^i, base^(i+1)]`, where
+/// `base = 2^(2^-scale)`. The first count is the count of the bucket with index
+/// `offset`.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ExponentialBuckets {
+    offset: i32,
+    counts: Vec<u64>,
+}
+
+impl ExponentialBuckets {
+    /// Create new exponential buckets
+    pub fn new(offset: i32, counts: Vec<u64>) -> Self {
+        ExponentialBuckets { offset, counts }
+    }
+
+    /// The index of the first bucket
+    pub fn offset(&self) -> i32 {
+        self.offset
+    }
+
+    /// Counts of the buckets, starting at the bucket with index `offset`
+    pub fn counts(&self) -> &Vec<u64> {
+        &self.counts
+    }
+}
+
+/// ExponentialHistogram returns the count of events in buckets whose
+/// boundaries grow exponentially, with a resolution chosen by the aggregator.
+pub trait ExponentialHistogram: Histogram {
+    /// The scale of the buckets, with `2^(2^-scale)` as the ratio of the
+    /// boundaries of consecutive buckets.
+    fn scale(&self) -> Result<i8>;
+
+    /// The count of events with a value of zero.
+    fn zero_count(&self) -> Result<u64>;
+
+    /// The buckets of events with a positive value.
+    fn positive(&self) -> Result<ExponentialBuckets>;
+
+    /// The buckets of events with a negative value, indexed by the absolute
+    /// value.
+    fn negative(&self) -> Result<ExponentialBuckets>;
+}
+
+/// An exemplar is a measurement recorded within a sampled trace, retained by
+/// an aggregator to link the aggregated metric to an example trace.
+#[cfg_attr(feature = "serialize", derive(Deserialize, Serialize))]
+#[derive(Clone, Debug)]
+pub struct Exemplar {
+    value: Number,
+    timestamp: SystemTime,
+    trace_id: u128,
+    span_id: u64,
+    filtered_labels: Vec<KeyValue>,
+}
+
+impl Exemplar {
+    /// Create a new exemplar
+    pub fn new(
+        value: Number,
+        timestamp: SystemTime,
+        trace_id: u128,
+        span_id: u64,
+        filtered_labels: Vec<KeyValue>,
+    ) -> Self {
+        Exemplar {
+            value,
+            timestamp,
+            trace_id,
+            span_id,
+            filtered_labels,
+        }
+    }
+
+    /// The measured value
+    pub fn value(&self) -> &Number {
+        &self.value
+    }
+
+    /// The time the measurement was recorded
+    pub fn timestamp(&self) -> SystemTime {
+        self.timestamp
+    }
+
+    /// The id of the trace the measurement was recorded in
+    pub fn trace_id(&self) -> u128 {
+        self.trace_id
+    }
+
+    /// The id of the span the measurement was recorded in
+    pub fn span_id(&self) -> u64 {
+        self.span_id
+    }
+
+    /// The labels of the measurement that are not part of the aggregated
+    /// label set.
+    pub fn filtered_labels(&self) -> &[KeyValue] {
+        &self.filtered_labels
+    }
+}
+
+/// Exemplars returns the exemplars sampled from the aggregated measurements.
+pub trait Exemplars {
+    /// The exemplars of the currently aggregated metrics
+    fn exemplars(&self) -> Result<Vec<Exemplar>>;
+}
+
 /// MinMaxSumCount supports the Min, Max, Sum, and Count interfaces.
 pub trait MinMaxSumCount: Min + Max + Sum + Count {}
 
@@ -12,8 +12,8 @@ use std::time::SystemTime;
 mod aggregation;
 
 pub use aggregation::{
-    Buckets, Count, Distribution, Histogram, LastValue, Max, Min, MinMaxSumCount, Points, Quantile,
-    Sum,
+    Buckets, Count, Distribution, Exemplar, Exemplars, ExponentialBuckets, ExponentialHistogram,
+    Histogram, LastValue, Max, Min, MinMaxSumCount, Points, Quantile, Sum,
 };
 
 /// Processor is responsible for deciding which kind of aggregation to use (via
@@ -118,6 +118,20 @@ pub trait Aggregator: fmt::Debug {
     /// `SpanContext`.
     fn update(&self, number: &Number, descriptor: &Descriptor) -> Result<()>;
 
+    /// Update receives a new measured value along with an `Exemplar` sampled
+    /// from the context it was recorded in.
+    ///
+    /// Aggregators that do not retain exemplars ignore it and only `update`
+    /// the aggregation, which is the default behavior.
+    fn update_with_exemplar(
+        &self,
+        number: &Number,
+        descriptor: &Descriptor,
+        _exemplar: Exemplar,
+    ) -> Result<()> {
+        self.update(number, descriptor)
+    }
+
     /// This method is called during collection to finish one period of aggregation
     /// by atomically saving the currently-updating state into the argument
     /// Aggregator.
@@ -145,6 +159,12 @@ pub trait Aggregator: fmt::Debug {
     /// of both `Aggregator` states.
     fn merge(&self, other: &(dyn Aggregator + Send + Sync), descriptor: &Descriptor) -> Result<()>;
 
+    /// Returns the implementing aggregator as a `Subtractor` if it supports
+    /// subtraction, which is required to export precomputed sums as deltas.
+    fn as_subtractor(&self) -> Option<&dyn Subtractor> {
+        None
+    }
+
     /// Returns the implementing aggregator as `Any` for downcasting.
     fn as_any(&self) -> &dyn Any;
 }
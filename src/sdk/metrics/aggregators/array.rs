@@ -3,6 +3,8 @@ use crate::sdk::{
     export::metrics::{Count, Distribution, Max, Min, MinMaxSumCount, Points, Quantile, Sum},
     metrics::Aggregator,
 };
+#[cfg(feature = "serialize")]
+use serde::{Deserialize, Serialize};
 use std::any::Any;
 use std::mem;
 use std::sync::{Arc, Mutex};
@@ -13,6 +15,7 @@ pub fn array() -> ArrayAggregator {
 }
 
 /// An aggregator which stores metrics in an array.
+#[cfg_attr(feature = "serialize", derive(Deserialize, Serialize))]
 #[derive(Debug, Default)]
 pub struct ArrayAggregator {
     inner: Mutex<Inner>,
@@ -160,12 +163,14 @@ impl Aggregator for ArrayAggregator {
     }
 }
 
+#[cfg_attr(feature = "serialize", derive(Deserialize, Serialize))]
 #[derive(Debug, Default)]
 struct Inner {
     sum: AtomicNumber,
     points: Option<PointsData>,
 }
 
+#[cfg_attr(feature = "serialize", derive(Deserialize, Serialize))]
 #[derive(Clone, Debug, Default)]
 struct PointsData(Vec<Number>);
 
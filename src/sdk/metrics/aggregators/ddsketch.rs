@@ -9,6 +9,8 @@
 //!
 //! The detail of this algorithm can be found in https://arxiv.org/pdf/1908.10693
 
+#[cfg(feature = "serialize")]
+use serde::{Deserialize, Serialize};
 use std::{
     any::Any,
     cmp::Ordering,
@@ -48,6 +50,7 @@ pub fn ddsketch(config: &DDSketchConfig, kind: NumberKind) -> DDSKetchAggregator
 /// guarantee, the value returned should within the range of 0.98 to 1.02 millisecond.
 ///
 /// In order to support both negative and positive inputs, DDSketchAggregator has two DDSketch store within itself to store the negative and positive inputs.
+#[cfg_attr(feature = "serialize", derive(Deserialize, Serialize))]
 #[derive(Debug)]
 pub struct DDSKetchAggregator {
     inner: RwLock<Inner>,
@@ -185,7 +188,7 @@ impl Aggregator for DDSKetchAggregator {
     fn synchronized_move(
         &self,
         destination: &Arc<(dyn Aggregator + Send + Sync)>,
-        descriptor: &Descriptor,
+        _descriptor: &Descriptor,
     ) -> Result<()> {
         if let Some(other) = destination.as_any().downcast_ref::<Self>() {
             other
@@ -194,16 +197,8 @@ impl Aggregator for DDSKetchAggregator {
                 .map_err(From::from)
                 .and_then(|mut other| {
                     self.inner.write().map_err(From::from).map(|mut inner| {
-                        let kind = descriptor.number_kind();
-                        other.max_value = mem::replace(&mut inner.max_value, kind.zero());
-                        other.min_value = mem::replace(&mut inner.min_value, kind.zero());
-                        other.key_epsilon = mem::take(&mut inner.key_epsilon);
-                        other.offset = mem::take(&mut inner.offset);
-                        other.gamma = mem::take(&mut inner.gamma);
-                        other.gamma_ln = mem::take(&mut inner.gamma_ln);
-                        other.positive_store = mem::take(&mut inner.positive_store);
-                        other.negative_store = mem::take(&mut inner.negative_store);
-                        other.sum = mem::replace(&mut inner.sum, kind.zero());
+                        let empty = inner.empty();
+                        *other = mem::replace(&mut *inner, empty);
                     })
                 })
         } else {
@@ -293,7 +288,7 @@ impl Aggregator for DDSKetchAggregator {
 }
 
 /// DDSKetch Configuration.
-#[derive(Debug)]
+#[derive(Clone, Debug)]
 pub struct DDSketchConfig {
     alpha: f64,
     max_num_bins: i64,
@@ -321,6 +316,7 @@ impl DDSketchConfig {
 /// According to the paper, the DDSKetch only support positive number. Inner support
 /// either positive or negative number. But cannot yield actual result when input has
 /// both positive and negative number.
+#[cfg_attr(feature = "serialize", derive(Deserialize, Serialize))]
 #[derive(Debug)]
 struct Inner {
     positive_store: Store,
@@ -365,6 +361,23 @@ impl Inner {
         inner
     }
 
+    /// Create an empty sketch sharing the parameters of this one, so that the
+    /// two can be merged.
+    fn empty(&self) -> Inner {
+        Inner {
+            positive_store: Store::new(self.positive_store.max_num_bins),
+            negative_store: Store::new(self.negative_store.max_num_bins),
+            min_value: self.kind.max(),
+            max_value: self.kind.min(),
+            sum: self.kind.zero(),
+            gamma: self.gamma,
+            gamma_ln: self.gamma_ln,
+            key_epsilon: self.key_epsilon,
+            offset: self.offset,
+            kind: self.kind.clone(),
+        }
+    }
+
     fn add(&mut self, v: &Number, kind: &NumberKind) {
         let key = self.key(v, kind);
         match v.partial_cmp(kind, &Number::from(0.0)) {
@@ -427,6 +440,7 @@ impl Inner {
     }
 }
 
+#[cfg_attr(feature = "serialize", derive(Deserialize, Serialize))]
 #[derive(Debug)]
 struct Store {
     bins: Vec<u64>,
@@ -596,49 +610,33 @@ impl Store {
 
     /// Merge two stores
     fn merge(&mut self, other: &Store) {
-        if self.count == 0 {
+        if other.count == 0 {
             return;
         }
-        if other.count == 0 {
+        if self.count == 0 {
             self.bins = other.bins.clone();
             self.min_key = other.min_key;
             self.max_key = other.max_key;
             self.count = other.count;
+            return;
         }
 
-        if self.max_key > other.max_key {
-            if other.min_key < self.min_key {
-                self.grow_left(other.min_key);
-            }
-            let start = if other.min_key > self.min_key {
-                other.min_key
-            } else {
-                self.min_key
-            } as usize;
-            for i in start..other.max_key as usize {
-                self.bins[i - self.min_key as usize] = other.bins[i - other.min_key as usize];
-            }
-            let mut n = 0;
-            for i in other.min_key as usize..self.min_key as usize {
-                n += other.bins[i - other.min_key as usize]
-            }
-            self.bins[0] += n;
-        } else if other.min_key < self.min_key {
-            let mut tmp_bins = vec![0u64; other.bins.len()];
-            tmp_bins.as_mut_slice().copy_from_slice(&other.bins);
-
-            for i in self.min_key as usize..self.max_key as usize {
-                tmp_bins[i - other.min_key as usize] += self.bins[i - self.min_key as usize];
-            }
-
-            self.bins = tmp_bins;
-            self.max_key = other.max_key;
-            self.min_key = other.min_key;
-        } else {
+        if other.min_key < self.min_key {
+            self.grow_left(other.min_key);
+        }
+        if other.max_key > self.max_key {
             self.grow_right(other.max_key);
-            for i in other.min_key as usize..(other.max_key + 1) as usize {
-                self.bins[i - self.min_key as usize] += other.bins[i - other.min_key as usize];
-            }
+        }
+
+        for (idx, count) in other.bins.iter().enumerate() {
+            let key = other.min_key + idx as i64;
+            // keys below the range of this store are collapsed into the first bin
+            let self_idx = if key < self.min_key {
+                0
+            } else {
+                key - self.min_key
+            };
+            self.bins[self_idx as usize] += count;
         }
 
         self.count += other.count;
@@ -927,6 +925,57 @@ mod tests {
         assert_eq!(store1.count, 1000);
     }
 
+    fn store_with_keys(max_num_bins: i64, keys: std::ops::Range<i64>) -> Store {
+        let mut store = Store::new(max_num_bins);
+        for key in keys {
+            store.add(key);
+        }
+        store
+    }
+
+    /// Merging into or from an empty store keeps all the counts of the other
+    #[test]
+    fn test_merge_empty_stores() {
+        let mut store = Store::new(TEST_MAX_BINS);
+        store.merge(&store_with_keys(TEST_MAX_BINS, 0..10));
+        assert_eq!(store.count, 10);
+        assert_eq!(store.key_at_rank(10), 9);
+
+        store.merge(&Store::new(TEST_MAX_BINS));
+        assert_eq!(store.count, 10);
+        assert_eq!(store.bins.iter().sum::<u64>(), 10);
+    }
+
+    /// Stores with disjoint ranges grow to the right or to the left to hold
+    /// both ranges
+    #[test]
+    fn test_merge_disjoint_stores() {
+        for &grow_left in &[true, false] {
+            let low = store_with_keys(TEST_MAX_BINS, 0..10);
+            let high = store_with_keys(TEST_MAX_BINS, 500..510);
+            let (mut store, other) = if grow_left { (high, low) } else { (low, high) };
+
+            store.merge(&other);
+            assert_eq!(store.count, 20);
+            assert_eq!(store.bins.iter().sum::<u64>(), 20);
+            assert_eq!(store.key_at_rank(1), 0);
+            assert_eq!(store.key_at_rank(10), 9);
+            assert_eq!(store.key_at_rank(11), 500);
+            assert_eq!(store.key_at_rank(20), 509);
+        }
+    }
+
+    /// Overlapping stores add the counts of their common keys
+    #[test]
+    fn test_merge_overlapping_stores() {
+        let mut store = store_with_keys(TEST_MAX_BINS, 0..10);
+        store.merge(&store_with_keys(TEST_MAX_BINS, 5..15));
+        assert_eq!(store.count, 20);
+        assert_eq!(store.key_at_rank(5), 4);
+        assert_eq!(store.key_at_rank(7), 5);
+        assert_eq!(store.key_at_rank(20), 14);
+    }
+
     // Test ddsketch with different distribution
 
     #[test]
@@ -1034,4 +1083,37 @@ mod tests {
             );
         }
     }
+
+    #[test]
+    fn test_update_after_synchronized_move() {
+        let config = DDSketchConfig::new(TEST_ALPHA, TEST_MAX_BINS, TEST_KEY_EPSILON);
+        let descriptor = Descriptor::new(
+            "test".to_string(),
+            "test".to_string(),
+            InstrumentKind::ValueRecorder,
+            NumberKind::F64,
+        );
+        let ddsketch = DDSKetchAggregator::new(&config, NumberKind::F64);
+        let moved: Arc<dyn Aggregator + Send + Sync> =
+            Arc::new(DDSKetchAggregator::new(&config, NumberKind::F64));
+
+        ddsketch.update(&Number::from(1.0), &descriptor).unwrap();
+        ddsketch.synchronized_move(&moved, &descriptor).unwrap();
+
+        // the moved-from sketch must remain usable and mergeable
+        ddsketch.update(&Number::from(2.0), &descriptor).unwrap();
+        assert_eq!(ddsketch.count(), Ok(1));
+        assert!((ddsketch.min().unwrap().to_f64(&NumberKind::F64) - 2.0).abs() < std::f64::EPSILON);
+        moved.merge(&ddsketch, &descriptor).unwrap();
+
+        let moved = moved
+            .as_any()
+            .downcast_ref::<DDSKetchAggregator>()
+            .expect("Fail to cast dyn Aggregator down to DDSketchAggregator");
+        assert_eq!(moved.count(), Ok(2));
+        assert!((moved.sum().unwrap().to_f64(&NumberKind::F64) - 3.0).abs() < std::f64::EPSILON);
+        assert!(
+            (moved.quantile(1.0).unwrap().to_f64(&NumberKind::F64) - 2.0).abs() < std::f64::EPSILON
+        );
+    }
 }
@@ -0,0 +1,118 @@
+use crate::metrics::Result;
+#[cfg(feature = "serialize")]
+use serde::{Deserialize, Serialize};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::thread;
+
+const HOT_BIT: u64 = 1 << 63;
+
+/// Two copies of the state of an aggregator, so that updates never wait for a
+/// collection.
+///
+/// Updates are recorded in the hot state without locking. `swap` makes the cold
+/// state hot, then waits for the updates that were still recording into the
+/// previous hot state before handing it out to be drained.
+#[cfg_attr(feature = "serialize", derive(Deserialize, Serialize))]
+#[derive(Debug)]
+pub(super) struct DoubleBuffer<S> {
+    /// The index of the hot state in the high bit, and the number of updates
+    /// started since the last swap in the remaining bits.
+    hot_and_started: AtomicU64,
+    /// The number of updates completed in each state since it was last drained.
+    completed: [AtomicU64; 2],
+    states: [S; 2],
+    /// Serializes swaps.
+    swap: Mutex<()>,
+}
+
+impl<S> DoubleBuffer<S> {
+    /// Create a buffer from two empty states.
+    pub(super) fn new(hot: S, cold: S) -> Self {
+        DoubleBuffer {
+            hot_and_started: AtomicU64::new(0),
+            completed: [AtomicU64::new(0), AtomicU64::new(0)],
+            states: [hot, cold],
+            swap: Mutex::new(()),
+        }
+    }
+
+    /// Record an update in the hot state.
+    pub(super) fn update<T, F: FnOnce(&S) -> T>(&self, f: F) -> T {
+        let idx = hot_index(self.hot_and_started.fetch_add(1, Ordering::AcqRel));
+        let result = f(&self.states[idx]);
+        self.completed[idx].fetch_add(1, Ordering::Release);
+        result
+    }
+
+    /// The hot state.
+    ///
+    /// Reads of an aggregator that is concurrently updated may observe an
+    /// update partially applied.
+    pub(super) fn hot(&self) -> &S {
+        &self.states[hot_index(self.hot_and_started.load(Ordering::Acquire))]
+    }
+
+    /// Make the cold state hot, and drain the previous hot state with `f` once
+    /// all of its updates have completed.
+    ///
+    /// `f` must leave the state empty, as it receives the next updates after
+    /// the following swap.
+    pub(super) fn swap<T, F: FnOnce(&S) -> T>(&self, f: F) -> Result<T> {
+        let _guard = self.swap.lock()?;
+        let hot_bit = self.hot_and_started.load(Ordering::Acquire) & HOT_BIT;
+        let started = self
+            .hot_and_started
+            .swap(hot_bit ^ HOT_BIT, Ordering::AcqRel)
+            & !HOT_BIT;
+
+        let idx = hot_index(hot_bit);
+        while self.completed[idx].load(Ordering::Acquire) != started {
+            thread::yield_now();
+        }
+        self.completed[idx].store(0, Ordering::Release);
+
+        Ok(f(&self.states[idx]))
+    }
+}
+
+fn hot_index(hot_and_started: u64) -> usize {
+    (hot_and_started >> 63) as usize
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+
+    #[test]
+    fn swaps_do_not_lose_updates() {
+        let buffer = Arc::new(DoubleBuffer::new(AtomicU64::new(0), AtomicU64::new(0)));
+        let workers = (0..4)
+            .map(|_| {
+                let buffer = buffer.clone();
+                thread::spawn(move || {
+                    for _ in 0..10_000 {
+                        buffer.update(|count| count.fetch_add(1, Ordering::Relaxed));
+                    }
+                })
+            })
+            .collect::<Vec<_>>();
+
+        let mut drained = 0;
+        while Arc::strong_count(&buffer) > 1 {
+            drained += buffer
+                .swap(|count| count.swap(0, Ordering::Relaxed))
+                .unwrap();
+        }
+        for worker in workers {
+            worker.join().unwrap();
+        }
+        drained += buffer
+            .swap(|count| count.swap(0, Ordering::Relaxed))
+            .unwrap();
+
+        assert_eq!(drained, 40_000);
+        assert_eq!(buffer.hot().load(Ordering::Relaxed), 0);
+    }
+}
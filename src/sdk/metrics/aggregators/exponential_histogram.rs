@@ -0,0 +1,614 @@
+//! Exponential histogram aggregator.
+//!
+//! The aggregator counts events in buckets whose boundaries are powers of
+//! `base = 2^(2^-scale)`, with the bucket of index `i` covering
+//! `(base^i, base^(i+1)]`. It starts at the highest resolution and halves the
+//! resolution, by decrementing the scale, whenever the recorded values no
+//! longer fit in the configured number of buckets.
+use crate::metrics::{AtomicNumber, Descriptor, MetricsError, Number, NumberKind, Result};
+use crate::sdk::export::metrics::{
+    Aggregator, Buckets, Count, ExponentialBuckets, ExponentialHistogram, Histogram, Subtractor,
+    Sum,
+};
+#[cfg(feature = "serialize")]
+use serde::{Deserialize, Serialize};
+use std::mem;
+use std::sync::{Arc, RwLock};
+
+/// The default maximum number of buckets of both the positive and the
+/// negative range.
+pub const DEFAULT_MAX_SIZE: usize = 160;
+
+/// The highest resolution, with a relative error of about 0.00007%.
+const MAX_SCALE: i8 = 20;
+
+/// The lowest resolution, where all finite `f64` values fit in two buckets.
+const MIN_SCALE: i8 = -10;
+
+/// Create a new exponential histogram for the given descriptor with at most
+/// `max_size` positive and `max_size` negative buckets.
+pub fn exponential_histogram(desc: &Descriptor, max_size: usize) -> ExponentialHistogramAggregator {
+    ExponentialHistogramAggregator {
+        inner: RwLock::new(Inner {
+            max_size: max_size.max(2),
+            kind: desc.number_kind().clone(),
+            state: State::empty(),
+        }),
+    }
+}
+
+/// This aggregator counts events in exponentially growing buckets, choosing
+/// the bucket boundaries automatically. It also calculates the sum and count
+/// of all events. NaN and infinite values are dropped.
+#[cfg_attr(feature = "serialize", derive(Deserialize, Serialize))]
+#[derive(Debug)]
+pub struct ExponentialHistogramAggregator {
+    inner: RwLock<Inner>,
+}
+
+#[cfg_attr(feature = "serialize", derive(Deserialize, Serialize))]
+#[derive(Debug)]
+struct Inner {
+    max_size: usize,
+    kind: NumberKind,
+    state: State,
+}
+
+#[cfg_attr(feature = "serialize", derive(Deserialize, Serialize))]
+#[derive(Debug)]
+struct State {
+    scale: i8,
+    count: u64,
+    sum: AtomicNumber,
+    zero_count: u64,
+    positive: Store,
+    negative: Store,
+}
+
+impl State {
+    fn empty() -> Self {
+        State {
+            scale: MAX_SCALE,
+            count: 0,
+            sum: NumberKind::U64.zero().to_atomic(),
+            zero_count: 0,
+            positive: Store::default(),
+            negative: Store::default(),
+        }
+    }
+
+    /// Lower the resolution by `change` scales.
+    fn downscale(&mut self, change: i8) {
+        if change > 0 {
+            self.scale -= change;
+            self.positive.downscale(change);
+            self.negative.downscale(change);
+        }
+    }
+}
+
+/// A contiguous range of bucket counts.
+#[cfg_attr(feature = "serialize", derive(Deserialize, Serialize))]
+#[derive(Clone, Debug, Default)]
+struct Store {
+    offset: i32,
+    counts: Vec<u64>,
+}
+
+impl Store {
+    /// The index of the last bucket.
+    fn high(&self) -> i32 {
+        self.offset + self.counts.len() as i32 - 1
+    }
+
+    /// The range of bucket indexes if the given bucket was added.
+    fn range_with(&self, index: i32) -> (i32, i32) {
+        if self.counts.is_empty() {
+            (index, index)
+        } else {
+            (self.offset.min(index), self.high().max(index))
+        }
+    }
+
+    fn increment(&mut self, index: i32, count: u64) {
+        if self.counts.is_empty() {
+            self.offset = index;
+            self.counts.push(0);
+        } else if index < self.offset {
+            let grow = (self.offset - index) as usize;
+            self.counts.splice(0..0, vec![0; grow]);
+            self.offset = index;
+        } else if index >= self.offset + self.counts.len() as i32 {
+            self.counts.resize((index - self.offset) as usize + 1, 0);
+        }
+        self.counts[(index - self.offset) as usize] += count;
+    }
+
+    /// Merge each `2^change` consecutive buckets.
+    fn downscale(&mut self, change: i8) {
+        if self.counts.is_empty() {
+            return;
+        }
+
+        let offset = self.offset >> change;
+        let high = self.high() >> change;
+        let mut counts = vec![0; (high - offset) as usize + 1];
+        for (idx, count) in self.counts.iter().enumerate() {
+            counts[(((self.offset + idx as i32) >> change) - offset) as usize] += count;
+        }
+        self.offset = offset;
+        self.counts = counts;
+    }
+
+    /// Subtract the counts of the other store, which is at the same scale.
+    fn subtract(&mut self, other: &Store) {
+        for (idx, count) in other.counts.iter().enumerate() {
+            let index = other.offset + idx as i32;
+            if index >= self.offset && index <= self.high() {
+                let own = &mut self.counts[(index - self.offset) as usize];
+                *own = own.saturating_sub(*count);
+            }
+        }
+    }
+
+    fn to_buckets(&self) -> ExponentialBuckets {
+        ExponentialBuckets::new(self.offset, self.counts.clone())
+    }
+}
+
+/// The number of scales the range of bucket indexes `low..=high` has to be
+/// lowered by to fit in `max_size` buckets.
+fn scale_change(mut low: i32, mut high: i32, max_size: usize) -> i8 {
+    let mut change = 0;
+    while (high - low) as usize >= max_size {
+        low >>= 1;
+        high >>= 1;
+        change += 1;
+    }
+    change
+}
+
+/// The index of the bucket the positive value falls into at the given scale.
+fn map_to_index(value: f64, scale: i8) -> i32 {
+    let bits = value.to_bits();
+    // The value is in `[2^exponent, 2^(exponent + 1))`, and exactly
+    // `2^exponent` if the significand is zero.
+    let exponent = ((bits >> 52) & 0x7ff) as i32 - 1023;
+    let power_of_two = bits & ((1 << 52) - 1) == 0;
+
+    if scale <= 0 {
+        let index = if power_of_two { exponent - 1 } else { exponent };
+        index >> -scale
+    } else if power_of_two {
+        (exponent << scale) - 1
+    } else {
+        (value.log2() * f64::from(1 << scale)).ceil() as i32 - 1
+    }
+}
+
+/// The lower boundary of the bucket with the given index.
+fn lower_boundary(index: i32, scale: i8) -> f64 {
+    (f64::from(index) * 2f64.powi(-i32::from(scale))).exp2()
+}
+
+impl Sum for ExponentialHistogramAggregator {
+    fn sum(&self) -> Result<Number> {
+        self.inner
+            .read()
+            .map_err(From::from)
+            .map(|inner| inner.state.sum.load())
+    }
+}
+
+impl Count for ExponentialHistogramAggregator {
+    fn count(&self) -> Result<u64> {
+        self.inner
+            .read()
+            .map_err(From::from)
+            .map(|inner| inner.state.count)
+    }
+}
+
+impl Histogram for ExponentialHistogramAggregator {
+    /// The exponential buckets as explicit boundaries, with the zero count in
+    /// the bucket between the negative and the positive buckets.
+    fn histogram(&self) -> Result<Buckets> {
+        self.inner.read().map_err(From::from).map(|inner| {
+            let state = &inner.state;
+            let mut boundaries = Vec::new();
+            let mut counts = Vec::new();
+
+            let negative = &state.negative;
+            if !negative.counts.is_empty() {
+                let high = negative.offset + negative.counts.len() as i32;
+                counts.push(0.0);
+                for index in (negative.offset..high).rev() {
+                    boundaries.push(-lower_boundary(index + 1, state.scale));
+                    counts.push(negative.counts[(index - negative.offset) as usize] as f64);
+                }
+                boundaries.push(-lower_boundary(negative.offset, state.scale));
+            }
+
+            counts.push(state.zero_count as f64);
+
+            let positive = &state.positive;
+            if !positive.counts.is_empty() {
+                let high = positive.offset + positive.counts.len() as i32;
+                for index in positive.offset..high {
+                    boundaries.push(lower_boundary(index, state.scale));
+                    counts.push(positive.counts[(index - positive.offset) as usize] as f64);
+                }
+                boundaries.push(lower_boundary(high, state.scale));
+                counts.push(0.0);
+            }
+
+            Buckets::new(boundaries, counts)
+        })
+    }
+}
+
+impl ExponentialHistogram for ExponentialHistogramAggregator {
+    fn scale(&self) -> Result<i8> {
+        self.inner
+            .read()
+            .map_err(From::from)
+            .map(|inner| inner.state.scale)
+    }
+
+    fn zero_count(&self) -> Result<u64> {
+        self.inner
+            .read()
+            .map_err(From::from)
+            .map(|inner| inner.state.zero_count)
+    }
+
+    fn positive(&self) -> Result<ExponentialBuckets> {
+        self.inner
+            .read()
+            .map_err(From::from)
+            .map(|inner| inner.state.positive.to_buckets())
+    }
+
+    fn negative(&self) -> Result<ExponentialBuckets> {
+        self.inner
+            .read()
+            .map_err(From::from)
+            .map(|inner| inner.state.negative.to_buckets())
+    }
+}
+
+impl Subtractor for ExponentialHistogramAggregator {
+    fn subtract(
+        &self,
+        operand: &(dyn Aggregator + Send + Sync),
+        result: &(dyn Aggregator + Send + Sync),
+        descriptor: &Descriptor,
+    ) -> Result<()> {
+        match (
+            operand.as_any().downcast_ref::<Self>(),
+            result.as_any().downcast_ref::<Self>(),
+        ) {
+            (Some(op), Some(res)) => {
+                let inner = self.inner.read()?;
+                let op = op.inner.read()?;
+                let mut res = res.inner.write()?;
+
+                // Subtract at the lower of both scales.
+                let scale = inner.state.scale.min(op.state.scale);
+                let mut positive = inner.state.positive.clone();
+                let mut negative = inner.state.negative.clone();
+                positive.downscale(inner.state.scale - scale);
+                negative.downscale(inner.state.scale - scale);
+                let mut op_positive = op.state.positive.clone();
+                let mut op_negative = op.state.negative.clone();
+                op_positive.downscale(op.state.scale - scale);
+                op_negative.downscale(op.state.scale - scale);
+                positive.subtract(&op_positive);
+                negative.subtract(&op_negative);
+
+                let sum = inner.state.sum.load().to_atomic();
+                sum.fetch_sub(descriptor.number_kind(), &op.state.sum.load());
+                res.state = State {
+                    scale,
+                    count: inner.state.count.saturating_sub(op.state.count),
+                    sum,
+                    zero_count: inner.state.zero_count.saturating_sub(op.state.zero_count),
+                    positive,
+                    negative,
+                };
+                Ok(())
+            }
+            _ => Err(MetricsError::InconsistentAggregator(format!(
+                "Expected {:?}, got: {:?} and {:?}",
+                self, operand, result
+            ))),
+        }
+    }
+}
+
+impl Aggregator for ExponentialHistogramAggregator {
+    fn update(&self, number: &Number, _descriptor: &Descriptor) -> Result<()> {
+        self.inner.write().map_err(From::from).map(|mut inner| {
+            let kind = inner.kind.clone();
+            let value = number.to_f64(&kind);
+            // NaN and infinities have no bucket, they are dropped.
+            if !value.is_finite() {
+                return;
+            }
+            let max_size = inner.max_size;
+            let state = &mut inner.state;
+
+            state.count += 1;
+            state.sum.fetch_add(&kind, number);
+            if value == 0.0 {
+                state.zero_count += 1;
+                return;
+            }
+
+            let index = map_to_index(value.abs(), state.scale);
+            let store = if value > 0.0 {
+                &state.positive
+            } else {
+                &state.negative
+            };
+            let (low, high) = store.range_with(index);
+            let change = scale_change(low, high, max_size).min(state.scale - MIN_SCALE);
+            state.downscale(change);
+
+            let index = index >> change;
+            if value > 0.0 {
+                state.positive.increment(index, 1);
+            } else {
+                state.negative.increment(index, 1);
+            }
+        })
+    }
+
+    fn synchronized_move(
+        &self,
+        other: &Arc<dyn Aggregator + Send + Sync>,
+        _descriptor: &Descriptor,
+    ) -> Result<()> {
+        if let Some(other) = other.as_any().downcast_ref::<Self>() {
+            self.inner
+                .write()
+                .map_err(From::from)
+                .and_then(|mut inner| {
+                    other.inner.write().map_err(From::from).map(|mut other| {
+                        other.state = mem::replace(&mut inner.state, State::empty())
+                    })
+                })
+        } else {
+            Err(MetricsError::InconsistentAggregator(format!(
+                "Expected {:?}, got: {:?}",
+                self, other
+            )))
+        }
+    }
+
+    fn merge(&self, other: &(dyn Aggregator + Send + Sync), desc: &Descriptor) -> Result<()> {
+        if let Some(other) = other
+            .as_any()
+            .downcast_ref::<ExponentialHistogramAggregator>()
+        {
+            self.inner
+                .write()
+                .map_err(From::from)
+                .and_then(|mut inner| {
+                    other.inner.read().map_err(From::from).map(|other| {
+                        let max_size = inner.max_size;
+                        let state = &mut inner.state;
+                        let mut positive = other.state.positive.clone();
+                        let mut negative = other.state.negative.clone();
+
+                        // Both histograms are merged at the lower of their
+                        // scales, lowered further if the merged buckets would
+                        // not fit.
+                        let scale = state.scale.min(other.state.scale);
+                        state.downscale(state.scale - scale);
+                        let other_change = other.state.scale - scale;
+                        if other_change > 0 {
+                            positive.downscale(other_change);
+                            negative.downscale(other_change);
+                        }
+
+                        let change = [(&state.positive, &positive), (&state.negative, &negative)]
+                            .iter()
+                            .filter(|(_, theirs)| !theirs.counts.is_empty())
+                            .map(|(ours, theirs)| {
+                                let (low, high) = ours.range_with(theirs.offset);
+                                scale_change(low, high.max(theirs.high()), max_size)
+                            })
+                            .max()
+                            .unwrap_or(0)
+                            .min(state.scale - MIN_SCALE);
+                        state.downscale(change);
+                        positive.downscale(change);
+                        negative.downscale(change);
+
+                        for (idx, count) in positive.counts.iter().enumerate() {
+                            state
+                                .positive
+                                .increment(positive.offset + idx as i32, *count);
+                        }
+                        for (idx, count) in negative.counts.iter().enumerate() {
+                            state
+                                .negative
+                                .increment(negative.offset + idx as i32, *count);
+                        }
+                        state.count += other.state.count;
+                        state.zero_count += other.state.zero_count;
+                        state
+                            .sum
+                            .fetch_add(desc.number_kind(), &other.state.sum.load());
+                    })
+                })
+        } else {
+            Err(MetricsError::InconsistentAggregator(format!(
+                "Expected {:?}, got: {:?}",
+                self, other
+            )))
+        }
+    }
+
+    fn as_any(&self) -> &dyn std::any::Any {
+        self
+    }
+
+    fn as_subtractor(&self) -> Option<&dyn Subtractor> {
+        Some(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::metrics::InstrumentKind;
+
+    fn descriptor() -> Descriptor {
+        Descriptor::new(
+            "latency".to_string(),
+            "test".to_string(),
+            InstrumentKind::ValueRecorder,
+            NumberKind::F64,
+        )
+    }
+
+    fn record(agg: &ExponentialHistogramAggregator, values: &[f64]) {
+        let descriptor = descriptor();
+        for value in values {
+            agg.update(&Number::from(*value), &descriptor).unwrap();
+        }
+    }
+
+    #[test]
+    fn map_values_to_indexes() {
+        let data = vec![
+            (1.0, 0, -1),
+            (1.5, 0, 0),
+            (2.0, 0, 0),
+            (3.0, 0, 1),
+            (4.0, 0, 1),
+            (4.5, 0, 2),
+            (0.5, 0, -2),
+            (0.3, 0, -2),
+            (8.0, -1, 1),
+            (9.0, -1, 1),
+            (17.0, -1, 2),
+            (2.0, 1, 1),
+            (2.5, 1, 2),
+            (3.0, 1, 3),
+            (1.0, 3, -1),
+            (1.1, 3, 1),
+        ];
+
+        for (value, scale, expected) in data {
+            assert_eq!(
+                map_to_index(value, scale),
+                expected,
+                "value {} at scale {}",
+                value,
+                scale
+            );
+            assert!(lower_boundary(expected, scale) < value);
+            assert!(value <= lower_boundary(expected + 1, scale) * (1.0 + 1e-12));
+        }
+    }
+
+    #[test]
+    fn downscale_to_max_size() {
+        let agg = exponential_histogram(&descriptor(), 4);
+        record(&agg, &[1.5, 3.0, 0.0, 6.0, -10.0, 12.0, 24.0]);
+
+        assert_eq!(agg.count().unwrap(), 7);
+        assert_eq!(agg.sum().unwrap().to_f64(&NumberKind::F64), 36.5);
+        assert_eq!(agg.zero_count().unwrap(), 1);
+        // (1, 2], (2, 4], (4, 8], (8, 16], (16, 32] need scale -1 to fit in 4
+        // buckets: (1, 4], (4, 16], (16, 64].
+        assert_eq!(agg.scale().unwrap(), -1);
+        assert_eq!(
+            agg.positive().unwrap(),
+            ExponentialBuckets::new(0, vec![2, 2, 1])
+        );
+        assert_eq!(agg.negative().unwrap(), ExponentialBuckets::new(1, vec![1]));
+
+        let buckets = agg.histogram().unwrap();
+        assert_eq!(
+            buckets.boundaries(),
+            &vec![-16.0, -4.0, 1.0, 4.0, 16.0, 64.0]
+        );
+        assert_eq!(buckets.counts(), &vec![0.0, 1.0, 1.0, 2.0, 2.0, 1.0, 0.0]);
+    }
+
+    #[test]
+    fn drop_non_finite_values() {
+        let agg = exponential_histogram(&descriptor(), 4);
+        record(
+            &agg,
+            &[1.5, f64::NAN, f64::INFINITY, f64::NEG_INFINITY, -3.0],
+        );
+
+        assert_eq!(agg.count().unwrap(), 2);
+        assert_eq!(agg.sum().unwrap().to_f64(&NumberKind::F64), -1.5);
+        assert_eq!(agg.positive().unwrap().counts().iter().sum::<u64>(), 1);
+        assert_eq!(agg.negative().unwrap().counts().iter().sum::<u64>(), 1);
+    }
+
+    #[test]
+    fn merge_at_lower_scale() {
+        let descriptor = descriptor();
+        let fine = exponential_histogram(&descriptor, 4);
+        let coarse = exponential_histogram(&descriptor, 4);
+        record(&fine, &[1.5, 1.7]);
+        record(&coarse, &[1.5, 3.0, 6.0, 12.0, 24.0]);
+        let fine_scale = fine.scale().unwrap();
+        assert!(fine_scale > 0);
+
+        fine.merge(&coarse, &descriptor).unwrap();
+
+        assert_eq!(fine.count().unwrap(), 7);
+        assert_eq!(fine.scale().unwrap(), -1);
+        assert_eq!(
+            fine.positive().unwrap(),
+            ExponentialBuckets::new(0, vec![4, 2, 1])
+        );
+    }
+
+    #[test]
+    fn merge_downscales_to_fit() {
+        let descriptor = descriptor();
+        let low = exponential_histogram(&descriptor, 4);
+        let high = exponential_histogram(&descriptor, 4);
+        record(&low, &[1.5, 3.0]);
+        record(&high, &[100.0, 200.0]);
+        assert_eq!(low.scale().unwrap(), high.scale().unwrap());
+
+        low.merge(&high, &descriptor).unwrap();
+
+        let positive = low.positive().unwrap();
+        assert!(positive.counts().len() <= 4);
+        assert_eq!(positive.counts().iter().sum::<u64>(), 4);
+        assert_eq!(low.count().unwrap(), 4);
+    }
+
+    #[test]
+    fn subtract_previous_state() {
+        let descriptor = descriptor();
+        let previous = exponential_histogram(&descriptor, 4);
+        let current = exponential_histogram(&descriptor, 4);
+        let delta = exponential_histogram(&descriptor, 4);
+        record(&previous, &[1.5, 3.0]);
+        record(&current, &[1.5, 3.0, 0.0, 6.0, 12.0, 1.5]);
+
+        current.subtract(&previous, &delta, &descriptor).unwrap();
+
+        assert_eq!(delta.count().unwrap(), 4);
+        assert_eq!(delta.sum().unwrap().to_f64(&NumberKind::F64), 19.5);
+        assert_eq!(delta.zero_count().unwrap(), 1);
+        assert_eq!(delta.scale().unwrap(), 0);
+        assert_eq!(
+            delta.positive().unwrap(),
+            ExponentialBuckets::new(0, vec![1, 0, 1, 1])
+        );
+    }
+}
@@ -1,101 +1,206 @@
+use super::double_buffer::DoubleBuffer;
 use crate::metrics::{AtomicNumber, Descriptor, MetricsError, Number, NumberKind, Result};
-use crate::sdk::export::metrics::{Buckets, Count, Histogram, Sum};
+use crate::sdk::export::metrics::{
+    Buckets, Count, Exemplar, Exemplars, Histogram, Subtractor, Sum,
+};
 use crate::sdk::metrics::export::metrics::Aggregator;
+#[cfg(feature = "serialize")]
+use serde::{Deserialize, Serialize};
 use std::mem;
-use std::sync::{Arc, RwLock};
+use std::sync::{Arc, Mutex};
 
 /// Create a new histogram for the given descriptor with the given boundaries
-pub fn histogram(desc: &Descriptor, boundaries: &[f64]) -> HistogramAggregator {
+pub fn histogram(_desc: &Descriptor, boundaries: &[f64]) -> HistogramAggregator {
     let mut sorted_boundaries = boundaries.to_owned();
     sorted_boundaries.sort_by(|a, b| a.partial_cmp(&b).unwrap());
-    let state = State::empty(&sorted_boundaries);
+    let buffer = DoubleBuffer::new(
+        State::empty(&sorted_boundaries),
+        State::empty(&sorted_boundaries),
+    );
 
     HistogramAggregator {
-        inner: RwLock::new(Inner {
-            boundaries: sorted_boundaries,
-            kind: desc.number_kind().clone(),
-            state,
-        }),
+        boundaries: sorted_boundaries,
+        buffer,
     }
 }
 
 /// This aggregator observes events and counts them in pre-determined buckets. It
 /// also calculates the sum and count of all events.
+#[cfg_attr(feature = "serialize", derive(Deserialize, Serialize))]
 #[derive(Debug)]
 pub struct HistogramAggregator {
-    inner: RwLock<Inner>,
-}
-
-#[derive(Debug)]
-struct Inner {
     boundaries: Vec<f64>,
-    kind: NumberKind,
-    state: State,
+    buffer: DoubleBuffer<State>,
 }
 
+#[cfg_attr(feature = "serialize", derive(Deserialize, Serialize))]
 #[derive(Debug)]
 struct State {
-    bucket_counts: Vec<f64>,
+    bucket_counts: Vec<AtomicNumber>,
     count: AtomicNumber,
     sum: AtomicNumber,
+    /// The most recent exemplar of each bucket.
+    exemplars: Mutex<Vec<Option<Exemplar>>>,
 }
 
 impl State {
     fn empty(boundaries: &[f64]) -> Self {
         State {
-            bucket_counts: vec![0.0; boundaries.len() + 1],
+            bucket_counts: (0..=boundaries.len())
+                .map(|_| AtomicNumber::default())
+                .collect(),
             count: NumberKind::U64.zero().to_atomic(),
             sum: NumberKind::U64.zero().to_atomic(),
+            exemplars: Mutex::new(vec![None; boundaries.len() + 1]),
+        }
+    }
+
+    fn bucket_counts(&self) -> impl Iterator<Item = u64> + '_ {
+        self.bucket_counts
+            .iter()
+            .map(|count| count.load().to_u64(&NumberKind::U64))
+    }
+
+    /// Move the contents of this state into `other`, leaving this state empty.
+    fn move_into(&self, other: &State) -> Result<()> {
+        let zero = NumberKind::U64.zero();
+        for (count, other) in self.bucket_counts.iter().zip(other.bucket_counts.iter()) {
+            other.store(&count.load());
+            count.store(&zero);
         }
+        other.count.store(&self.count.load());
+        self.count.store(&zero);
+        other.sum.store(&self.sum.load());
+        self.sum.store(&zero);
+
+        let mut exemplars = self.exemplars.lock()?;
+        let empty = vec![None; exemplars.len()];
+        *other.exemplars.lock()? = mem::replace(&mut *exemplars, empty);
+        Ok(())
     }
 }
 
 impl Sum for HistogramAggregator {
     fn sum(&self) -> Result<Number> {
-        self.inner
-            .read()
-            .map_err(From::from)
-            .map(|inner| inner.state.sum.load())
+        Ok(self.buffer.hot().sum.load())
     }
 }
 
 impl Count for HistogramAggregator {
     fn count(&self) -> Result<u64> {
-        self.inner
-            .read()
-            .map_err(From::from)
-            .map(|inner| inner.state.sum.load().to_u64(&NumberKind::U64))
+        Ok(self.buffer.hot().count.load().to_u64(&NumberKind::U64))
     }
 }
 
 impl Histogram for HistogramAggregator {
     fn histogram(&self) -> Result<Buckets> {
-        self.inner
-            .read()
+        let counts = self
+            .buffer
+            .hot()
+            .bucket_counts()
+            .map(|count| count as f64)
+            .collect();
+        Ok(Buckets::new(self.boundaries.clone(), counts))
+    }
+}
+
+impl Exemplars for HistogramAggregator {
+    fn exemplars(&self) -> Result<Vec<Exemplar>> {
+        self.buffer
+            .hot()
+            .exemplars
+            .lock()
             .map_err(From::from)
-            .map(|inner| Buckets::new(inner.boundaries.clone(), inner.state.bucket_counts.clone()))
+            .map(|exemplars| exemplars.iter().filter_map(Option::clone).collect())
     }
 }
 
-impl Aggregator for HistogramAggregator {
-    fn update(&self, number: &Number, descriptor: &Descriptor) -> Result<()> {
-        self.inner.write().map_err(From::from).map(|mut inner| {
-            let kind = descriptor.number_kind();
-            let as_float = number.to_f64(kind);
-
-            let mut bucket_id = inner.boundaries.len();
-            for (idx, boundary) in inner.boundaries.iter().enumerate() {
-                if as_float < *boundary {
-                    bucket_id = idx;
-                    break;
+impl Subtractor for HistogramAggregator {
+    fn subtract(
+        &self,
+        operand: &(dyn Aggregator + Send + Sync),
+        result: &(dyn Aggregator + Send + Sync),
+        descriptor: &Descriptor,
+    ) -> Result<()> {
+        match (
+            operand.as_any().downcast_ref::<Self>(),
+            result.as_any().downcast_ref::<Self>(),
+        ) {
+            (Some(op), Some(res)) => {
+                if self.boundaries != op.boundaries || self.boundaries != res.boundaries {
+                    return Err(MetricsError::InconsistentAggregator(
+                        "Histograms with different boundaries".to_string(),
+                    ));
                 }
+
+                let kind = descriptor.number_kind();
+                let (inner, op, state) = (self.buffer.hot(), op.buffer.hot(), res.buffer.hot());
+                state.count.store(&inner.count.load());
+                state.count.fetch_sub(&NumberKind::U64, &op.count.load());
+                state.sum.store(&inner.sum.load());
+                state.sum.fetch_sub(kind, &op.sum.load());
+                for ((count, previous), result) in inner
+                    .bucket_counts()
+                    .zip(op.bucket_counts())
+                    .zip(state.bucket_counts.iter())
+                {
+                    result.store(&count.saturating_sub(previous).into());
+                }
+                let exemplars = inner.exemplars.lock()?.clone();
+                *state.exemplars.lock()? = exemplars;
+                Ok(())
+            }
+            _ => Err(MetricsError::InconsistentAggregator(format!(
+                "Expected {:?}, got: {:?} and {:?}",
+                self, operand, result
+            ))),
+        }
+    }
+}
+
+impl HistogramAggregator {
+    fn record(
+        &self,
+        number: &Number,
+        descriptor: &Descriptor,
+        exemplar: Option<Exemplar>,
+    ) -> Result<()> {
+        let kind = descriptor.number_kind();
+        let as_float = number.to_f64(kind);
+
+        let mut bucket_id = self.boundaries.len();
+        for (idx, boundary) in self.boundaries.iter().enumerate() {
+            if as_float < *boundary {
+                bucket_id = idx;
+                break;
             }
+        }
 
-            inner.state.count.fetch_add(&NumberKind::U64, &1u64.into());
-            inner.state.sum.fetch_add(kind, number);
-            inner.state.bucket_counts[bucket_id] += 1.0;
+        self.buffer.update(|state| {
+            state.count.fetch_add(&NumberKind::U64, &1u64.into());
+            state.sum.fetch_add(kind, number);
+            state.bucket_counts[bucket_id].fetch_add(&NumberKind::U64, &1u64.into());
+            if exemplar.is_some() {
+                state.exemplars.lock()?[bucket_id] = exemplar;
+            }
+            Ok(())
         })
     }
+}
+
+impl Aggregator for HistogramAggregator {
+    fn update(&self, number: &Number, descriptor: &Descriptor) -> Result<()> {
+        self.record(number, descriptor, None)
+    }
+
+    fn update_with_exemplar(
+        &self,
+        number: &Number,
+        descriptor: &Descriptor,
+        exemplar: Exemplar,
+    ) -> Result<()> {
+        self.record(number, descriptor, Some(exemplar))
+    }
 
     fn synchronized_move(
         &self,
@@ -103,15 +208,14 @@ impl Aggregator for HistogramAggregator {
         _descriptor: &crate::metrics::Descriptor,
     ) -> Result<()> {
         if let Some(other) = other.as_any().downcast_ref::<Self>() {
-            self.inner
-                .write()
-                .map_err(From::from)
-                .and_then(|mut inner| {
-                    other.inner.write().map_err(From::from).map(|mut other| {
-                        let empty = State::empty(&inner.boundaries);
-                        other.state = mem::replace(&mut inner.state, empty)
-                    })
-                })
+            if self.boundaries != other.boundaries {
+                return Err(MetricsError::InconsistentAggregator(
+                    "Histograms with different boundaries".to_string(),
+                ));
+            }
+            self.buffer
+                .swap(|state| state.move_into(other.buffer.hot()))
+                .and_then(|result| result)
         } else {
             Err(MetricsError::InconsistentAggregator(format!(
                 "Expected {:?}, got: {:?}",
@@ -122,25 +226,26 @@ impl Aggregator for HistogramAggregator {
 
     fn merge(&self, other: &(dyn Aggregator + Send + Sync), desc: &Descriptor) -> Result<()> {
         if let Some(other) = other.as_any().downcast_ref::<HistogramAggregator>() {
-            self.inner
-                .write()
-                .map_err(From::from)
-                .and_then(|mut inner| {
-                    other.inner.read().map_err(From::from).map(|other| {
-                        inner
-                            .state
-                            .sum
-                            .fetch_add(desc.number_kind(), &other.state.sum.load());
-                        inner
-                            .state
-                            .count
-                            .fetch_add(&NumberKind::U64, &other.state.count.load());
-
-                        for idx in 0..inner.state.bucket_counts.len() {
-                            inner.state.bucket_counts[idx] += other.state.bucket_counts[idx];
-                        }
-                    })
-                })
+            if self.boundaries != other.boundaries {
+                return Err(MetricsError::InconsistentAggregator(
+                    "Histograms with different boundaries".to_string(),
+                ));
+            }
+            let other = other.buffer.hot();
+            let other_exemplars = other.exemplars.lock()?.clone();
+            self.buffer.update(|state| {
+                state.sum.fetch_add(desc.number_kind(), &other.sum.load());
+                state.count.fetch_add(&NumberKind::U64, &other.count.load());
+
+                for (count, other) in state.bucket_counts.iter().zip(other.bucket_counts()) {
+                    count.fetch_add(&NumberKind::U64, &other.into());
+                }
+                let mut exemplars = state.exemplars.lock()?;
+                for (exemplar, other) in exemplars.iter_mut().zip(other_exemplars.iter()) {
+                    super::merge_exemplar(exemplar, other);
+                }
+                Ok(())
+            })
         } else {
             Err(MetricsError::InconsistentAggregator(format!(
                 "Expected {:?}, got: {:?}",
@@ -152,4 +257,8 @@ impl Aggregator for HistogramAggregator {
     fn as_any(&self) -> &dyn std::any::Any {
         self
     }
+
+    fn as_subtractor(&self) -> Option<&dyn Subtractor> {
+        Some(self)
+    }
 }
@@ -1,30 +1,40 @@
-use crate::metrics::{Descriptor, MetricsError, Number, Result};
+use super::double_buffer::DoubleBuffer;
+use crate::metrics::{AtomicNumber, Descriptor, MetricsError, Number, Result};
 use crate::sdk::export::metrics::{Aggregator, LastValue};
+#[cfg(feature = "serialize")]
+use serde::{Deserialize, Serialize};
 use std::any::Any;
-use std::sync::{Arc, Mutex};
-use std::time::SystemTime;
+use std::sync::atomic::{self, AtomicU64, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Set in the timestamp of a state while its value is written.
+const WRITING: u64 = 1 << 63;
 
 /// Create a new `LastValueAggregator`
 pub fn last_value() -> LastValueAggregator {
     LastValueAggregator {
-        inner: Mutex::new(Inner::default()),
+        buffer: DoubleBuffer::new(State::default(), State::default()),
     }
 }
 
 /// Aggregates last value events.
+#[cfg_attr(feature = "serialize", derive(Deserialize, Serialize))]
 #[derive(Debug)]
 pub struct LastValueAggregator {
-    inner: Mutex<Inner>,
+    buffer: DoubleBuffer<State>,
 }
 
 impl Aggregator for LastValueAggregator {
     fn update(&self, number: &Number, _descriptor: &Descriptor) -> Result<()> {
-        self.inner.lock().map_err(Into::into).map(|mut inner| {
-            inner.state = Some(LastValueData {
-                value: number.clone(),
-                timestamp: SystemTime::now(),
-            });
-        })
+        self.buffer.update(|state| {
+            // Timestamps strictly increase, even if the clock goes backwards.
+            state.store(number, |current| {
+                Some(to_nanos(SystemTime::now()).max(current + 1))
+            })
+        });
+        Ok(())
     }
     fn synchronized_move(
         &self,
@@ -32,10 +42,10 @@ impl Aggregator for LastValueAggregator {
         _descriptor: &Descriptor,
     ) -> Result<()> {
         if let Some(other) = other.as_any().downcast_ref::<Self>() {
-            self.inner.lock().map_err(From::from).and_then(|mut inner| {
-                other.inner.lock().map_err(From::from).map(|mut other| {
-                    other.state = inner.state.take();
-                })
+            self.buffer.swap(|state| {
+                let (value, timestamp) = state.load();
+                other.buffer.hot().store(&value, |_| Some(timestamp));
+                state.timestamp.store(0, Ordering::Release);
             })
         } else {
             Err(MetricsError::InconsistentAggregator(format!(
@@ -50,22 +60,14 @@ impl Aggregator for LastValueAggregator {
         _descriptor: &Descriptor,
     ) -> Result<()> {
         if let Some(other) = other.as_any().downcast_ref::<Self>() {
-            self.inner.lock().map_err(From::from).and_then(|mut inner| {
-                other.inner.lock().map_err(From::from).map(|mut other| {
-                    match (&inner.state, &other.state) {
-                        // Take if other timestamp is greater
-                        (Some(checkpoint), Some(other_checkpoint))
-                            if other_checkpoint.timestamp > checkpoint.timestamp =>
-                        {
-                            inner.state = other.state.take()
-                        }
-                        // Take if no value exists currently
-                        (None, Some(_)) => inner.state = other.state.take(),
-                        // Otherwise done
-                        _ => (),
-                    }
+            let (other_value, other_timestamp) = other.buffer.hot().load();
+            self.buffer.update(|state| {
+                // Take if other timestamp is greater
+                state.store(&other_value, |current| {
+                    Some(other_timestamp).filter(|other| *other > current)
                 })
-            })
+            });
+            Ok(())
         } else {
             Err(MetricsError::InconsistentAggregator(format!(
                 "Expected {:?}, got: {:?}",
@@ -80,23 +82,137 @@ impl Aggregator for LastValueAggregator {
 
 impl LastValue for LastValueAggregator {
     fn last_value(&self) -> Result<(Number, SystemTime)> {
-        self.inner.lock().map_err(Into::into).and_then(|inner| {
-            if let Some(checkpoint) = &inner.state {
-                Ok((checkpoint.value.clone(), checkpoint.timestamp))
-            } else {
-                Err(MetricsError::NoDataCollected)
-            }
-        })
+        match self.buffer.hot().load() {
+            (_, 0) => Err(MetricsError::NoDataCollected),
+            (value, timestamp) => Ok((value, UNIX_EPOCH + Duration::from_nanos(timestamp))),
+        }
     }
 }
 
+#[cfg_attr(feature = "serialize", derive(Deserialize, Serialize))]
 #[derive(Debug, Default)]
-struct Inner {
-    state: Option<LastValueData>,
+struct State {
+    value: AtomicNumber,
+    /// Nanoseconds since the unix epoch of the last update, or zero if there
+    /// is no value, with the `WRITING` bit set while the value is written.
+    timestamp: AtomicU64,
 }
 
-#[derive(Debug)]
-struct LastValueData {
-    value: Number,
-    timestamp: SystemTime,
+impl State {
+    /// Store the value with the timestamp computed from the current one, unless
+    /// `timestamp` returns `None`.
+    ///
+    /// The timestamp is the sequence of a seqlock: writers claim the state with
+    /// a single compare-and-swap setting `WRITING`, so the value always has the
+    /// timestamp it was stored with, and the timestamp is computed after any
+    /// concurrent update completed, so that an older update never overwrites a
+    /// newer one.
+    fn store<F: Fn(u64) -> Option<u64>>(&self, value: &Number, timestamp: F) {
+        loop {
+            let current = self.timestamp.load(Ordering::Acquire);
+            if current & WRITING == 0 {
+                let next = match timestamp(current) {
+                    Some(next) => next,
+                    None => return,
+                };
+                if self
+                    .timestamp
+                    .compare_exchange_weak(
+                        current,
+                        current | WRITING,
+                        Ordering::Acquire,
+                        Ordering::Relaxed,
+                    )
+                    .is_ok()
+                {
+                    atomic::fence(Ordering::Release);
+                    self.value.store(value);
+                    self.timestamp.store(next, Ordering::Release);
+                    return;
+                }
+            }
+            thread::yield_now();
+        }
+    }
+
+    /// Load the value and its timestamp, zero if there is no value.
+    fn load(&self) -> (Number, u64) {
+        loop {
+            let timestamp = self.timestamp.load(Ordering::Acquire);
+            if timestamp & WRITING == 0 {
+                let value = self.value.load();
+                atomic::fence(Ordering::Acquire);
+                if self.timestamp.load(Ordering::Relaxed) == timestamp {
+                    return (value, timestamp);
+                }
+            }
+            thread::yield_now();
+        }
+    }
+}
+
+fn to_nanos(time: SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH)
+        .map_or(1, |duration| duration.as_nanos() as u64)
+        .max(1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::metrics::{InstrumentKind, NumberKind};
+
+    fn descriptor() -> Descriptor {
+        Descriptor::new(
+            "temperature".to_string(),
+            "test".to_string(),
+            InstrumentKind::ValueObserver,
+            NumberKind::F64,
+        )
+    }
+
+    #[test]
+    fn merge_keeps_the_latest_value() {
+        let descriptor = descriptor();
+        let older = last_value();
+        let newer = last_value();
+        older.update(&Number::from(1.0), &descriptor).unwrap();
+        newer.update(&Number::from(2.0), &descriptor).unwrap();
+        let (_, newer_time) = newer.last_value().unwrap();
+        assert!(older.last_value().unwrap().1 < newer_time);
+
+        newer.merge(&older, &descriptor).unwrap();
+        let (value, time) = newer.last_value().unwrap();
+        assert_eq!((value.to_f64(&NumberKind::F64), time), (2.0, newer_time));
+
+        older.merge(&newer, &descriptor).unwrap();
+        let (value, time) = older.last_value().unwrap();
+        assert_eq!((value.to_f64(&NumberKind::F64), time), (2.0, newer_time));
+    }
+
+    #[test]
+    fn concurrent_timestamps_never_go_backwards() {
+        let descriptor = Arc::new(descriptor());
+        let agg = Arc::new(last_value());
+        let workers = (0..4)
+            .map(|_| {
+                let descriptor = descriptor.clone();
+                let agg = agg.clone();
+                thread::spawn(move || {
+                    let mut previous = 0;
+                    for _ in 0..1_000 {
+                        agg.update(&Number::from(1.0), &descriptor).unwrap();
+                        // Timestamps never go backwards, even when an update
+                        // races with another one.
+                        let (_, timestamp) = agg.buffer.hot().load();
+                        assert!(timestamp > previous);
+                        previous = timestamp;
+                    }
+                })
+            })
+            .collect::<Vec<_>>();
+        for worker in workers {
+            worker.join().unwrap();
+        }
+    }
 }
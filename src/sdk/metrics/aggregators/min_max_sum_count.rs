@@ -1,70 +1,60 @@
+use super::double_buffer::DoubleBuffer;
 use crate::metrics::{AtomicNumber, Descriptor, MetricsError, Number, NumberKind, Result};
 use crate::sdk::export::metrics::{Aggregator, Count, Max, Min, MinMaxSumCount, Sum};
+#[cfg(feature = "serialize")]
+use serde::{Deserialize, Serialize};
 use std::any::Any;
-use std::cmp::Ordering;
-use std::sync::{Arc, Mutex};
+use std::sync::Arc;
 
 /// Create a new `MinMaxSumCountAggregator`
 pub fn min_max_sum_count(descriptor: &Descriptor) -> MinMaxSumCountAggregator {
     let kind = descriptor.number_kind().clone();
     MinMaxSumCountAggregator {
-        inner: Mutex::new(Inner { state: None }),
+        buffer: DoubleBuffer::new(State::empty(&kind), State::empty(&kind)),
         kind,
     }
 }
 
-#[derive(Debug)]
-struct Inner {
-    state: Option<State>,
-}
-
 /// An `Aggregator` that aggregates events that form a distribution, keeping
 /// only the min, max, sum, and count.
+#[cfg_attr(feature = "serialize", derive(Deserialize, Serialize))]
 #[derive(Debug)]
 pub struct MinMaxSumCountAggregator {
-    inner: Mutex<Inner>,
+    buffer: DoubleBuffer<State>,
     kind: NumberKind,
 }
 
 impl Min for MinMaxSumCountAggregator {
     fn min(&self) -> Result<Number> {
-        self.inner.lock().map_err(From::from).map(|inner| {
-            inner
-                .state
-                .as_ref()
-                .map_or(0u64.into(), |state| state.min.load())
+        let state = self.buffer.hot();
+        Ok(if state.is_empty() {
+            0u64.into()
+        } else {
+            state.min.load()
         })
     }
 }
 
 impl Max for MinMaxSumCountAggregator {
     fn max(&self) -> Result<Number> {
-        self.inner.lock().map_err(From::from).map(|inner| {
-            inner
-                .state
-                .as_ref()
-                .map_or(0u64.into(), |state| state.max.load())
+        let state = self.buffer.hot();
+        Ok(if state.is_empty() {
+            0u64.into()
+        } else {
+            state.max.load()
         })
     }
 }
 
 impl Sum for MinMaxSumCountAggregator {
     fn sum(&self) -> Result<Number> {
-        self.inner.lock().map_err(From::from).map(|inner| {
-            inner
-                .state
-                .as_ref()
-                .map_or(0u64.into(), |state| state.sum.load())
-        })
+        Ok(self.buffer.hot().sum.load())
     }
 }
 
 impl Count for MinMaxSumCountAggregator {
     fn count(&self) -> Result<u64> {
-        self.inner
-            .lock()
-            .map_err(From::from)
-            .map(|inner| inner.state.as_ref().map_or(0u64, |state| state.count))
+        Ok(self.buffer.hot().count.load().to_u64(&NumberKind::U64))
     }
 }
 
@@ -72,30 +62,14 @@ impl MinMaxSumCount for MinMaxSumCountAggregator {}
 
 impl Aggregator for MinMaxSumCountAggregator {
     fn update(&self, number: &Number, descriptor: &Descriptor) -> Result<()> {
-        self.inner
-            .lock()
-            .map(|mut inner| {
-                if let Some(state) = &mut inner.state {
-                    let kind = descriptor.number_kind();
-
-                    state.count = state.count.saturating_add(1);
-                    state.sum.fetch_add(kind, number);
-                    if number.partial_cmp(kind, &state.min.load()) == Some(Ordering::Less) {
-                        state.min = number.to_atomic();
-                    }
-                    if number.partial_cmp(kind, &state.max.load()) == Some(Ordering::Greater) {
-                        state.max = number.to_atomic();
-                    }
-                } else {
-                    inner.state = Some(State {
-                        count: 1,
-                        sum: number.to_atomic(),
-                        min: number.to_atomic(),
-                        max: number.to_atomic(),
-                    })
-                }
-            })
-            .map_err(From::from)
+        let kind = descriptor.number_kind();
+        self.buffer.update(|state| {
+            state.min.fetch_min(kind, number);
+            state.max.fetch_max(kind, number);
+            state.sum.fetch_add(kind, number);
+            state.count.fetch_add(&NumberKind::U64, &1u64.into());
+        });
+        Ok(())
     }
 
     fn synchronized_move(
@@ -104,10 +78,9 @@ impl Aggregator for MinMaxSumCountAggregator {
         _descriptor: &Descriptor,
     ) -> Result<()> {
         if let Some(other) = other.as_any().downcast_ref::<Self>() {
-            self.inner.lock().map_err(From::from).and_then(|mut inner| {
-                other.inner.lock().map_err(From::from).map(|mut oi| {
-                    oi.state = inner.state.take();
-                })
+            self.buffer.swap(|state| {
+                other.buffer.hot().store(state);
+                state.reset(&self.kind);
             })
         } else {
             Err(MetricsError::InconsistentAggregator(format!(
@@ -119,33 +92,17 @@ impl Aggregator for MinMaxSumCountAggregator {
 
     fn merge(&self, aggregator: &(dyn Aggregator + Send + Sync), desc: &Descriptor) -> Result<()> {
         if let Some(other) = aggregator.as_any().downcast_ref::<Self>() {
-            self.inner.lock().map_err(From::from).and_then(|mut inner| {
-                other.inner.lock().map_err(From::from).map(|oi| {
-                    match (inner.state.as_mut(), oi.state.as_ref()) {
-                        (None, Some(other_checkpoint)) => {
-                            inner.state = Some(other_checkpoint.clone());
-                        }
-                        (Some(_), None) | (None, None) => (),
-                        (Some(state), Some(other)) => {
-                            state.count = state.count.saturating_add(other.count);
-                            state.sum.fetch_add(desc.number_kind(), &other.sum.load());
-
-                            let other_min = other.min.load();
-                            let other_max = other.max.load();
-                            if state.min.load().partial_cmp(desc.number_kind(), &other_min)
-                                == Some(Ordering::Greater)
-                            {
-                                state.min.store(&other_min);
-                            }
-                            if state.max.load().partial_cmp(desc.number_kind(), &other_max)
-                                == Some(Ordering::Less)
-                            {
-                                state.max.store(&other_max);
-                            }
-                        }
-                    }
-                })
-            })
+            let other = other.buffer.hot();
+            if !other.is_empty() {
+                let kind = desc.number_kind();
+                self.buffer.update(|state| {
+                    state.min.fetch_min(kind, &other.min.load());
+                    state.max.fetch_max(kind, &other.max.load());
+                    state.sum.fetch_add(kind, &other.sum.load());
+                    state.count.fetch_add(&NumberKind::U64, &other.count.load());
+                });
+            }
+            Ok(())
         } else {
             Err(MetricsError::InconsistentAggregator(format!(
                 "Expected {:?}, got: {:?}",
@@ -159,10 +116,47 @@ impl Aggregator for MinMaxSumCountAggregator {
     }
 }
 
-#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serialize", derive(Deserialize, Serialize))]
+#[derive(Debug)]
 struct State {
-    count: u64,
+    count: AtomicNumber,
     sum: AtomicNumber,
     min: AtomicNumber,
     max: AtomicNumber,
 }
+
+impl State {
+    fn empty(kind: &NumberKind) -> Self {
+        let state = State {
+            count: AtomicNumber::default(),
+            sum: AtomicNumber::default(),
+            min: AtomicNumber::default(),
+            max: AtomicNumber::default(),
+        };
+        state.reset(kind);
+        state
+    }
+
+    fn is_empty(&self) -> bool {
+        self.count.load().to_u64(&NumberKind::U64) == 0
+    }
+
+    /// Empty the state, with bounds that any number replaces.
+    fn reset(&self, kind: &NumberKind) {
+        let (min, max) = match kind {
+            NumberKind::F64 => (f64::INFINITY.into(), f64::NEG_INFINITY.into()),
+            _ => (kind.max(), kind.min()),
+        };
+        self.count.store(&NumberKind::U64.zero());
+        self.sum.store(&kind.zero());
+        self.min.store(&min);
+        self.max.store(&max);
+    }
+
+    fn store(&self, other: &State) {
+        self.count.store(&other.count.load());
+        self.sum.store(&other.sum.load());
+        self.min.store(&other.min.load());
+        self.max.store(&other.max.load());
+    }
+}
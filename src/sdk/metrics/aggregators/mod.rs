@@ -1,8 +1,11 @@
 //! Metric Aggregators
 use crate::metrics::{Descriptor, InstrumentKind, MetricsError, Number, NumberKind, Result};
+use crate::sdk::export::metrics::Exemplar;
 
 mod array;
 mod ddsketch;
+mod double_buffer;
+mod exponential_histogram;
 mod histogram;
 mod last_value;
 mod min_max_sum_count;
@@ -10,6 +13,9 @@ mod sum;
 
 pub use array::{array, ArrayAggregator};
 pub use ddsketch::{ddsketch, DDSKetchAggregator, DDSketchConfig};
+pub use exponential_histogram::{
+    exponential_histogram, ExponentialHistogramAggregator, DEFAULT_MAX_SIZE,
+};
 pub use histogram::{histogram, HistogramAggregator};
 pub use last_value::{last_value, LastValueAggregator};
 pub use min_max_sum_count::{min_max_sum_count, MinMaxSumCountAggregator};
@@ -36,3 +42,15 @@ pub fn range_test(number: &Number, descriptor: &Descriptor) -> Result<()> {
     };
     Ok(())
 }
+
+/// Keeps the most recent of the two exemplars.
+fn merge_exemplar(exemplar: &mut Option<Exemplar>, other: &Option<Exemplar>) {
+    if let Some(other) = other {
+        let newer = exemplar
+            .as_ref()
+            .map_or(true, |exemplar| exemplar.timestamp() < other.timestamp());
+        if newer {
+            *exemplar = Some(other.clone());
+        }
+    }
+}
@@ -1,7 +1,9 @@
 use crate::metrics::{AtomicNumber, Descriptor, MetricsError, Number, Result};
-use crate::sdk::export::metrics::{Aggregator, Subtractor, Sum};
+use crate::sdk::export::metrics::{Aggregator, Exemplar, Exemplars, Subtractor, Sum};
+#[cfg(feature = "serialize")]
+use serde::{Deserialize, Serialize};
 use std::any::Any;
-use std::sync::Arc;
+use std::sync::{Arc, Mutex};
 
 /// Create a new sum aggregator.
 pub fn sum() -> SumAggregator {
@@ -9,9 +11,12 @@ pub fn sum() -> SumAggregator {
 }
 
 /// An aggregator for counter events.
+#[cfg_attr(feature = "serialize", derive(Deserialize, Serialize))]
 #[derive(Debug, Default)]
 pub struct SumAggregator {
     value: AtomicNumber,
+    /// The most recent exemplar.
+    exemplar: Mutex<Option<Exemplar>>,
 }
 
 impl Sum for SumAggregator {
@@ -20,6 +25,15 @@ impl Sum for SumAggregator {
     }
 }
 
+impl Exemplars for SumAggregator {
+    fn exemplars(&self) -> Result<Vec<Exemplar>> {
+        self.exemplar
+            .lock()
+            .map_err(From::from)
+            .map(|exemplar| exemplar.iter().cloned().collect())
+    }
+}
+
 impl Subtractor for SumAggregator {
     fn subtract(
         &self,
@@ -34,7 +48,9 @@ impl Subtractor for SumAggregator {
             (Some(op), Some(res)) => {
                 res.value.store(&self.value.load());
                 res.value
-                    .fetch_add(descriptor.number_kind(), &op.value.load());
+                    .fetch_sub(descriptor.number_kind(), &op.value.load());
+                let exemplar = self.exemplar.lock()?.clone();
+                *res.exemplar.lock()? = exemplar;
                 Ok(())
             }
             _ => Err(MetricsError::InconsistentAggregator(format!(
@@ -50,6 +66,16 @@ impl Aggregator for SumAggregator {
         self.value.fetch_add(descriptor.number_kind(), number);
         Ok(())
     }
+    fn update_with_exemplar(
+        &self,
+        number: &Number,
+        descriptor: &Descriptor,
+        exemplar: Exemplar,
+    ) -> Result<()> {
+        self.update(number, descriptor)?;
+        *self.exemplar.lock()? = Some(exemplar);
+        Ok(())
+    }
     fn synchronized_move(
         &self,
         other: &Arc<dyn Aggregator + Send + Sync>,
@@ -59,6 +85,7 @@ impl Aggregator for SumAggregator {
             let kind = descriptor.number_kind();
             other.value.store(&self.value.load());
             self.value.store(&kind.zero());
+            *other.exemplar.lock()? = self.exemplar.lock()?.take();
             Ok(())
         } else {
             Err(MetricsError::InconsistentAggregator(format!(
@@ -70,7 +97,8 @@ impl Aggregator for SumAggregator {
     fn merge(&self, other: &(dyn Aggregator + Send + Sync), descriptor: &Descriptor) -> Result<()> {
         if let Some(other_sum) = other.as_any().downcast_ref::<SumAggregator>() {
             self.value
-                .fetch_add(descriptor.number_kind(), &other_sum.value.load())
+                .fetch_add(descriptor.number_kind(), &other_sum.value.load());
+            super::merge_exemplar(&mut *self.exemplar.lock()?, &*other_sum.exemplar.lock()?);
         }
 
         Ok(())
@@ -78,4 +106,8 @@ impl Aggregator for SumAggregator {
     fn as_any(&self) -> &dyn Any {
         self
     }
+
+    fn as_subtractor(&self) -> Option<&dyn Subtractor> {
+        Some(self)
+    }
 }
@@ -0,0 +1,107 @@
+use crate::metrics::registry;
+use crate::sdk::{
+    export::metrics::{AggregatorSelector, ExportKindSelector, Processor},
+    metrics::{
+        accumulator,
+        processors::{self, BasicProcessor},
+        views::View,
+        Accumulator, ErrorHandler, ObserverSpawner, Retention,
+    },
+    Resource,
+};
+use std::sync::Arc;
+use std::time::Duration;
+
+/// The options of the accumulator built by a controller or shared by several
+/// controllers.
+#[derive(Debug)]
+pub struct AccumulatorConfig {
+    pub(crate) aggregator_selector: Box<dyn AggregatorSelector + Send + Sync>,
+    pub(crate) resource: Option<Resource>,
+    pub(crate) views: Vec<View>,
+    pub(crate) cardinality_limit: Option<usize>,
+    pub(crate) observer_timeout: Option<Duration>,
+    pub(crate) observer_spawner: Option<ObserverSpawner>,
+    pub(crate) error_handler: Option<ErrorHandler>,
+    pub(crate) retention: Option<Retention>,
+    /// Whether invalid or duplicate instruments fail to be created.
+    pub(crate) strict: bool,
+}
+
+impl AccumulatorConfig {
+    pub(crate) fn new(aggregator_selector: Box<dyn AggregatorSelector + Send + Sync>) -> Self {
+        AccumulatorConfig {
+            aggregator_selector,
+            resource: None,
+            views: Vec::new(),
+            cardinality_limit: None,
+            observer_timeout: None,
+            observer_spawner: None,
+            error_handler: None,
+            retention: None,
+            strict: false,
+        }
+    }
+
+    /// Build the accumulator and the provider of its meters, handing the
+    /// measurements to the processor created from the aggregator selector,
+    /// views and retention.
+    pub(crate) fn build<P, F>(
+        self,
+        processor: F,
+    ) -> (Arc<P>, Accumulator, registry::RegistryMeterProvider)
+    where
+        P: Processor + Send + Sync + 'static,
+        F: FnOnce(Box<dyn AggregatorSelector + Send + Sync>, &[View], Option<Retention>) -> P,
+    {
+        let processor = Arc::new(processor(
+            self.aggregator_selector,
+            &self.views,
+            self.retention,
+        ));
+
+        let mut accumulator = accumulator(processor.clone())
+            .with_resource(self.resource.unwrap_or_default())
+            .with_views(self.views);
+        if let Some(limit) = self.cardinality_limit {
+            accumulator = accumulator.with_cardinality_limit(limit);
+        }
+        if let Some(timeout) = self.observer_timeout {
+            accumulator = accumulator.with_observer_timeout(timeout);
+        }
+        if let Some(spawner) = self.observer_spawner {
+            accumulator = accumulator.with_spawner(spawner);
+        }
+        if let Some(handler) = self.error_handler {
+            accumulator = accumulator.with_handler(handler);
+        }
+        if let Some(retention) = self.retention {
+            accumulator = accumulator.with_retention(retention);
+        }
+        let accumulator = accumulator.build();
+        let provider = if self.strict {
+            registry::strict_meter_provider(Arc::new(accumulator.clone()))
+        } else {
+            registry::meter_provider(Arc::new(accumulator.clone()))
+        };
+
+        (processor, accumulator, provider)
+    }
+}
+
+/// The processor of a controller, exporting the views and remembering series
+/// as long as the accumulator retains them.
+pub(crate) fn processor(
+    aggregator_selector: Box<dyn AggregatorSelector + Send + Sync>,
+    export_selector: Box<dyn ExportKindSelector + Send + Sync>,
+    memory: bool,
+    views: &[View],
+    retention: Option<Retention>,
+) -> BasicProcessor {
+    let processor =
+        processors::basic(aggregator_selector, export_selector, memory).with_views(views.to_vec());
+    match retention {
+        Some(retention) => processor.with_retention(retention),
+        None => processor,
+    }
+}
@@ -1,6 +1,9 @@
 //! SDK Metrics Controllers
+mod config;
 mod pull;
 mod push;
+mod shared;
 
 pub use pull::{pull, PullController};
 pub use push::{push, PushController, PushControllerWorker};
+pub use shared::{shared, SharedAccumulator, SharedAccumulatorBuilder};
@@ -1,13 +1,11 @@
-use crate::metrics::{registry, Result};
+use super::config::{self, AccumulatorConfig};
+use super::shared::{Collector, SharedAccumulator};
+use crate::metrics::{registry, MetricsError, ObserverFuture, Result};
 use crate::sdk::{
     export::metrics::{
         AggregatorSelector, CheckpointSet, Checkpointer, ExportKindSelector, Record,
     },
-    metrics::{
-        accumulator,
-        processors::{self, BasicProcessor},
-        Accumulator,
-    },
+    metrics::{processors::BasicProcessor, views::View, ErrorHandler, ObserverSpawner, Retention},
     Resource,
 };
 use std::sync::Arc;
@@ -30,7 +28,7 @@ pub fn pull(
 /// option, which ensures that every `CheckpointSet` includes full state.
 #[derive(Debug)]
 pub struct PullController {
-    accumulator: Accumulator,
+    collector: Collector,
     processor: Arc<BasicProcessor>,
     provider: registry::RegistryMeterProvider,
     period: Duration,
@@ -43,6 +41,12 @@ impl PullController {
         self.provider.clone()
     }
 
+    /// The resource of the metrics collected by this controller, which is the
+    /// resource of the shared accumulator when reading one.
+    pub fn resource(&self) -> &Resource {
+        self.collector.resource()
+    }
+
     /// Collects all metrics if the last collected at time is past the current period
     pub fn collect(&mut self) -> Result<()> {
         if self
@@ -53,8 +57,8 @@ impl PullController {
             self.last_collect = SystemTime::now();
             self.processor.lock().and_then(|mut checkpointer| {
                 checkpointer.start_collection();
-                self.accumulator.0.collect(&mut checkpointer);
-                checkpointer.finish_collection()
+                let collected = self.collector.collect(&mut checkpointer);
+                checkpointer.finish_collection().and(collected)
             })
         } else {
             Ok(())
@@ -78,17 +82,14 @@ impl CheckpointSet for PullController {
 
 /// Configuration for a `PullController`.
 #[derive(Debug)]
-pub struct PullControllerBuilder {
-    /// The aggregator selector used by the controller
-    aggregator_selector: Box<dyn AggregatorSelector + Send + Sync>,
+pub struct PullControllerBuilder<A = AccumulatorConfig> {
+    /// The accumulator built for the controller, or the shared accumulator it
+    /// reads.
+    accumulator: A,
 
     /// The export kind selector used by this controller
     export_selector: Box<dyn ExportKindSelector + Send + Sync>,
 
-    /// Resource is the OpenTelemetry resource associated with all Meters created by
-    /// the controller.
-    resource: Option<Resource>,
-
     /// CachePeriod is the period which a recently-computed result will be returned
     /// without gathering metric data again.
     ///
@@ -103,63 +104,195 @@ pub struct PullControllerBuilder {
     memory: bool,
 }
 
+/// A builder collecting the given accumulator.
+pub(crate) fn builder<A>(
+    accumulator: A,
+    export_selector: Box<dyn ExportKindSelector + Send + Sync>,
+) -> PullControllerBuilder<A> {
+    PullControllerBuilder {
+        accumulator,
+        export_selector,
+        cache_period: None,
+        memory: true,
+    }
+}
+
+impl<A> PullControllerBuilder<A> {
+    /// Configure the cache period for this controller
+    pub fn with_cache_period(self, period: Duration) -> Self {
+        PullControllerBuilder {
+            cache_period: Some(period),
+            ..self
+        }
+    }
+
+    /// Sets the memory behavior of the controller's `Processor`.  If this is
+    /// `true`, the processor will report metric instruments and label sets that
+    /// were previously reported but not updated in the most recent interval.
+    pub fn with_memory(self, memory: bool) -> Self {
+        PullControllerBuilder { memory, ..self }
+    }
+
+    fn controller(
+        period: Option<Duration>,
+        processor: Arc<BasicProcessor>,
+        collector: Collector,
+        provider: registry::RegistryMeterProvider,
+    ) -> PullController {
+        PullController {
+            collector,
+            processor,
+            provider,
+            period: period.unwrap_or(DEFAULT_CACHE_DURATION),
+            last_collect: SystemTime::now(),
+        }
+    }
+}
+
 impl PullControllerBuilder {
     /// Configure the sectors for this controller
     pub fn with_selectors(
         aggregator_selector: Box<dyn AggregatorSelector + Send + Sync>,
         export_selector: Box<dyn ExportKindSelector + Send + Sync>,
     ) -> Self {
-        PullControllerBuilder {
-            aggregator_selector,
-            export_selector,
-            resource: None,
-            cache_period: None,
-            memory: true,
-        }
+        builder(AccumulatorConfig::new(aggregator_selector), export_selector)
     }
 
     /// Configure the resource for this controller
     pub fn with_resource(self, resource: Resource) -> Self {
         PullControllerBuilder {
-            resource: Some(resource),
+            accumulator: AccumulatorConfig {
+                resource: Some(resource),
+                ..self.accumulator
+            },
             ..self
         }
     }
 
-    /// Configure the cache period for this controller
-    pub fn with_cache_period(self, period: Duration) -> Self {
+    /// Configure the views applied by the controller's `Processor`, and the
+    /// baggage keys labeling the measurements of the instruments they match.
+    pub fn with_views(self, views: Vec<View>) -> Self {
         PullControllerBuilder {
-            cache_period: Some(period),
+            accumulator: AccumulatorConfig {
+                views,
+                ..self.accumulator
+            },
             ..self
         }
     }
 
-    /// Sets the memory behavior of the controller's `Processor`.  If this is
-    /// `true`, the processor will report metric instruments and label sets that
-    /// were previously reported but not updated in the most recent interval.
-    pub fn with_memory(self, memory: bool) -> Self {
-        PullControllerBuilder { memory, ..self }
+    /// Configure the cardinality limit of the accumulator, see
+    /// `AccumulatorBuilder::with_cardinality_limit`.
+    pub fn with_cardinality_limit(self, limit: usize) -> Self {
+        PullControllerBuilder {
+            accumulator: AccumulatorConfig {
+                cardinality_limit: Some(limit),
+                ..self.accumulator
+            },
+            ..self
+        }
+    }
+
+    /// Configure the observer timeout of the accumulator, see
+    /// `AccumulatorBuilder::with_observer_timeout`.
+    pub fn with_observer_timeout(self, timeout: Duration) -> Self {
+        PullControllerBuilder {
+            accumulator: AccumulatorConfig {
+                observer_timeout: Some(timeout),
+                ..self.accumulator
+            },
+            ..self
+        }
+    }
+
+    /// Configure the observer spawner of the accumulator, see
+    /// `AccumulatorBuilder::with_observer_spawner`.
+    pub fn with_observer_spawner<F>(self, spawn: F) -> Self
+    where
+        F: Fn(ObserverFuture) + Send + Sync + 'static,
+    {
+        PullControllerBuilder {
+            accumulator: AccumulatorConfig {
+                observer_spawner: Some(ObserverSpawner::new(spawn)),
+                ..self.accumulator
+            },
+            ..self
+        }
+    }
+
+    /// Configure the error handler of the accumulator, see
+    /// `AccumulatorBuilder::with_error_handler`.
+    pub fn with_error_handler<F>(self, handle: F) -> Self
+    where
+        F: Fn(MetricsError) + Send + Sync + 'static,
+    {
+        PullControllerBuilder {
+            accumulator: AccumulatorConfig {
+                error_handler: Some(ErrorHandler::new(handle)),
+                ..self.accumulator
+            },
+            ..self
+        }
+    }
+
+    /// Configure how long the series of synchronous instruments without
+    /// updates are kept by the accumulator and remembered by the processor.
+    pub fn with_retention(self, retention: Retention) -> Self {
+        PullControllerBuilder {
+            accumulator: AccumulatorConfig {
+                retention: Some(retention),
+                ..self.accumulator
+            },
+            ..self
+        }
+    }
+
+    /// Create the meters of the controller with a
+    /// `registry::strict_meter_provider` instead of a `registry::meter_provider`.
+    pub fn with_strict_validation(self, strict: bool) -> Self {
+        PullControllerBuilder {
+            accumulator: AccumulatorConfig {
+                strict,
+                ..self.accumulator
+            },
+            ..self
+        }
     }
 
     /// Build a new `PullController` from the current configuration.
     pub fn build(self) -> PullController {
-        let processor = Arc::new(processors::basic(
-            self.aggregator_selector,
-            self.export_selector,
-            self.memory,
-        ));
-
-        let accumulator = accumulator(processor.clone())
-            .with_resource(self.resource.unwrap_or_default())
-            .build();
-        let provider = registry::meter_provider(Arc::new(accumulator.clone()));
+        let export_selector = self.export_selector;
+        let memory = self.memory;
+        let (processor, accumulator, provider) =
+            self.accumulator
+                .build(|aggregator_selector, views, retention| {
+                    config::processor(
+                        aggregator_selector,
+                        export_selector,
+                        memory,
+                        views,
+                        retention,
+                    )
+                });
 
-        PullController {
-            accumulator,
+        Self::controller(
+            self.cache_period,
             processor,
+            Collector::Accumulator(accumulator),
             provider,
-            period: self.cache_period.unwrap_or(DEFAULT_CACHE_DURATION),
-            last_collect: SystemTime::now(),
-        }
+        )
+    }
+}
+
+impl PullControllerBuilder<SharedAccumulator> {
+    /// Build a new `PullController` reading the shared accumulator.
+    pub fn build(self) -> PullController {
+        let (processor, collector) = self.accumulator.reader(self.export_selector, self.memory);
+        Self::controller(
+            self.cache_period,
+            processor,
+            collector,
+            self.accumulator.provider(),
+        )
     }
 }
@@ -1,17 +1,18 @@
+use super::config::{self, AccumulatorConfig};
+use super::shared::{Collector, SharedAccumulator};
 use crate::global;
-use crate::metrics::registry;
+use crate::metrics::{registry, MetricsError, ObserverFuture, Result};
 use crate::sdk::{
     export::metrics::{AggregatorSelector, Checkpointer, ExportKindSelector, Exporter},
-    metrics::{
-        self,
-        processors::{self, BasicProcessor},
-        Accumulator,
-    },
+    metrics::{processors::BasicProcessor, views::View, ErrorHandler, ObserverSpawner, Retention},
     Resource,
 };
 use futures::{channel::mpsc, task, Future, Stream, StreamExt};
+use std::fmt;
 use std::pin::Pin;
-use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{mpsc as sync_mpsc, Arc, Condvar, Mutex, Weak};
+use std::thread;
 use std::time;
 
 lazy_static::lazy_static! {
@@ -32,14 +33,34 @@ where
     E: Exporter + Send + Sync + 'static,
     SP: Fn(PushControllerWorker) -> SO,
     I: Fn(time::Duration) -> IO,
+{
+    builder(
+        AccumulatorConfig::new(Box::new(aggregator_selector)),
+        export_selector,
+        exporter,
+        spawn,
+        interval,
+    )
+}
+
+/// A builder pushing the collections of the given accumulator.
+pub(crate) fn builder<A, ES, E, SP, I>(
+    accumulator: A,
+    export_selector: ES,
+    exporter: E,
+    spawn: SP,
+    interval: I,
+) -> PushControllerBuilder<SP, I, A>
+where
+    ES: ExportKindSelector + Send + Sync + 'static,
+    E: Exporter + Send + Sync + 'static,
 {
     PushControllerBuilder {
-        aggregator_selector: Box::new(aggregator_selector),
+        accumulator,
         export_selector: Box::new(export_selector),
         exporter: Box::new(exporter),
         spawn,
         interval,
-        resource: None,
         stateful: None,
         period: None,
         timeout: None,
@@ -51,6 +72,7 @@ where
 pub struct PushController {
     message_sender: Mutex<mpsc::Sender<PushMessage>>,
     provider: registry::RegistryMeterProvider,
+    pipeline: Pipeline,
 }
 
 #[derive(Debug)]
@@ -59,27 +81,246 @@ enum PushMessage {
     Shutdown,
 }
 
+/// The collection and export shared by the worker and the controller.
+///
+/// Exports run one at a time on a dedicated thread, so a slow exporter neither
+/// blocks the executor running the worker nor piles up threads. An export
+/// exceeding the timeout is abandoned along with its thread, the next export
+/// runs on a fresh thread once the abandoned one released the processor.
+#[derive(Clone)]
+struct Pipeline {
+    export: Arc<Export>,
+    in_flight: Arc<InFlight>,
+    timeout: time::Duration,
+    is_shutdown: Arc<AtomicBool>,
+}
+
+/// Receives the result of a requested export.
+type Reply = sync_mpsc::Sender<Result<()>>;
+
+/// The metrics collected and exported by the export threads.
+struct Export {
+    collector: Collector,
+    processor: Arc<BasicProcessor>,
+    exporter: Arc<dyn Exporter + Send + Sync>,
+}
+
+/// The export in flight, if any.
+struct InFlight {
+    state: Mutex<InFlightState>,
+    changed: Condvar,
+}
+
+struct InFlightState {
+    /// Incremented when the export in flight is abandoned.
+    generation: u64,
+    /// Requests exports from the thread of the current generation.
+    requests: sync_mpsc::Sender<Option<Reply>>,
+    /// The start of the export in flight, if any.
+    started: Option<time::Instant>,
+    /// Whether the export in flight holds the processor, rather than waiting
+    /// for an abandoned export to release it.
+    exporting: bool,
+}
+
+impl fmt::Debug for Pipeline {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Pipeline")
+            .field("exporter", &self.export.exporter)
+            .field("timeout", &self.timeout)
+            .finish()
+    }
+}
+
+impl Export {
+    fn run(&self, in_flight: &Weak<InFlight>, generation: u64) -> Result<()> {
+        let mut checkpointer = self.processor.lock()?;
+        if let Some(in_flight) = in_flight.upgrade() {
+            in_flight.update(generation, |state| state.exporting = true);
+        }
+        checkpointer.start_collection();
+        let collected = self.collector.collect(&mut checkpointer);
+        checkpointer.finish_collection().and(collected)?;
+        self.exporter.export(checkpointer.checkpoint_set())
+    }
+
+    /// Start a thread running the requested exports of the given generation,
+    /// until its export in flight is abandoned.
+    fn start(
+        self: Arc<Self>,
+        requests: sync_mpsc::Receiver<Option<Reply>>,
+        in_flight: Weak<InFlight>,
+        generation: u64,
+    ) {
+        thread::spawn(move || {
+            for reply in requests {
+                let result = self.run(&in_flight, generation);
+                let current = in_flight.upgrade().map_or(false, |in_flight| {
+                    in_flight.update(generation, |state| {
+                        state.started = None;
+                        state.exporting = false;
+                    })
+                });
+
+                // Results nobody waits for anymore go to the error handler.
+                let result = match reply {
+                    Some(reply) => match reply.send(result) {
+                        Err(sync_mpsc::SendError(result)) => result,
+                        Ok(()) => Ok(()),
+                    },
+                    None => result,
+                };
+                if let Err(err) = result {
+                    global::handle_error(err)
+                }
+                if !current {
+                    break;
+                }
+            }
+        });
+    }
+}
+
+impl InFlight {
+    /// Update the state if the given generation is still current, returning
+    /// whether it is.
+    fn update<F: FnOnce(&mut InFlightState)>(&self, generation: u64, f: F) -> bool {
+        match self.state.lock() {
+            Ok(mut state) if state.generation == generation => {
+                f(&mut state);
+                self.changed.notify_all();
+                true
+            }
+            _ => false,
+        }
+    }
+}
+
+impl Pipeline {
+    fn new(
+        collector: Collector,
+        processor: Arc<BasicProcessor>,
+        exporter: Arc<dyn Exporter + Send + Sync>,
+        timeout: time::Duration,
+    ) -> Self {
+        let export = Arc::new(Export {
+            collector,
+            processor,
+            exporter,
+        });
+        let (requests, receiver) = sync_mpsc::channel();
+        let in_flight = Arc::new(InFlight {
+            state: Mutex::new(InFlightState {
+                generation: 0,
+                requests,
+                started: None,
+                exporting: false,
+            }),
+            changed: Condvar::new(),
+        });
+        export
+            .clone()
+            .start(receiver, Arc::downgrade(&in_flight), 0);
+
+        Pipeline {
+            export,
+            in_flight,
+            timeout,
+            is_shutdown: Arc::new(AtomicBool::new(false)),
+        }
+    }
+
+    /// Start a collection and export without waiting for it, unless an export
+    /// is already in flight.
+    ///
+    /// Fails if the export in flight has exceeded the configured timeout, in
+    /// which case it is abandoned and a new export is started.
+    fn start_export(&self) -> Result<()> {
+        let mut state = self.in_flight.state.lock()?;
+        match state.started {
+            Some(at) if at.elapsed() < self.timeout => Ok(()),
+            Some(_) if !state.exporting => Err(MetricsError::ExportTimedOut(self.timeout)),
+            Some(_) => {
+                self.abandon(&mut state);
+                self.request(&mut state, None)?;
+                Err(MetricsError::ExportTimedOut(self.timeout))
+            }
+            None => self.request(&mut state, None),
+        }
+    }
+
+    /// Collect and export the metrics once the export in flight, if any,
+    /// completed or was abandoned, failing if both take longer than the
+    /// configured timeout.
+    ///
+    /// The results of an export exceeding the timeout are reported to the
+    /// global error handler.
+    fn collect_and_export(&self) -> Result<()> {
+        let deadline = time::Instant::now() + self.timeout;
+        let (reply, result) = sync_mpsc::channel();
+        {
+            let mut state = self.in_flight.state.lock()?;
+            while let Some(started) = state.started {
+                let now = time::Instant::now();
+                let expired = started + self.timeout;
+                if now >= expired && state.exporting {
+                    self.abandon(&mut state);
+                    break;
+                }
+                if now >= deadline {
+                    return Err(MetricsError::ExportTimedOut(self.timeout));
+                }
+                let wake = if now < expired { expired } else { deadline };
+                state = self.in_flight.changed.wait_timeout(state, wake - now)?.0;
+            }
+            self.request(&mut state, Some(reply))?;
+        }
+
+        match result.recv_timeout(deadline.saturating_duration_since(time::Instant::now())) {
+            Ok(result) => result,
+            Err(sync_mpsc::RecvTimeoutError::Timeout) => {
+                Err(MetricsError::ExportTimedOut(self.timeout))
+            }
+            Err(sync_mpsc::RecvTimeoutError::Disconnected) => {
+                Err(MetricsError::Other("metrics export panicked".to_string()))
+            }
+        }
+    }
+
+    /// Abandon the export in flight, running the next exports on a fresh
+    /// thread.
+    fn abandon(&self, state: &mut InFlightState) {
+        let (requests, receiver) = sync_mpsc::channel();
+        state.generation = state.generation.wrapping_add(1);
+        state.requests = requests;
+        self.export
+            .clone()
+            .start(receiver, Arc::downgrade(&self.in_flight), state.generation);
+        state.started = None;
+        state.exporting = false;
+    }
+
+    fn request(&self, state: &mut InFlightState, reply: Option<Reply>) -> Result<()> {
+        state
+            .requests
+            .send(reply)
+            .map_err(|_| MetricsError::Other("metrics export panicked".to_string()))?;
+        state.started = Some(time::Instant::now());
+        Ok(())
+    }
+}
+
 /// The future which executes push controller work periodically. Can be run on a
 /// passed in executor.
 #[allow(missing_debug_implementations)]
 pub struct PushControllerWorker {
     messages: Pin<Box<dyn Stream<Item = PushMessage> + Send>>,
-    accumulator: Accumulator,
-    processor: Arc<BasicProcessor>,
-    exporter: Box<dyn Exporter + Send + Sync>,
-    _timeout: time::Duration,
+    pipeline: Pipeline,
 }
 
 impl PushControllerWorker {
     fn on_tick(&mut self) {
-        // TODO handle timeout
-
-        if let Err(err) = self.processor.lock().and_then(|mut checkpointer| {
-            checkpointer.start_collection();
-            self.accumulator.0.collect(&mut checkpointer);
-            checkpointer.finish_collection()?;
-            self.exporter.export(checkpointer.checkpoint_set())
-        }) {
+        if let Err(err) = self.pipeline.start_export() {
             global::handle_error(err)
         }
     }
@@ -103,8 +344,17 @@ impl Future for PushControllerWorker {
 
 impl Drop for PushControllerWorker {
     fn drop(&mut self) {
-        // Try to push data one last time
-        self.on_tick()
+        // Try to push data one last time, unless the controller already did
+        // on shutdown. The export is awaited on a thread of its own, so the
+        // executor dropping the worker is not blocked.
+        if !self.pipeline.is_shutdown.load(Ordering::SeqCst) {
+            let pipeline = self.pipeline.clone();
+            thread::spawn(move || {
+                if let Err(err) = pipeline.collect_and_export() {
+                    global::handle_error(err)
+                }
+            });
+        }
     }
 }
 
@@ -113,6 +363,29 @@ impl PushController {
     pub fn provider(&self) -> registry::RegistryMeterProvider {
         self.provider.clone()
     }
+
+    /// Collect and export the metrics recorded since the last export,
+    /// independently of the push period.
+    ///
+    /// Blocks until the export finished or the configured timeout elapsed.
+    pub fn force_flush(&self) -> Result<()> {
+        self.pipeline.collect_and_export()
+    }
+
+    /// Stop the periodic push and export the remaining metrics one last time.
+    ///
+    /// Unlike dropping the controller, this waits for the final export and
+    /// returns its result. Calling it more than once has no effect.
+    pub fn shutdown(&self) -> Result<()> {
+        if self.pipeline.is_shutdown.swap(true, Ordering::SeqCst) {
+            return Ok(());
+        }
+
+        if let Ok(mut sender) = self.message_sender.lock() {
+            let _ = sender.try_send(PushMessage::Shutdown);
+        }
+        self.pipeline.collect_and_export()
+    }
 }
 
 impl Drop for PushController {
@@ -125,19 +398,18 @@ impl Drop for PushController {
 
 /// Configuration for building a new `PushController`.
 #[derive(Debug)]
-pub struct PushControllerBuilder<S, I> {
-    aggregator_selector: Box<dyn AggregatorSelector + Send + Sync>,
+pub struct PushControllerBuilder<S, I, A = AccumulatorConfig> {
+    accumulator: A,
     export_selector: Box<dyn ExportKindSelector + Send + Sync>,
     exporter: Box<dyn Exporter + Send + Sync>,
     spawn: S,
     interval: I,
-    resource: Option<Resource>,
     stateful: Option<bool>,
     period: Option<time::Duration>,
     timeout: Option<time::Duration>,
 }
 
-impl<S, SO, I, IS, ISI> PushControllerBuilder<S, I>
+impl<S, SO, I, IS, ISI, A> PushControllerBuilder<S, I, A>
 where
     S: Fn(PushControllerWorker) -> SO,
     I: Fn(time::Duration) -> IS,
@@ -159,41 +431,377 @@ where
         }
     }
 
-    /// Configure the resource used by this controller
-    pub fn with_resource(self, resource: Resource) -> Self {
+    /// Configure the maximum duration of each export, defaults to the push
+    /// period.
+    pub fn with_timeout(self, timeout: time::Duration) -> Self {
         PushControllerBuilder {
-            resource: Some(resource),
+            timeout: Some(timeout),
             ..self
         }
     }
 
-    /// Build a new `PushController` with this configuration.
-    pub fn build(self) -> PushController {
-        let processor = processors::basic(self.aggregator_selector, self.export_selector, false);
-        let processor = Arc::new(processor);
-        let mut accumulator = metrics::accumulator(processor.clone());
-
-        if let Some(resource) = self.resource {
-            accumulator = accumulator.with_resource(resource);
-        }
-        let accumulator = accumulator.build();
-        let provider = registry::meter_provider(Arc::new(accumulator.clone()));
-
+    /// Spawn the worker pushing the collections of the collector.
+    fn start(
+        spawn: S,
+        interval: I,
+        period: Option<time::Duration>,
+        timeout: Option<time::Duration>,
+        exporter: Box<dyn Exporter + Send + Sync>,
+        (processor, collector, provider): (
+            Arc<BasicProcessor>,
+            Collector,
+            registry::RegistryMeterProvider,
+        ),
+    ) -> PushController {
         let (message_sender, message_receiver) = mpsc::channel(256);
-        let ticker =
-            (self.interval)(self.period.unwrap_or(*DEFAULT_PUSH_PERIOD)).map(|_| PushMessage::Tick);
+        let ticker = (interval)(period.unwrap_or(*DEFAULT_PUSH_PERIOD)).map(|_| PushMessage::Tick);
 
-        (self.spawn)(PushControllerWorker {
-            messages: Box::pin(futures::stream::select(message_receiver, ticker)),
-            accumulator,
+        let pipeline = Pipeline::new(
+            collector,
             processor,
-            exporter: self.exporter,
-            _timeout: self.timeout.unwrap_or(*DEFAULT_PUSH_PERIOD),
+            Arc::from(exporter),
+            timeout.unwrap_or(*DEFAULT_PUSH_PERIOD),
+        );
+
+        (spawn)(PushControllerWorker {
+            messages: Box::pin(futures::stream::select(message_receiver, ticker)),
+            pipeline: pipeline.clone(),
         });
 
         PushController {
             message_sender: Mutex::new(message_sender),
             provider,
+            pipeline,
         }
     }
 }
+
+impl<S, SO, I, IS, ISI> PushControllerBuilder<S, I>
+where
+    S: Fn(PushControllerWorker) -> SO,
+    I: Fn(time::Duration) -> IS,
+    IS: Stream<Item = ISI> + Send + 'static,
+{
+    /// Configure the resource used by this controller
+    pub fn with_resource(self, resource: Resource) -> Self {
+        PushControllerBuilder {
+            accumulator: AccumulatorConfig {
+                resource: Some(resource),
+                ..self.accumulator
+            },
+            ..self
+        }
+    }
+
+    /// Configure the views applied by this controller's `Processor`, and the
+    /// baggage keys labeling the measurements of the instruments they match.
+    pub fn with_views(self, views: Vec<View>) -> Self {
+        PushControllerBuilder {
+            accumulator: AccumulatorConfig {
+                views,
+                ..self.accumulator
+            },
+            ..self
+        }
+    }
+
+    /// Configure the cardinality limit of the accumulator, see
+    /// `AccumulatorBuilder::with_cardinality_limit`.
+    pub fn with_cardinality_limit(self, limit: usize) -> Self {
+        PushControllerBuilder {
+            accumulator: AccumulatorConfig {
+                cardinality_limit: Some(limit),
+                ..self.accumulator
+            },
+            ..self
+        }
+    }
+
+    /// Configure the observer timeout of the accumulator, see
+    /// `AccumulatorBuilder::with_observer_timeout`.
+    pub fn with_observer_timeout(self, timeout: time::Duration) -> Self {
+        PushControllerBuilder {
+            accumulator: AccumulatorConfig {
+                observer_timeout: Some(timeout),
+                ..self.accumulator
+            },
+            ..self
+        }
+    }
+
+    /// Configure the observer spawner of the accumulator, see
+    /// `AccumulatorBuilder::with_observer_spawner`.
+    pub fn with_observer_spawner<F>(self, spawn: F) -> Self
+    where
+        F: Fn(ObserverFuture) + Send + Sync + 'static,
+    {
+        PushControllerBuilder {
+            accumulator: AccumulatorConfig {
+                observer_spawner: Some(ObserverSpawner::new(spawn)),
+                ..self.accumulator
+            },
+            ..self
+        }
+    }
+
+    /// Configure the error handler of the accumulator, see
+    /// `AccumulatorBuilder::with_error_handler`.
+    pub fn with_error_handler<F>(self, handle: F) -> Self
+    where
+        F: Fn(MetricsError) + Send + Sync + 'static,
+    {
+        PushControllerBuilder {
+            accumulator: AccumulatorConfig {
+                error_handler: Some(ErrorHandler::new(handle)),
+                ..self.accumulator
+            },
+            ..self
+        }
+    }
+
+    /// Configure how long the series of synchronous instruments without
+    /// updates are kept by the accumulator and remembered by the processor.
+    pub fn with_retention(self, retention: Retention) -> Self {
+        PushControllerBuilder {
+            accumulator: AccumulatorConfig {
+                retention: Some(retention),
+                ..self.accumulator
+            },
+            ..self
+        }
+    }
+
+    /// Create the meters of the controller with a
+    /// `registry::strict_meter_provider` instead of a `registry::meter_provider`.
+    pub fn with_strict_validation(self, strict: bool) -> Self {
+        PushControllerBuilder {
+            accumulator: AccumulatorConfig {
+                strict,
+                ..self.accumulator
+            },
+            ..self
+        }
+    }
+
+    /// Build a new `PushController` with this configuration.
+    pub fn build(self) -> PushController {
+        let export_selector = self.export_selector;
+        let (processor, accumulator, provider) =
+            self.accumulator
+                .build(|aggregator_selector, views, retention| {
+                    config::processor(
+                        aggregator_selector,
+                        export_selector,
+                        false,
+                        views,
+                        retention,
+                    )
+                });
+
+        Self::start(
+            self.spawn,
+            self.interval,
+            self.period,
+            self.timeout,
+            self.exporter,
+            (processor, Collector::Accumulator(accumulator), provider),
+        )
+    }
+}
+
+impl<S, SO, I, IS, ISI> PushControllerBuilder<S, I, SharedAccumulator>
+where
+    S: Fn(PushControllerWorker) -> SO,
+    I: Fn(time::Duration) -> IS,
+    IS: Stream<Item = ISI> + Send + 'static,
+{
+    /// Build a new `PushController` reading the shared accumulator.
+    pub fn build(self) -> PushController {
+        let (processor, collector) = self.accumulator.reader(self.export_selector, false);
+        let provider = self.accumulator.provider();
+
+        Self::start(
+            self.spawn,
+            self.interval,
+            self.period,
+            self.timeout,
+            self.exporter,
+            (processor, collector, provider),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::metrics::{Descriptor, MeterProvider};
+    use crate::sdk::{
+        export::metrics::{CheckpointSet, ExportKind, ExportKindSelector},
+        metrics::selectors::simple::Selector,
+    };
+    use crate::KeyValue;
+    use std::sync::atomic::AtomicUsize;
+
+    #[derive(Debug, Default)]
+    struct TestExporter {
+        exports: Arc<AtomicUsize>,
+        exported: Arc<Mutex<Vec<String>>>,
+        gate: Option<Gate>,
+    }
+
+    /// Signals the start of each export, which then waits to be released.
+    #[derive(Debug)]
+    struct Gate {
+        started: Mutex<sync_mpsc::Sender<()>>,
+        released: Mutex<sync_mpsc::Receiver<()>>,
+    }
+
+    fn gated(
+        exports: Arc<AtomicUsize>,
+    ) -> (TestExporter, sync_mpsc::Receiver<()>, sync_mpsc::Sender<()>) {
+        let (started, on_start) = sync_mpsc::channel();
+        let (release, released) = sync_mpsc::channel();
+        let exporter = TestExporter {
+            exports,
+            gate: Some(Gate {
+                started: Mutex::new(started),
+                released: Mutex::new(released),
+            }),
+            ..Default::default()
+        };
+        (exporter, on_start, release)
+    }
+
+    impl ExportKindSelector for TestExporter {
+        fn export_kind_for(&self, _descriptor: &Descriptor) -> ExportKind {
+            ExportKind::Delta
+        }
+    }
+
+    impl Exporter for TestExporter {
+        fn export(&self, checkpoint_set: &mut dyn CheckpointSet) -> Result<()> {
+            self.exports.fetch_add(1, Ordering::SeqCst);
+            if let Some(gate) = &self.gate {
+                let _ = gate.started.lock().unwrap().send(());
+                let _ = gate.released.lock().unwrap().recv();
+            }
+            checkpoint_set.try_for_each(&ExportKind::Delta, &mut |record| {
+                self.exported
+                    .lock()
+                    .unwrap()
+                    .push(record.descriptor().name().to_string());
+                Ok(())
+            })
+        }
+    }
+
+    fn controller(
+        exporter: TestExporter,
+        timeout: time::Duration,
+    ) -> (PushController, PushControllerWorker) {
+        let worker = Arc::new(Mutex::new(None));
+        let spawned = worker.clone();
+        let controller = push(
+            Selector::Exact,
+            ExportKind::Delta,
+            exporter,
+            // The worker is never polled, ticks are sent by the tests.
+            move |worker| *spawned.lock().unwrap() = Some(worker),
+            |_period| futures::stream::pending::<()>(),
+        )
+        .with_timeout(timeout)
+        .build();
+        let worker = worker.lock().unwrap().take().unwrap();
+        (controller, worker)
+    }
+
+    #[test]
+    fn force_flush_and_shutdown() {
+        let exported = Arc::new(Mutex::new(Vec::new()));
+        let (controller, worker) = controller(
+            TestExporter {
+                exported: exported.clone(),
+                ..Default::default()
+            },
+            time::Duration::from_secs(5),
+        );
+        let counter = controller.provider().meter("test").u64_counter("a").init();
+
+        counter.add(1, &[KeyValue::new("k", "v")]);
+        controller.force_flush().unwrap();
+        assert_eq!(*exported.lock().unwrap(), vec!["a".to_string()]);
+
+        counter.add(1, &[KeyValue::new("k", "v")]);
+        controller.shutdown().unwrap();
+        assert_eq!(exported.lock().unwrap().len(), 2);
+
+        // Further shutdowns and drops don't export again.
+        controller.shutdown().unwrap();
+        drop(controller);
+        drop(worker);
+        assert_eq!(exported.lock().unwrap().len(), 2);
+    }
+
+    #[test]
+    fn export_timeout() {
+        let (exporter, _on_start, _release) = gated(Arc::default());
+        let (controller, _worker) = controller(exporter, time::Duration::from_millis(50));
+
+        assert_eq!(
+            controller.force_flush(),
+            Err(MetricsError::ExportTimedOut(time::Duration::from_millis(
+                50
+            )))
+        );
+    }
+
+    #[test]
+    fn slow_exports_do_not_overlap() {
+        let exports = Arc::new(AtomicUsize::new(0));
+        let (exporter, on_start, release) = gated(exports.clone());
+        let (controller, mut worker) = controller(exporter, time::Duration::from_secs(60));
+
+        // Ticks while an export is in flight don't start another.
+        worker.on_tick();
+        on_start.recv().unwrap();
+        worker.on_tick();
+        assert_eq!(exports.load(Ordering::SeqCst), 1);
+
+        // Flushes wait for the export in flight.
+        release.send(()).unwrap();
+        release.send(()).unwrap();
+        controller.force_flush().unwrap();
+        assert_eq!(exports.load(Ordering::SeqCst), 2);
+    }
+
+    #[test]
+    fn stuck_exports_are_abandoned() {
+        let exports = Arc::new(AtomicUsize::new(0));
+        let (exporter, on_start, release) = gated(exports.clone());
+        let timeout = time::Duration::from_millis(100);
+        let (controller, _worker) = controller(exporter, timeout);
+
+        assert_eq!(
+            controller.force_flush(),
+            Err(MetricsError::ExportTimedOut(timeout))
+        );
+        on_start.recv().unwrap();
+
+        // The stuck export is abandoned, the next one waits for it to release
+        // the processor.
+        assert_eq!(
+            controller.force_flush(),
+            Err(MetricsError::ExportTimedOut(timeout))
+        );
+        assert_eq!(exports.load(Ordering::SeqCst), 1);
+        release.send(()).unwrap();
+        on_start.recv().unwrap();
+        assert_eq!(exports.load(Ordering::SeqCst), 2);
+
+        // Exports run again once the abandoned one returned.
+        release.send(()).unwrap();
+        release.send(()).unwrap();
+        controller.force_flush().unwrap();
+        assert_eq!(exports.load(Ordering::SeqCst), 3);
+    }
+}
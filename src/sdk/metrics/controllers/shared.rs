@@ -0,0 +1,485 @@
+//! Shared accumulator
+//!
+//! A shared accumulator records the measurements of all instruments once and
+//! fans them out to several controllers, each exporting with its own export
+//! kind, exporter and collection period.
+use super::config::{self, AccumulatorConfig};
+use super::pull::{self, PullControllerBuilder};
+use super::push::{self, PushControllerBuilder, PushControllerWorker};
+use crate::labels::{hash_labels, LabelSet};
+use crate::metrics::{registry, Descriptor, MetricsError, ObserverFuture, Result};
+use crate::sdk::{
+    export::metrics::{
+        self, Accumulation, Aggregator, AggregatorSelector, ExportKindSelector, Exporter,
+        LockedProcessor, Processor,
+    },
+    metrics::{
+        processors::{BasicLockedProcessor, BasicProcessor},
+        views::{View, ViewSelector},
+        Accumulator, ErrorHandler, ObserverSpawner, Retention,
+    },
+    Resource,
+};
+use fnv::FnvHasher;
+use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
+use std::sync::{Arc, Mutex, PoisonError};
+use std::time::Duration;
+
+/// Returns a builder for creating a `SharedAccumulator` with the given
+/// aggregator selector.
+pub fn shared(
+    aggregator_selector: Box<dyn AggregatorSelector + Send + Sync>,
+) -> SharedAccumulatorBuilder {
+    let aggregator_selector = SharedAggregatorSelector(Arc::from(aggregator_selector));
+    SharedAccumulatorBuilder {
+        accumulator: AccumulatorConfig::new(Box::new(aggregator_selector.clone())),
+        aggregator_selector,
+    }
+}
+
+/// An accumulator read by several pull and push controllers.
+///
+/// Instruments created with the shared provider are aggregated once. Every
+/// collection of one of the controllers also hands the collected aggregations
+/// to the other controllers, which merge them until their own collection, so
+/// each controller can collect and export at its own pace.
+///
+/// All controllers export the aggregators selected by the shared accumulator,
+/// create the controllers with `SharedAccumulator::pull` and
+/// `SharedAccumulator::push`:
+///
+/// ```
+/// use opentelemetry::sdk::{
+///     export::metrics::ExportKind,
+///     metrics::{controllers, selectors::simple::Selector},
+/// };
+///
+/// let shared = controllers::shared(Box::new(Selector::Exact)).build();
+///
+/// // Scraped cumulatively, e.g. by Prometheus.
+/// let pull = shared.pull(Box::new(ExportKind::Cumulative)).build();
+/// # drop(pull);
+/// ```
+#[derive(Clone, Debug)]
+pub struct SharedAccumulator {
+    accumulator: Accumulator,
+    provider: registry::RegistryMeterProvider,
+    fan_out: Arc<FanOut>,
+    aggregator_selector: SharedAggregatorSelector,
+    views: Vec<View>,
+    retention: Option<Retention>,
+}
+
+impl SharedAccumulator {
+    /// The meter provider of all instruments read by the controllers.
+    pub fn provider(&self) -> registry::RegistryMeterProvider {
+        self.provider.clone()
+    }
+
+    /// Returns a builder for creating a `PullController` reading this
+    /// accumulator.
+    pub fn pull(
+        &self,
+        export_selector: Box<dyn ExportKindSelector + Send + Sync>,
+    ) -> PullControllerBuilder<SharedAccumulator> {
+        pull::builder(self.clone(), export_selector)
+    }
+
+    /// Returns a builder for creating a `PushController` reading this
+    /// accumulator.
+    pub fn push<ES, E, SP, SO, I, IO>(
+        &self,
+        export_selector: ES,
+        exporter: E,
+        spawn: SP,
+        interval: I,
+    ) -> PushControllerBuilder<SP, I, SharedAccumulator>
+    where
+        ES: ExportKindSelector + Send + Sync + 'static,
+        E: Exporter + Send + Sync + 'static,
+        SP: Fn(PushControllerWorker) -> SO,
+        I: Fn(Duration) -> IO,
+    {
+        push::builder(self.clone(), export_selector, exporter, spawn, interval)
+    }
+
+    /// Register a new reader, returning its processor and collector.
+    pub(crate) fn reader(
+        &self,
+        export_selector: Box<dyn ExportKindSelector + Send + Sync>,
+        memory: bool,
+    ) -> (Arc<BasicProcessor>, Collector) {
+        let processor = config::processor(
+            Box::new(self.aggregator_selector.clone()),
+            export_selector,
+            memory,
+            &self.views,
+            self.retention,
+        );
+
+        // The pending aggregations remain usable if a reader panicked.
+        let mut readers = self
+            .fan_out
+            .readers
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+        let index = readers.len();
+        readers.push(Some(HashMap::new()));
+
+        let reader = Reader {
+            accumulator: self.accumulator.clone(),
+            fan_out: self.fan_out.clone(),
+            index,
+        };
+        (Arc::new(processor), Collector::Reader(Arc::new(reader)))
+    }
+}
+
+/// Configuration for a `SharedAccumulator`.
+#[derive(Debug)]
+pub struct SharedAccumulatorBuilder {
+    accumulator: AccumulatorConfig,
+    aggregator_selector: SharedAggregatorSelector,
+}
+
+impl SharedAccumulatorBuilder {
+    /// Configure the resource associated with all meters of the accumulator
+    pub fn with_resource(self, resource: Resource) -> Self {
+        SharedAccumulatorBuilder {
+            accumulator: AccumulatorConfig {
+                resource: Some(resource),
+                ..self.accumulator
+            },
+            ..self
+        }
+    }
+
+    /// Configure the views applied by all controllers.
+    pub fn with_views(self, views: Vec<View>) -> Self {
+        SharedAccumulatorBuilder {
+            accumulator: AccumulatorConfig {
+                views,
+                ..self.accumulator
+            },
+            ..self
+        }
+    }
+
+    /// Configure the cardinality limit of the shared accumulator, see
+    /// `AccumulatorBuilder::with_cardinality_limit`.
+    pub fn with_cardinality_limit(self, limit: usize) -> Self {
+        SharedAccumulatorBuilder {
+            accumulator: AccumulatorConfig {
+                cardinality_limit: Some(limit),
+                ..self.accumulator
+            },
+            ..self
+        }
+    }
+
+    /// Configure the observer timeout of the shared accumulator, see
+    /// `AccumulatorBuilder::with_observer_timeout`.
+    pub fn with_observer_timeout(self, timeout: Duration) -> Self {
+        SharedAccumulatorBuilder {
+            accumulator: AccumulatorConfig {
+                observer_timeout: Some(timeout),
+                ..self.accumulator
+            },
+            ..self
+        }
+    }
+
+    /// Configure the observer spawner of the shared accumulator, see
+    /// `AccumulatorBuilder::with_observer_spawner`.
+    pub fn with_observer_spawner<F>(self, spawn: F) -> Self
+    where
+        F: Fn(ObserverFuture) + Send + Sync + 'static,
+    {
+        SharedAccumulatorBuilder {
+            accumulator: AccumulatorConfig {
+                observer_spawner: Some(ObserverSpawner::new(spawn)),
+                ..self.accumulator
+            },
+            ..self
+        }
+    }
+
+    /// Configure the error handler of the shared accumulator, see
+    /// `AccumulatorBuilder::with_error_handler`.
+    pub fn with_error_handler<F>(self, handle: F) -> Self
+    where
+        F: Fn(MetricsError) + Send + Sync + 'static,
+    {
+        SharedAccumulatorBuilder {
+            accumulator: AccumulatorConfig {
+                error_handler: Some(ErrorHandler::new(handle)),
+                ..self.accumulator
+            },
+            ..self
+        }
+    }
+
+    /// Configure how long the series of synchronous instruments without
+    /// updates are kept by the accumulator and remembered by the processors of
+    /// all controllers.
+    pub fn with_retention(self, retention: Retention) -> Self {
+        SharedAccumulatorBuilder {
+            accumulator: AccumulatorConfig {
+                retention: Some(retention),
+                ..self.accumulator
+            },
+            ..self
+        }
+    }
+
+    /// Create the shared meters with a `registry::strict_meter_provider`
+    /// instead of a `registry::meter_provider`.
+    pub fn with_strict_validation(self, strict: bool) -> Self {
+        SharedAccumulatorBuilder {
+            accumulator: AccumulatorConfig {
+                strict,
+                ..self.accumulator
+            },
+            ..self
+        }
+    }
+
+    /// Build a new `SharedAccumulator` from the current configuration.
+    pub fn build(self) -> SharedAccumulator {
+        let views = self.accumulator.views.clone();
+        let retention = self.accumulator.retention;
+        let (fan_out, accumulator, provider) =
+            self.accumulator
+                .build(|aggregator_selector, views, _retention| FanOut {
+                    aggregator_selector: ViewSelector::new(views.to_vec(), aggregator_selector),
+                    readers: Mutex::new(Vec::new()),
+                });
+
+        SharedAccumulator {
+            accumulator,
+            provider,
+            fan_out,
+            aggregator_selector: self.aggregator_selector,
+            views,
+            retention,
+        }
+    }
+}
+
+/// The aggregator selector of the accumulator, shared with the processors of
+/// all readers so their aggregators can be merged.
+#[derive(Clone, Debug)]
+struct SharedAggregatorSelector(Arc<dyn AggregatorSelector + Send + Sync>);
+
+impl AggregatorSelector for SharedAggregatorSelector {
+    fn aggregator_for(&self, descriptor: &Descriptor) -> Option<Arc<dyn Aggregator + Send + Sync>> {
+        self.0.aggregator_for(descriptor)
+    }
+}
+
+/// The aggregations collected for each reader since its last collection.
+#[derive(Debug)]
+struct FanOut {
+    aggregator_selector: ViewSelector,
+    /// Pending aggregations by reader, `None` once a reader is dropped.
+    readers: Mutex<Vec<Option<HashMap<u64, Pending>>>>,
+}
+
+impl Processor for FanOut {
+    fn aggregation_selector(&self) -> &dyn AggregatorSelector {
+        &self.aggregator_selector
+    }
+}
+
+/// An aggregation owned by the fan-out until the reader collects it.
+#[derive(Debug)]
+struct Pending {
+    descriptor: Descriptor,
+    labels: LabelSet,
+    resource: Resource,
+    aggregator: Arc<dyn Aggregator + Send + Sync>,
+}
+
+/// Hands each accumulation to all readers.
+struct FanOutLockedProcessor<'a> {
+    aggregator_selector: &'a ViewSelector,
+    readers: &'a mut Vec<Option<HashMap<u64, Pending>>>,
+}
+
+impl LockedProcessor for FanOutLockedProcessor<'_> {
+    fn process(&mut self, accumulation: Accumulation<'_>) -> Result<()> {
+        let descriptor = accumulation.descriptor();
+        let mut hasher = FnvHasher::default();
+        descriptor.attribute_hash().hash(&mut hasher);
+        hash_labels(&mut hasher, accumulation.labels().into_iter());
+        hash_labels(&mut hasher, accumulation.resource().into_iter());
+        let key = hasher.finish();
+
+        for pending in self.readers.iter_mut().flatten() {
+            match pending.get_mut(&key) {
+                // Precomputed sums are observed in full, the last observation
+                // replaces the prior ones.
+                Some(value) if !descriptor.instrument_kind().precomputed_sum() => {
+                    value
+                        .aggregator
+                        .merge(accumulation.aggregator().as_ref(), descriptor)?;
+                }
+                _ => {
+                    let aggregator = self
+                        .aggregator_selector
+                        .aggregator_for(descriptor)
+                        .ok_or_else(|| {
+                            MetricsError::Other(format!(
+                                "no aggregator selected for {}",
+                                descriptor.name()
+                            ))
+                        })?;
+                    aggregator.merge(accumulation.aggregator().as_ref(), descriptor)?;
+                    pending.insert(
+                        key,
+                        Pending {
+                            descriptor: descriptor.clone(),
+                            labels: accumulation.labels().clone(),
+                            resource: accumulation.resource().clone(),
+                            aggregator,
+                        },
+                    );
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// A controller's handle on the shared accumulator.
+#[derive(Debug)]
+pub(crate) struct Reader {
+    accumulator: Accumulator,
+    fan_out: Arc<FanOut>,
+    index: usize,
+}
+
+impl Reader {
+    /// Collect the accumulator, and process everything collected since the
+    /// last collection of this reader.
+    fn collect(&self, checkpointer: &mut BasicLockedProcessor<'_>) -> Result<()> {
+        let mut readers = self.fan_out.readers.lock()?;
+        self.accumulator.0.collect(&mut FanOutLockedProcessor {
+            aggregator_selector: &self.fan_out.aggregator_selector,
+            readers: &mut readers,
+        });
+
+        let pending = readers[self.index].as_mut().map(std::mem::take);
+        drop(readers);
+
+        pending.into_iter().flatten().try_for_each(|(_key, value)| {
+            checkpointer.process(metrics::accumulation(
+                &value.descriptor,
+                &value.labels,
+                &value.resource,
+                &value.aggregator,
+            ))
+        })
+    }
+}
+
+impl Drop for Reader {
+    fn drop(&mut self) {
+        if let Ok(mut readers) = self.fan_out.readers.lock() {
+            readers[self.index] = None;
+        }
+    }
+}
+
+/// The source of the aggregations of a controller.
+#[derive(Clone, Debug)]
+pub(crate) enum Collector {
+    /// An accumulator owned by the controller.
+    Accumulator(Accumulator),
+    /// A reader of a shared accumulator.
+    Reader(Arc<Reader>),
+}
+
+impl Collector {
+    pub(crate) fn collect(&self, checkpointer: &mut BasicLockedProcessor<'_>) -> Result<()> {
+        match self {
+            Collector::Accumulator(accumulator) => {
+                accumulator.0.collect(checkpointer);
+                Ok(())
+            }
+            Collector::Reader(reader) => reader.collect(checkpointer),
+        }
+    }
+
+    /// The resource of the collected accumulator.
+    pub(crate) fn resource(&self) -> &Resource {
+        match self {
+            Collector::Accumulator(accumulator) => &accumulator.0.resource,
+            Collector::Reader(reader) => &reader.accumulator.0.resource,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::metrics::{MeterProvider, NumberKind};
+    use crate::sdk::{
+        export::metrics::{CheckpointSet, ExportKind, Sum},
+        metrics::{
+            aggregators::SumAggregator, controllers, selectors::simple::Selector, PullController,
+        },
+    };
+    use crate::KeyValue;
+    use std::time::Duration;
+
+    fn sum(controller: &mut PullController, export_kind: &ExportKind) -> Option<u64> {
+        controller.collect().unwrap();
+        let mut sum = None;
+        controller
+            .try_for_each(export_kind, &mut |record| {
+                let agg = record.aggregator().unwrap();
+                let value = agg
+                    .as_any()
+                    .downcast_ref::<SumAggregator>()
+                    .unwrap()
+                    .sum()?;
+                sum = Some(value.to_u64(&NumberKind::U64));
+                Ok(())
+            })
+            .unwrap();
+        sum
+    }
+
+    #[test]
+    fn readers_collect_independently() {
+        let shared = controllers::shared(Box::new(Selector::Exact)).build();
+        let mut cumulative = shared
+            .pull(Box::new(ExportKind::Cumulative))
+            .with_cache_period(Duration::from_secs(0))
+            .build();
+        let mut delta = shared
+            .pull(Box::new(ExportKind::Delta))
+            .with_cache_period(Duration::from_secs(0))
+            .with_memory(false)
+            .build();
+
+        let counter = shared.provider().meter("test").u64_counter("a").init();
+        let labels = [KeyValue::new("k", "v")];
+
+        counter.add(1, &labels);
+        assert_eq!(sum(&mut cumulative, &ExportKind::Cumulative), Some(1));
+        counter.add(2, &labels);
+        assert_eq!(sum(&mut delta, &ExportKind::Delta), Some(3));
+        counter.add(4, &labels);
+        assert_eq!(sum(&mut cumulative, &ExportKind::Cumulative), Some(7));
+        assert_eq!(sum(&mut delta, &ExportKind::Delta), Some(4));
+        assert_eq!(sum(&mut delta, &ExportKind::Delta), None);
+
+        // Dropped readers no longer receive aggregations.
+        drop(delta);
+        counter.add(8, &labels);
+        assert_eq!(sum(&mut cumulative, &ExportKind::Cumulative), Some(15));
+    }
+}
@@ -1,39 +1,145 @@
 //! # OpenTelemetry Metrics SDK
 use crate::global;
 use crate::metrics::{
-    sdk_api::{self, InstrumentCore as _, SyncBoundInstrumentCore as _},
-    AsyncRunner, AtomicNumber, Descriptor, Measurement, Number, NumberKind, Observation, Result,
+    sdk_api::{self, InstrumentCore as _},
+    AsyncRunner, AtomicNumber, Descriptor, Measurement, MetricsError, Number, NumberKind,
+    Observation, ObserverFuture, Result,
 };
 use crate::sdk::{
     export::{
         self,
-        metrics::{Aggregator, LockedProcessor, Processor},
+        metrics::{Aggregator, Exemplar, LockedProcessor, Processor},
     },
+    metrics::views::View,
     resource::Resource,
 };
 use crate::{
+    baggage::BaggageExt,
     labels::{hash_labels, LabelSet},
-    Context, KeyValue,
+    Context, Key, KeyValue,
 };
+use dashmap::mapref::entry::Entry;
 use fnv::FnvHasher;
+use futures::{channel::oneshot, future, task, Future, FutureExt};
 use std::any::Any;
+use std::borrow::Cow;
 use std::cmp::Ordering;
 use std::collections::HashMap;
+use std::fmt;
 use std::hash::{Hash, Hasher};
+use std::sync::atomic::{self, AtomicBool, AtomicUsize};
 use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant, SystemTime};
 
 pub mod aggregators;
 pub mod controllers;
 pub mod processors;
 pub mod selectors;
+pub mod views;
 
 pub use controllers::{PullController, PushController, PushControllerWorker};
 
+/// The label of the series measurements are recorded to once an instrument
+/// reached its cardinality limit.
+const OVERFLOW_LABEL_KEY: &str = "otel.metric.overflow";
+
+/// The time asynchronous observer callbacks have to complete on each
+/// collection by default.
+const DEFAULT_OBSERVER_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Spawns the futures of asynchronous observer callbacks on an async runtime.
+#[derive(Clone)]
+pub(crate) struct ObserverSpawner(Arc<dyn Fn(ObserverFuture) + Send + Sync>);
+
+impl ObserverSpawner {
+    pub(crate) fn new<F>(spawn: F) -> Self
+    where
+        F: Fn(ObserverFuture) + Send + Sync + 'static,
+    {
+        ObserverSpawner(Arc::new(spawn))
+    }
+
+    /// Spawn the future, returning a future completing with it and a handle
+    /// cancelling it.
+    fn spawn(&self, future: ObserverFuture) -> (ObserverFuture, future::AbortHandle) {
+        let (future, abort_handle) = future::abortable(future);
+        let (done, completed) = oneshot::channel();
+        (self.0)(Box::pin(async move {
+            let _ = future.await;
+            let _ = done.send(());
+        }));
+        (Box::pin(completed.map(drop)), abort_handle)
+    }
+}
+
+impl fmt::Debug for ObserverSpawner {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("Fn(ObserverFuture)")
+    }
+}
+
+/// Handles the errors of an accumulator instead of the global error handler.
+#[derive(Clone)]
+pub(crate) struct ErrorHandler(Arc<dyn Fn(MetricsError) + Send + Sync>);
+
+impl ErrorHandler {
+    pub(crate) fn new<F>(handle: F) -> Self
+    where
+        F: Fn(MetricsError) + Send + Sync + 'static,
+    {
+        ErrorHandler(Arc::new(handle))
+    }
+}
+
+impl fmt::Debug for ErrorHandler {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("Fn(MetricsError)")
+    }
+}
+
+/// How long series without updates are kept.
+///
+/// Keeping series longer avoids recreating the series of label sets that are
+/// updated irregularly, at the cost of the memory of the series that are not
+/// updated anymore.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Retention {
+    /// Remove series on the first collection without updates.
+    Immediate,
+    /// Keep series for the given number of collections without updates.
+    Collections(u64),
+    /// Keep series for the given duration after the last collection with
+    /// updates.
+    Duration(Duration),
+    /// Never remove series.
+    Forever,
+}
+
+impl Retention {
+    /// Whether a series that was not updated in the given number of
+    /// collections, lasting the given duration, has expired.
+    pub(crate) fn expired(&self, idle_collections: u64, idle_time: Duration) -> bool {
+        match self {
+            Retention::Immediate => true,
+            Retention::Collections(collections) => idle_collections > *collections,
+            Retention::Duration(duration) => idle_time > *duration,
+            Retention::Forever => false,
+        }
+    }
+}
+
 /// Creates a new accumulator builder
 pub fn accumulator(processor: Arc<dyn Processor + Send + Sync>) -> AccumulatorBuilder {
     AccumulatorBuilder {
         processor,
         resource: None,
+        cardinality_limit: None,
+        observer_timeout: None,
+        observer_spawner: None,
+        error_handler: None,
+        views: Vec::new(),
+        retention: None,
     }
 }
 
@@ -42,6 +148,12 @@ pub fn accumulator(processor: Arc<dyn Processor + Send + Sync>) -> AccumulatorBu
 pub struct AccumulatorBuilder {
     processor: Arc<dyn Processor + Send + Sync>,
     resource: Option<Resource>,
+    cardinality_limit: Option<usize>,
+    observer_timeout: Option<Duration>,
+    observer_spawner: Option<ObserverSpawner>,
+    error_handler: Option<ErrorHandler>,
+    views: Vec<View>,
+    retention: Option<Retention>,
 }
 
 impl AccumulatorBuilder {
@@ -53,12 +165,120 @@ impl AccumulatorBuilder {
         }
     }
 
+    /// The maximum number of distinct label sets recorded per instrument.
+    ///
+    /// Once an instrument reached this limit, measurements with new label sets
+    /// are recorded to a single series labeled `otel.metric.overflow=true`
+    /// until series without updates are removed on collection. The number of
+    /// label sets is not limited by default.
+    pub fn with_cardinality_limit(self, limit: usize) -> Self {
+        AccumulatorBuilder {
+            cardinality_limit: Some(limit),
+            ..self
+        }
+    }
+
+    /// The maximum time asynchronous observer callbacks have to complete on
+    /// each collection, 5 seconds by default.
+    ///
+    /// The futures of all callbacks run concurrently. Callbacks that did not
+    /// complete in time are cancelled, keeping the observations they already
+    /// made.
+    ///
+    /// Synchronous callbacks cannot be cancelled and are not bounded by the
+    /// timeout: they run one after another on the collecting thread before the
+    /// futures are polled, so a slow synchronous callback delays the collection
+    /// and the other synchronous callbacks, but not the futures, which get the
+    /// full timeout once the synchronous callbacks returned. Observers with
+    /// slow callbacks should be created with the `*_async` meter methods.
+    pub fn with_observer_timeout(self, timeout: Duration) -> Self {
+        AccumulatorBuilder {
+            observer_timeout: Some(timeout),
+            ..self
+        }
+    }
+
+    /// Spawn the futures of asynchronous observer callbacks with the given
+    /// function, e.g. `|future| { tokio::spawn(future); }`, while collection
+    /// waits for them.
+    ///
+    /// Without a spawner, the futures are polled on the collecting thread,
+    /// outside of any async runtime, so callbacks relying on a runtime's
+    /// reactor or timers need one. Collections must then not run on the
+    /// thread of a single threaded runtime the futures are spawned on.
+    pub fn with_observer_spawner<F>(self, spawn: F) -> Self
+    where
+        F: Fn(ObserverFuture) + Send + Sync + 'static,
+    {
+        AccumulatorBuilder {
+            observer_spawner: Some(ObserverSpawner::new(spawn)),
+            ..self
+        }
+    }
+
+    pub(crate) fn with_spawner(self, spawner: ObserverSpawner) -> Self {
+        AccumulatorBuilder {
+            observer_spawner: Some(spawner),
+            ..self
+        }
+    }
+
+    /// Handle the errors of this accumulator with the given function instead
+    /// of the global error handler.
+    pub fn with_error_handler<F>(self, handle: F) -> Self
+    where
+        F: Fn(MetricsError) + Send + Sync + 'static,
+    {
+        AccumulatorBuilder {
+            error_handler: Some(ErrorHandler::new(handle)),
+            ..self
+        }
+    }
+
+    pub(crate) fn with_handler(self, error_handler: ErrorHandler) -> Self {
+        AccumulatorBuilder {
+            error_handler: Some(error_handler),
+            ..self
+        }
+    }
+
+    /// The views whose baggage keys label the measurements of the instruments
+    /// they match, and whose dropped labels are kept on exemplars.
+    ///
+    /// Each instrument is labeled according to the first view matching it,
+    /// other view options are applied by the processor.
+    pub fn with_views(self, views: Vec<View>) -> Self {
+        AccumulatorBuilder { views, ..self }
+    }
+
+    /// How long the series of synchronous instruments are kept without
+    /// updates, `Retention::Immediate` by default.
+    ///
+    /// Series with bound handles are kept until the handles are dropped.
+    /// Series removed by the accumulator are only forgotten by processors
+    /// configured with the same retention.
+    pub fn with_retention(self, retention: Retention) -> Self {
+        AccumulatorBuilder {
+            retention: Some(retention),
+            ..self
+        }
+    }
+
     /// Create a new accumulator from this configuration
     pub fn build(self) -> Accumulator {
-        Accumulator(Arc::new(AccumulatorCore::new(
-            self.processor,
-            self.resource.unwrap_or_default(),
-        )))
+        Accumulator(Arc::new(AccumulatorCore {
+            current: dashmap::DashMap::new(),
+            async_instruments: Mutex::new(AsyncInstrumentState::default()),
+            current_epoch: NumberKind::U64.zero().to_atomic(),
+            processor: self.processor,
+            resource: self.resource.unwrap_or_default(),
+            cardinality_limit: self.cardinality_limit,
+            observer_timeout: self.observer_timeout.unwrap_or(DEFAULT_OBSERVER_TIMEOUT),
+            observer_spawner: self.observer_spawner,
+            error_handler: self.error_handler,
+            views: self.views,
+            retention: self.retention.unwrap_or(Retention::Immediate),
+        }))
     }
 }
 
@@ -82,9 +302,12 @@ struct AsyncInstrumentState {
     /// runners maintains the set of runners in the order they were
     /// registered.
     runners: Vec<(
-        AsyncRunner,
+        Arc<AsyncRunner>,
         Arc<dyn sdk_api::AsyncInstrumentCore + Send + Sync>,
     )>,
+    /// The callbacks of the batch observers, which observe the instruments
+    /// registered in `runners` by their batch.
+    batch_runners: Vec<Arc<AsyncRunner>>,
 }
 
 fn collect_async(labels: &[KeyValue], observations: &[Observation]) {
@@ -102,11 +325,48 @@ fn collect_async(labels: &[KeyValue], observations: &[Observation]) {
 }
 
 impl AsyncInstrumentState {
-    fn run(&self) {
-        for (runner, instrument) in self.runners.iter() {
-            // TODO see if batch needs other logic
-            runner.run(instrument.clone(), collect_async)
+    /// Run the callbacks, returning the futures of the asynchronous ones.
+    ///
+    /// Synchronous callbacks run to completion in registration order, the
+    /// observer timeout only applies to the returned futures.
+    fn run(&self) -> Vec<ObserverFuture> {
+        self.runners
+            .iter()
+            .filter_map(|(runner, instrument)| runner.start(instrument.clone(), collect_async))
+            .chain(
+                self.batch_runners
+                    .iter()
+                    .filter_map(|runner| runner.start_batch(collect_async)),
+            )
+            .collect()
+    }
+}
+
+/// Poll the future on the current thread until it completes, returning
+/// whether it completed before the timeout elapsed.
+fn block_on_timeout<F: Future>(future: F, timeout: Duration) -> bool {
+    struct ThreadWaker(thread::Thread);
+
+    impl task::ArcWake for ThreadWaker {
+        fn wake_by_ref(arc_self: &Arc<Self>) {
+            arc_self.0.unpark()
+        }
+    }
+
+    let deadline = Instant::now() + timeout;
+    let waker = task::waker(Arc::new(ThreadWaker(thread::current())));
+    let mut cx = task::Context::from_waker(&waker);
+    futures::pin_mut!(future);
+
+    loop {
+        if future.as_mut().poll(&mut cx).is_ready() {
+            return true;
         }
+        let now = Instant::now();
+        if now >= deadline {
+            return false;
+        }
+        thread::park_timeout(deadline - now);
     }
 }
 
@@ -123,16 +383,25 @@ struct AccumulatorCore {
     processor: Arc<dyn Processor + Send + Sync>,
     /// The resource applied to all records in this Accumulator.
     resource: Resource,
+    /// The maximum number of distinct label sets recorded per instrument.
+    cardinality_limit: Option<usize>,
+    /// The time asynchronous observer callbacks have to complete.
+    observer_timeout: Duration,
+    /// Spawns the futures of asynchronous observer callbacks.
+    observer_spawner: Option<ObserverSpawner>,
+    /// Handles errors instead of the global error handler.
+    error_handler: Option<ErrorHandler>,
+    /// The views selecting the baggage keys labeling measurements.
+    views: Vec<View>,
+    /// How long records are kept without updates.
+    retention: Retention,
 }
 
 impl AccumulatorCore {
-    fn new(processor: Arc<dyn Processor + Send + Sync>, resource: Resource) -> Self {
-        AccumulatorCore {
-            current: dashmap::DashMap::new(),
-            async_instruments: Mutex::new(AsyncInstrumentState::default()),
-            current_epoch: NumberKind::U64.zero().to_atomic(),
-            processor,
-            resource,
+    fn handle_error(&self, err: MetricsError) {
+        match &self.error_handler {
+            Some(error_handler) => (error_handler.0)(err),
+            None => global::handle_error(err),
         }
     }
 
@@ -145,7 +414,18 @@ impl AccumulatorCore {
             .lock()
             .map_err(Into::into)
             .map(|mut async_instruments| {
-                async_instruments.runners.push((runner, instrument));
+                async_instruments
+                    .runners
+                    .push((Arc::new(runner), instrument));
+            })
+    }
+
+    fn register_batch(&self, runner: AsyncRunner) -> Result<()> {
+        self.async_instruments
+            .lock()
+            .map_err(Into::into)
+            .map(|mut async_instruments| {
+                async_instruments.batch_runners.push(Arc::new(runner));
             })
     }
 
@@ -158,25 +438,52 @@ impl AccumulatorCore {
     }
 
     fn observe_async_instruments(&self, locked_processor: &mut dyn LockedProcessor) -> usize {
-        self.async_instruments
-            .lock()
-            .map_or(0, |async_instruments| {
-                let mut async_collected = 0;
+        // Callbacks run without holding the lock, so they may register new
+        // instruments, which are observed from the next collection on.
+        let async_instruments = match self.async_instruments.lock() {
+            Ok(async_instruments) => AsyncInstrumentState {
+                runners: async_instruments.runners.clone(),
+                batch_runners: async_instruments.batch_runners.clone(),
+            },
+            Err(_) => return 0,
+        };
 
-                async_instruments.run();
+        let mut futures = async_instruments.run();
+        let mut abort_handles = Vec::new();
+        if let Some(spawner) = &self.observer_spawner {
+            futures = futures
+                .into_iter()
+                .map(|future| {
+                    let (future, abort_handle) = spawner.spawn(future);
+                    abort_handles.push(abort_handle);
+                    future
+                })
+                .collect();
+        }
+        if !futures.is_empty()
+            && !block_on_timeout(future::join_all(futures), self.observer_timeout)
+        {
+            self.handle_error(MetricsError::ObserverTimedOut(self.observer_timeout));
+        }
+        for abort_handle in abort_handles {
+            abort_handle.abort();
+        }
 
-                for (_runner, instrument) in &async_instruments.runners {
-                    if let Some(a) = instrument.as_any().downcast_ref::<AsyncInstrument>() {
-                        async_collected += self.checkpoint_async(a, locked_processor);
-                    }
-                }
+        let mut async_collected = 0;
+        for (_runner, instrument) in &async_instruments.runners {
+            if let Some(a) = instrument.as_any().downcast_ref::<AsyncInstrument>() {
+                async_collected += self.checkpoint_async(a, locked_processor);
+            }
+        }
 
-                async_collected
-            })
+        async_collected
     }
 
     fn collect_sync_instruments(&self, locked_processor: &mut dyn LockedProcessor) -> usize {
         let mut checkpointed = 0;
+        let mut unused = Vec::new();
+        let epoch = self.current_epoch.load().to_u64(&NumberKind::U64);
+        let now = Instant::now();
 
         for element in self.current.iter() {
             let (key, value) = element.pair();
@@ -188,18 +495,37 @@ impl AccumulatorCore {
                 // checkpoint and continue.
                 checkpointed += self.checkpoint_record(value, locked_processor);
                 value.collected_count.store(mods);
-            } else {
-                // Having no updates since last collection, try to remove if
-                // there are no bound handles
-                if Arc::strong_count(&value) == 1 {
-                    self.current.remove(key);
-
-                    // There's a potential race between loading collected count and
-                    // loading the strong count in this function.  Since this is the
-                    // last we'll see of this record, checkpoint.
-                    if mods.partial_cmp(&NumberKind::U64, coll) != Some(Ordering::Equal) {
-                        checkpointed += self.checkpoint_record(value, locked_processor);
-                    }
+                if let Ok(mut last_collection) = value.last_collection.lock() {
+                    *last_collection = (epoch, now);
+                }
+            } else if Arc::strong_count(value) == 1 && self.expired(value, epoch, now) {
+                // Having no updates for longer than the retention, try to
+                // remove if there are no bound handles. Records are removed
+                // once the iteration released its lock on the map.
+                unused.push(key.clone());
+            }
+        }
+
+        for key in unused {
+            if let Some((_key, record)) = self
+                .current
+                .remove_if(&key, |_key, record| Arc::strong_count(record) == 1)
+            {
+                if !record.overflow {
+                    record
+                        .instrument
+                        .instrument
+                        .cardinality
+                        .fetch_sub(1, atomic::Ordering::Relaxed);
+                }
+
+                // There's a potential race between loading collected count and
+                // loading the strong count in this function.  Since this is the
+                // last we'll see of this record, checkpoint.
+                let mods = &record.update_count.load();
+                let coll = &record.collected_count.load();
+                if mods.partial_cmp(&NumberKind::U64, coll) != Some(Ordering::Equal) {
+                    checkpointed += self.checkpoint_record(&record, locked_processor);
                 }
             }
         }
@@ -207,6 +533,19 @@ impl AccumulatorCore {
         checkpointed
     }
 
+    fn expired(&self, record: &Record, epoch: u64, now: Instant) -> bool {
+        record
+            .last_collection
+            .lock()
+            .map_or(true, |last_collection| {
+                let (last_epoch, last_time) = *last_collection;
+                self.retention.expired(
+                    epoch.wrapping_sub(last_epoch),
+                    now.saturating_duration_since(last_time),
+                )
+            })
+    }
+
     fn checkpoint_record(
         &self,
         record: &Record,
@@ -215,7 +554,7 @@ impl AccumulatorCore {
         if let (Some(current), Some(checkpoint)) = (&record.current, &record.checkpoint) {
             if let Err(err) = current.synchronized_move(checkpoint, record.instrument.descriptor())
             {
-                global::handle_error(err);
+                self.handle_error(err);
 
                 return 0;
             }
@@ -227,7 +566,7 @@ impl AccumulatorCore {
                 &checkpoint,
             );
             if let Err(err) = locked_processor.process(accumulation) {
-                global::handle_error(err);
+                self.handle_error(err);
             }
 
             1
@@ -261,7 +600,7 @@ impl AccumulatorCore {
                                 );
 
                                 if let Err(err) = locked_processor.process(accumulation) {
-                                    global::handle_error(err);
+                                    self.handle_error(err);
                                 }
                                 checkpointed += 1;
                             }
@@ -288,7 +627,53 @@ struct SyncInstrument {
 }
 
 impl SyncInstrument {
+    /// The labels extended with the configured baggage entries of the context.
+    fn labels_in_context<'a>(&self, cx: &Context, labels: &'a [KeyValue]) -> Cow<'a, [KeyValue]> {
+        if self.instrument.baggage_keys.is_empty() {
+            return Cow::Borrowed(labels);
+        }
+
+        let baggage = cx.baggage();
+        let mut labels = labels.to_vec();
+        for key in self.instrument.baggage_keys.iter() {
+            if labels.iter().any(|kv| &kv.key == key) {
+                continue;
+            }
+            if let Some(value) = baggage.get(key.clone()) {
+                labels.push(KeyValue::new(key.clone(), value.clone()));
+            }
+        }
+
+        Cow::Owned(labels)
+    }
+
     fn acquire_handle(&self, labels: &[KeyValue]) -> Arc<Record> {
+        let map_key = self.map_key(labels);
+        let current = &self.instrument.meter.0.current;
+        if let Some(existing_record) = current.get(&map_key) {
+            return existing_record.value().clone();
+        }
+
+        match current.entry(map_key) {
+            Entry::Occupied(entry) => return entry.get().clone(),
+            Entry::Vacant(entry) if self.instrument.reserve_label_set() => {
+                return entry.insert(self.new_record(labels, false)).value().clone();
+            }
+            Entry::Vacant(_) => (),
+        };
+
+        // The instrument reached its cardinality limit, record to the overflow
+        // series instead.
+        self.instrument.warn_overflow();
+        let overflow_labels = [KeyValue::new(OVERFLOW_LABEL_KEY, true)];
+        current
+            .entry(self.map_key(&overflow_labels))
+            .or_insert_with(|| self.new_record(&overflow_labels, true))
+            .value()
+            .clone()
+    }
+
+    fn map_key(&self, labels: &[KeyValue]) -> MapKey {
         let mut hasher = FnvHasher::default();
         self.instrument
             .descriptor
@@ -297,18 +682,23 @@ impl SyncInstrument {
 
         hash_labels(&mut hasher, labels.iter().map(|kv| (&kv.key, &kv.value)));
 
-        let map_key = MapKey {
+        MapKey {
             instrument_hash: hasher.finish(),
-        };
-        let current = &self.instrument.meter.0.current;
-        if let Some(existing_record) = current.get(&map_key) {
-            return existing_record.value().clone();
         }
+    }
 
-        let record = Arc::new(Record {
+    fn new_record(&self, labels: &[KeyValue], overflow: bool) -> Arc<Record> {
+        // New records count as collected by the previous collection.
+        let epoch = self.instrument.meter.0.current_epoch.load();
+        Arc::new(Record {
             update_count: NumberKind::U64.zero().to_atomic(),
             collected_count: NumberKind::U64.zero().to_atomic(),
+            last_collection: Mutex::new((
+                epoch.to_u64(&NumberKind::U64).wrapping_sub(1),
+                Instant::now(),
+            )),
             labels: LabelSet::from_labels(labels.iter().cloned()),
+            overflow,
             instrument: self.clone(),
             current: self
                 .instrument
@@ -324,10 +714,7 @@ impl SyncInstrument {
                 .processor
                 .aggregation_selector()
                 .aggregator_for(&self.instrument.descriptor),
-        });
-        current.insert(map_key, record.clone());
-
-        record
+        })
     }
 }
 
@@ -342,11 +729,17 @@ impl sdk_api::SyncInstrumentCore for SyncInstrument {
         &self,
         labels: &'a [KeyValue],
     ) -> Arc<dyn sdk_api::SyncBoundInstrumentCore + Send + Sync> {
-        self.acquire_handle(labels)
+        // Errors are handled once the context is released, as error handlers
+        // may attach contexts.
+        let labels = Context::map_current(|cx| self.labels_in_context(cx, labels));
+        self.acquire_handle(&labels)
     }
     fn record_one<'a>(&self, number: Number, labels: &'a [KeyValue]) {
-        let handle = self.acquire_handle(labels);
-        handle.record_one(number)
+        let (labels, span) =
+            Context::map_current(|cx| (self.labels_in_context(cx, labels), sampled_span(cx)));
+        let handle = self.acquire_handle(&labels);
+        let exemplar = sample_exemplar(&number, &handle, span);
+        handle.record_with_exemplar(number, exemplar)
     }
     fn as_any(&self) -> &dyn Any {
         self
@@ -369,20 +762,39 @@ struct AsyncInstrument {
 impl AsyncInstrument {
     fn observe(&self, number: &Number, labels: &LabelSet) {
         if let Err(err) = aggregators::range_test(number, &self.instrument.descriptor) {
-            global::handle_error(err);
+            self.instrument.meter.0.handle_error(err);
         }
         if let Some(recorder) = self.get_recorder(labels) {
             if let Err(err) = recorder.update(number, &self.instrument.descriptor) {
-                global::handle_error(err)
+                self.instrument.meter.0.handle_error(err)
             }
         }
     }
 
     fn get_recorder(&self, labels: &LabelSet) -> Option<Arc<dyn Aggregator + Send + Sync>> {
         self.recorders.lock().map_or(None, |mut recorders| {
-            let mut hasher = FnvHasher::default();
-            hash_labels(&mut hasher, labels.into_iter());
-            let label_hash = hasher.finish();
+            let mut label_hash = hash_label_set(labels);
+            let overflow_labels;
+            let mut labels = labels;
+            let mut overflow = false;
+            if let Some(limit) = self.instrument.meter.0.cardinality_limit {
+                let overflow_hash = hash_label_set(&overflow_label_set());
+                let label_sets = recorders.as_ref().map_or(0, |rec| {
+                    rec.len() - rec.contains_key(&overflow_hash) as usize
+                });
+                let known = recorders
+                    .as_ref()
+                    .map_or(false, |rec| rec.contains_key(&label_hash));
+                if !known && label_hash != overflow_hash && label_sets >= limit {
+                    // The instrument reached its cardinality limit, observe
+                    // the overflow series instead.
+                    self.instrument.warn_overflow();
+                    overflow_labels = overflow_label_set();
+                    labels = &overflow_labels;
+                    label_hash = overflow_hash;
+                    overflow = true;
+                }
+            }
             if let Some(recorder) = recorders.as_mut().and_then(|rec| rec.get_mut(&label_hash)) {
                 let current_epoch = self
                     .instrument
@@ -391,7 +803,12 @@ impl AsyncInstrument {
                     .current_epoch
                     .load()
                     .to_u64(&NumberKind::U64);
-                if recorder.observed_epoch == current_epoch {
+                if overflow {
+                    // The observations of all label sets exceeding the limit
+                    // are aggregated, summed by sum observers and last value
+                    // wins for value observers.
+                    recorder.observed_epoch = current_epoch;
+                } else if recorder.observed_epoch == current_epoch {
                     // last value wins for Observers, so if we see the same labels
                     // in the current epoch, we replace the old recorder
                     return self
@@ -466,11 +883,19 @@ struct Record {
     /// a round.
     collected_count: AtomicNumber,
 
+    /// The epoch and time of the last collection with updates, supports
+    /// removing records without updates once their retention expired.
+    last_collection: Mutex<(u64, Instant)>,
+
     /// The processed label set for this record.
     ///
     /// TODO: look at perf here.
     labels: LabelSet,
 
+    /// Whether this record collects the measurements of label sets exceeding
+    /// the cardinality limit of the instrument.
+    overflow: bool,
+
     /// The corresponding instrument.
     instrument: SyncInstrument,
 
@@ -480,15 +905,18 @@ struct Record {
     checkpoint: Option<Arc<dyn Aggregator + Send + Sync>>,
 }
 
-impl sdk_api::SyncBoundInstrumentCore for Record {
-    fn record_one<'a>(&self, number: Number) {
+impl Record {
+    fn record_with_exemplar(&self, number: Number, exemplar: Option<Exemplar>) {
         // check if the instrument is disabled according to the AggregatorSelector.
         if let Some(recorder) = &self.current {
+            let descriptor = &self.instrument.instrument.descriptor;
             if let Err(err) =
-                aggregators::range_test(&number, &self.instrument.instrument.descriptor)
-                    .and_then(|_| recorder.update(&number, &self.instrument.instrument.descriptor))
+                aggregators::range_test(&number, descriptor).and_then(|_| match exemplar {
+                    Some(exemplar) => recorder.update_with_exemplar(&number, descriptor, exemplar),
+                    None => recorder.update(&number, descriptor),
+                })
             {
-                global::handle_error(err);
+                self.instrument.instrument.meter.0.handle_error(err);
                 return;
             }
 
@@ -499,10 +927,136 @@ impl sdk_api::SyncBoundInstrumentCore for Record {
     }
 }
 
+impl sdk_api::SyncBoundInstrumentCore for Record {
+    fn record_one<'a>(&self, number: Number) {
+        let span = Context::map_current(sampled_span);
+        let exemplar = sample_exemplar(&number, self, span);
+        self.record_with_exemplar(number, exemplar)
+    }
+}
+
+/// The trace and span ids of the span of the context, if that span is sampled.
+#[cfg(feature = "trace")]
+fn sampled_span(cx: &Context) -> Option<(u128, u64)> {
+    use crate::trace::TraceContextExt;
+
+    let span_context = cx.span().span_context();
+    if span_context.is_valid() && span_context.is_sampled() {
+        Some((
+            span_context.trace_id().to_u128(),
+            span_context.span_id().to_u64(),
+        ))
+    } else {
+        None
+    }
+}
+
+/// Exemplars link measurements to spans, which requires the `trace` feature.
+#[cfg(not(feature = "trace"))]
+fn sampled_span(_cx: &Context) -> Option<(u128, u64)> {
+    None
+}
+
+/// Samples an exemplar linking the measurement to the sampled span it was
+/// recorded in, if any, keeping the labels of the record dropped by its view.
+fn sample_exemplar(
+    number: &Number,
+    record: &Record,
+    span: Option<(u128, u64)>,
+) -> Option<Exemplar> {
+    span.map(|(trace_id, span_id)| {
+        Exemplar::new(
+            number.clone(),
+            SystemTime::now(),
+            trace_id,
+            span_id,
+            record
+                .instrument
+                .instrument
+                .view
+                .as_ref()
+                .map(|view| view.filtered_labels(&record.labels))
+                .unwrap_or_default(),
+        )
+    })
+}
+
 #[derive(Debug)]
 struct Instrument {
     descriptor: Descriptor,
     meter: Accumulator,
+    /// The number of distinct label sets currently recorded by this instrument,
+    /// excluding the overflow series.
+    cardinality: AtomicUsize,
+    /// The first view matching this instrument, if any.
+    view: Option<View>,
+    /// The baggage keys labeling the measurements of this instrument.
+    baggage_keys: Vec<Key>,
+    /// Whether exceeding the cardinality limit was already reported.
+    overflowed: AtomicBool,
+}
+
+impl Instrument {
+    fn new(descriptor: Descriptor, meter: Accumulator) -> Self {
+        let view = meter
+            .0
+            .views
+            .iter()
+            .find(|view| view.matches(&descriptor))
+            .cloned();
+        let baggage_keys = view
+            .as_ref()
+            .and_then(|view| view.baggage_keys())
+            .cloned()
+            .unwrap_or_default();
+
+        Instrument {
+            descriptor,
+            meter,
+            view,
+            baggage_keys,
+            cardinality: AtomicUsize::new(0),
+            overflowed: AtomicBool::new(false),
+        }
+    }
+
+    /// Reserve a new label set, returns `false` if the cardinality limit of
+    /// the accumulator is reached.
+    fn reserve_label_set(&self) -> bool {
+        let limit = self.meter.0.cardinality_limit;
+        self.cardinality
+            .fetch_update(atomic::Ordering::Relaxed, atomic::Ordering::Relaxed, |n| {
+                if limit.map_or(true, |limit| n < limit) {
+                    Some(n + 1)
+                } else {
+                    None
+                }
+            })
+            .is_ok()
+    }
+
+    /// Report the first measurement exceeding the cardinality limit.
+    fn warn_overflow(&self) {
+        if !self.overflowed.swap(true, atomic::Ordering::Relaxed) {
+            let err = MetricsError::CardinalityLimitExceeded(format!(
+                "{} reached the limit of {} label sets, recording further label sets as {}=true",
+                self.descriptor.name(),
+                self.meter.0.cardinality_limit.unwrap_or_default(),
+                OVERFLOW_LABEL_KEY,
+            ));
+            self.meter.0.handle_error(err);
+        }
+    }
+}
+
+fn overflow_label_set() -> LabelSet {
+    LabelSet::from_labels(vec![KeyValue::new(OVERFLOW_LABEL_KEY, true)])
+}
+
+fn hash_label_set(labels: &LabelSet) -> u64 {
+    let mut hasher = FnvHasher::default();
+    hash_labels(&mut hasher, labels.into_iter());
+    hasher.finish()
 }
 
 impl sdk_api::InstrumentCore for Instrument {
@@ -517,16 +1071,13 @@ impl sdk_api::MeterCore for Accumulator {
         descriptor: Descriptor,
     ) -> Result<Arc<dyn sdk_api::SyncInstrumentCore + Send + Sync>> {
         Ok(Arc::new(SyncInstrument {
-            instrument: Arc::new(Instrument {
-                descriptor,
-                meter: self.clone(),
-            }),
+            instrument: Arc::new(Instrument::new(descriptor, self.clone())),
         }))
     }
 
     fn record_batch_with_context(
         &self,
-        _cx: &Context,
+        cx: &Context,
         labels: &[KeyValue],
         measurements: Vec<Measurement>,
     ) {
@@ -537,9 +1088,11 @@ impl sdk_api::MeterCore for Accumulator {
                 .as_any()
                 .downcast_ref::<SyncInstrument>()
             {
-                let handle = instrument.acquire_handle(labels);
+                let handle = instrument.acquire_handle(&instrument.labels_in_context(cx, labels));
 
-                handle.record_one(measure.into_number());
+                let number = measure.into_number();
+                let exemplar = sample_exemplar(&number, &handle, sampled_span(cx));
+                handle.record_with_exemplar(number, exemplar);
             }
         }
     }
@@ -550,10 +1103,7 @@ impl sdk_api::MeterCore for Accumulator {
         runner: AsyncRunner,
     ) -> Result<Arc<dyn sdk_api::AsyncInstrumentCore + Send + Sync>> {
         let instrument = Arc::new(AsyncInstrument {
-            instrument: Arc::new(Instrument {
-                descriptor,
-                meter: self.clone(),
-            }),
+            instrument: Arc::new(Instrument::new(descriptor, self.clone())),
             recorders: Arc::new(Mutex::new(None)),
         });
 
@@ -561,4 +1111,544 @@ impl sdk_api::MeterCore for Accumulator {
 
         Ok(instrument)
     }
+
+    fn new_batch_observer(&self, runner: AsyncRunner) -> Result<()> {
+        self.0.register_batch(runner)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::labels;
+    use crate::metrics::{BatchObserverResult, MeterProvider, ObserverResult, SumObserver};
+    use crate::sdk::{
+        export::metrics::{CheckpointSet, ExportKind, LastValue, Sum},
+        metrics::{
+            aggregators::{LastValueAggregator, SumAggregator},
+            controllers,
+            selectors::simple::Selector,
+        },
+    };
+    use std::time::Duration;
+
+    fn collect_sums(
+        controller: &mut PullController,
+        export_kind: &ExportKind,
+    ) -> HashMap<String, u64> {
+        controller.collect().unwrap();
+
+        let encoder = labels::default_encoder();
+        let mut sums = HashMap::new();
+        controller
+            .try_for_each(export_kind, &mut |record| {
+                let agg = record.aggregator().unwrap();
+                if let Some(sum) = agg.as_any().downcast_ref::<SumAggregator>() {
+                    sums.insert(
+                        record.labels().encoded(Some(encoder.as_ref())),
+                        sum.sum()?.to_u64(&NumberKind::U64),
+                    );
+                }
+                Ok(())
+            })
+            .unwrap();
+        sums
+    }
+
+    #[test]
+    fn cardinality_limit() {
+        let errors = Arc::new(Mutex::new(Vec::new()));
+        let handled = errors.clone();
+
+        let mut controller =
+            controllers::pull(Box::new(Selector::Exact), Box::new(ExportKind::Delta))
+                .with_error_handler(move |err| handled.lock().unwrap().push(err.to_string()))
+                .with_cardinality_limit(2)
+                .with_cache_period(Duration::from_secs(0))
+                .with_memory(false)
+                .build();
+        let counter = controller
+            .provider()
+            .meter("test")
+            .u64_counter("requests")
+            .init();
+
+        for (id, value) in &[("1", 1), ("2", 2), ("3", 4), ("4", 8), ("1", 16)] {
+            counter.add(*value, &[KeyValue::new("id", *id)]);
+        }
+
+        let mut expected = HashMap::new();
+        expected.insert("id=1".to_string(), 17);
+        expected.insert("id=2".to_string(), 2);
+        expected.insert("otel.metric.overflow=true".to_string(), 12);
+        assert_eq!(collect_sums(&mut controller, &ExportKind::Delta), expected);
+        assert_eq!(
+            errors
+                .lock()
+                .unwrap()
+                .iter()
+                .filter(|err| err.contains("requests reached the limit of 2 label sets"))
+                .count(),
+            1
+        );
+
+        // Label sets without updates are removed, making room for new ones.
+        counter.add(1, &[KeyValue::new("id", "1")]);
+        collect_sums(&mut controller, &ExportKind::Delta);
+        counter.add(32, &[KeyValue::new("id", "5")]);
+
+        let mut expected = HashMap::new();
+        expected.insert("id=5".to_string(), 32);
+        assert_eq!(collect_sums(&mut controller, &ExportKind::Delta), expected);
+    }
+
+    #[test]
+    fn error_handler_attaching_contexts() {
+        let errors = Arc::new(AtomicUsize::new(0));
+        let handled = errors.clone();
+        let controller = controllers::pull(Box::new(Selector::Exact), Box::new(ExportKind::Delta))
+            .with_error_handler(move |_| {
+                let _guard = Context::new().attach();
+                handled.fetch_add(1, atomic::Ordering::SeqCst);
+            })
+            .with_cardinality_limit(1)
+            .build();
+        let meter = controller.provider().meter("test");
+        let counter = meter.u64_counter("requests").init();
+        let recorder = meter.f64_value_recorder("latency").init();
+
+        // Errors are handled outside of the current context.
+        counter.add(1, &[KeyValue::new("id", "1")]);
+        counter.add(1, &[KeyValue::new("id", "2")]);
+        counter.bind(&[KeyValue::new("id", "3")]).add(1);
+        recorder.record(f64::NAN, &[]);
+        assert_eq!(errors.load(atomic::Ordering::SeqCst), 2);
+    }
+
+    #[test]
+    fn observer_cardinality_limit() {
+        let mut controller =
+            controllers::pull(Box::new(Selector::Exact), Box::new(ExportKind::Cumulative))
+                .with_error_handler(|_| {})
+                .with_cardinality_limit(2)
+                .with_cache_period(Duration::from_secs(0))
+                .build();
+        let meter = controller.provider().meter("test");
+        let observe = |result: ObserverResult<u64>| {
+            for (id, value) in &[("1", 1), ("2", 2), ("3", 4), ("4", 8), ("5", 16)] {
+                result.observe(*value, &[KeyValue::new("id", *id)]);
+            }
+        };
+        meter.u64_sum_observer("reads", observe).init();
+        meter.u64_value_observer("queued", observe).init();
+
+        for _ in 0..2 {
+            controller.collect().unwrap();
+            let encoder = labels::default_encoder();
+            let mut values = Vec::new();
+            controller
+                .try_for_each(&ExportKind::Cumulative, &mut |record| {
+                    let agg = record.aggregator().unwrap().as_any();
+                    let value = if let Some(sum) = agg.downcast_ref::<SumAggregator>() {
+                        sum.sum()?
+                    } else if let Some(last) = agg.downcast_ref::<LastValueAggregator>() {
+                        last.last_value()?.0
+                    } else {
+                        return Ok(());
+                    };
+                    values.push((
+                        format!(
+                            "{} {}",
+                            record.descriptor().name(),
+                            record.labels().encoded(Some(encoder.as_ref()))
+                        ),
+                        value.to_u64(&NumberKind::U64),
+                    ));
+                    Ok(())
+                })
+                .unwrap();
+            values.sort();
+
+            // The label sets over the limit are summed by the sum observer,
+            // the value observer keeps the last one.
+            assert_eq!(
+                values,
+                vec![
+                    ("queued id=1".to_string(), 1),
+                    ("queued id=2".to_string(), 2),
+                    ("queued otel.metric.overflow=true".to_string(), 16),
+                    ("reads id=1".to_string(), 1),
+                    ("reads id=2".to_string(), 2),
+                    ("reads otel.metric.overflow=true".to_string(), 28),
+                ]
+            );
+        }
+    }
+
+    #[test]
+    fn retention() {
+        let mut controller =
+            controllers::pull(Box::new(Selector::Exact), Box::new(ExportKind::Cumulative))
+                .with_cardinality_limit(1)
+                .with_retention(Retention::Collections(1))
+                .with_cache_period(Duration::from_secs(0))
+                .build();
+        let counter = controller
+            .provider()
+            .meter("test")
+            .u64_counter("requests")
+            .init();
+        let sums = |controller: &mut PullController| {
+            let mut sums = collect_sums(controller, &ExportKind::Cumulative)
+                .into_iter()
+                .collect::<Vec<_>>();
+            sums.sort();
+            sums
+        };
+
+        counter.add(1, &[KeyValue::new("id", "1")]);
+        assert_eq!(sums(&mut controller), vec![("id=1".to_string(), 1)]);
+
+        // Kept for a collection without updates, so new label sets overflow.
+        assert_eq!(sums(&mut controller), vec![("id=1".to_string(), 1)]);
+        counter.add(2, &[KeyValue::new("id", "2")]);
+        assert_eq!(
+            sums(&mut controller),
+            vec![("otel.metric.overflow=true".to_string(), 2)]
+        );
+
+        // Removed after the second collection without updates.
+        counter.add(4, &[KeyValue::new("id", "3")]);
+        assert_eq!(
+            sums(&mut controller),
+            vec![
+                ("id=3".to_string(), 4),
+                ("otel.metric.overflow=true".to_string(), 2)
+            ]
+        );
+    }
+
+    #[test]
+    fn async_observers() {
+        let mut controller =
+            controllers::pull(Box::new(Selector::Exact), Box::new(ExportKind::Cumulative))
+                .with_observer_timeout(Duration::from_millis(200))
+                .with_cache_period(Duration::from_secs(0))
+                .build();
+        let meter = controller.provider().meter("test");
+
+        meter
+            .u64_sum_observer_async("stalled", |result| async move {
+                future::pending::<()>().await;
+                result.observe(1, &[KeyValue::new("name", "stalled")]);
+            })
+            .init();
+        meter
+            .u64_sum_observer_async("delayed", |result| async move {
+                let (sender, receiver) = futures::channel::oneshot::channel();
+                thread::spawn(move || {
+                    thread::sleep(Duration::from_millis(10));
+                    let _ = sender.send(2);
+                });
+                if let Ok(value) = receiver.await {
+                    result.observe(value, &[KeyValue::new("name", "delayed")]);
+                }
+            })
+            .init();
+        meter
+            .u64_sum_observer("sync", |result| {
+                result.observe(3, &[KeyValue::new("name", "sync")])
+            })
+            .init();
+
+        // The stalled callback is cancelled without holding back the others.
+        let start = Instant::now();
+        let mut expected = HashMap::new();
+        expected.insert("name=delayed".to_string(), 2);
+        expected.insert("name=sync".to_string(), 3);
+        assert_eq!(
+            collect_sums(&mut controller, &ExportKind::Cumulative),
+            expected
+        );
+        assert!(start.elapsed() < Duration::from_secs(5));
+    }
+
+    #[test]
+    fn slow_sync_observers() {
+        let errors = Arc::new(Mutex::new(Vec::new()));
+        let handled = errors.clone();
+
+        let mut controller =
+            controllers::pull(Box::new(Selector::Exact), Box::new(ExportKind::Cumulative))
+                .with_error_handler(move |err| handled.lock().unwrap().push(err.to_string()))
+                .with_observer_timeout(Duration::from_millis(200))
+                .with_cache_period(Duration::from_secs(0))
+                .build();
+        let meter = controller.provider().meter("test");
+
+        meter
+            .u64_sum_observer("slow", |result| {
+                thread::sleep(Duration::from_millis(300));
+                result.observe(1, &[KeyValue::new("name", "slow")])
+            })
+            .init();
+        meter
+            .u64_sum_observer("sync", |result| {
+                result.observe(2, &[KeyValue::new("name", "sync")])
+            })
+            .init();
+        meter
+            .u64_sum_observer_async("delayed", |result| async move {
+                let (sender, receiver) = futures::channel::oneshot::channel();
+                thread::spawn(move || {
+                    thread::sleep(Duration::from_millis(10));
+                    let _ = sender.send(3);
+                });
+                if let Ok(value) = receiver.await {
+                    result.observe(value, &[KeyValue::new("name", "delayed")]);
+                }
+            })
+            .init();
+
+        // Synchronous callbacks outlast the timeout, which only bounds the
+        // futures polled after them.
+        let start = Instant::now();
+        let mut expected = HashMap::new();
+        expected.insert("name=slow".to_string(), 1);
+        expected.insert("name=sync".to_string(), 2);
+        expected.insert("name=delayed".to_string(), 3);
+        assert_eq!(
+            collect_sums(&mut controller, &ExportKind::Cumulative),
+            expected
+        );
+        assert!(start.elapsed() >= Duration::from_millis(300));
+        assert!(errors.lock().unwrap().is_empty());
+    }
+
+    #[test]
+    fn batch_observer() {
+        let mut controller =
+            controllers::pull(Box::new(Selector::Exact), Box::new(ExportKind::Cumulative))
+                .with_cache_period(Duration::from_secs(0))
+                .build();
+        let runs = Arc::new(AtomicUsize::new(0));
+        let batch_runs = runs.clone();
+        controller
+            .provider()
+            .meter("test")
+            .build_batch_observer(|batch| {
+                let reads = batch.u64_sum_observer("reads").try_init()?;
+                let writes = batch.u64_sum_observer("writes").try_init()?;
+
+                Ok(move |result: BatchObserverResult| {
+                    let run = batch_runs.fetch_add(1, atomic::Ordering::SeqCst) as u64 + 1;
+                    result.observe(
+                        &[KeyValue::new("disk", "a")],
+                        &[reads.observation(run), writes.observation(2 * run)],
+                    );
+                })
+            })
+            .unwrap();
+
+        controller.collect().unwrap();
+        let mut sums = Vec::new();
+        controller
+            .try_for_each(&ExportKind::Cumulative, &mut |record| {
+                let agg = record.aggregator().unwrap();
+                if let Some(sum) = agg.as_any().downcast_ref::<SumAggregator>() {
+                    sums.push((
+                        record.descriptor().name().to_string(),
+                        sum.sum()?.to_u64(&NumberKind::U64),
+                    ));
+                }
+                Ok(())
+            })
+            .unwrap();
+        sums.sort();
+
+        // Both instruments are observed by a single run of the callback.
+        assert_eq!(runs.load(atomic::Ordering::SeqCst), 1);
+        assert_eq!(
+            sums,
+            vec![("reads".to_string(), 1), ("writes".to_string(), 2)]
+        );
+    }
+
+    #[test]
+    fn batch_observer_callback() {
+        let mut controller =
+            controllers::pull(Box::new(Selector::Exact), Box::new(ExportKind::Cumulative))
+                .with_cache_period(Duration::from_secs(0))
+                .build();
+        let meter = controller.provider().meter("test");
+        let instrument = Arc::new(Mutex::new(None::<SumObserver<u64>>));
+        let observed = instrument.clone();
+
+        let batch = meter.batch_observer(Box::new(move |result| {
+            if let Some(reads) = observed.lock().unwrap().as_ref() {
+                result.observe(&[KeyValue::new("disk", "a")], &[reads.observation(4)]);
+            }
+        }));
+        *instrument.lock().unwrap() = Some(batch.u64_sum_observer("reads").init());
+
+        let mut expected = HashMap::new();
+        expected.insert("disk=a".to_string(), 4);
+        assert_eq!(
+            collect_sums(&mut controller, &ExportKind::Cumulative),
+            expected
+        );
+    }
+
+    #[test]
+    fn async_batch_observer() {
+        let mut controller =
+            controllers::pull(Box::new(Selector::Exact), Box::new(ExportKind::Cumulative))
+                .with_observer_timeout(Duration::from_millis(200))
+                .with_cache_period(Duration::from_secs(0))
+                .build();
+        let meter = controller.provider().meter("test");
+
+        meter
+            .build_batch_observer_async(|batch| {
+                let reads = batch.u64_sum_observer("reads").try_init()?;
+                let writes = batch.u64_sum_observer("writes").try_init()?;
+
+                Ok(move |result: BatchObserverResult| {
+                    let (sender, receiver) = futures::channel::oneshot::channel();
+                    thread::spawn(move || {
+                        thread::sleep(Duration::from_millis(10));
+                        let _ = sender.send(1);
+                    });
+                    let (reads, writes) = (reads.observation(1), writes.observation(2));
+                    async move {
+                        if receiver.await.is_ok() {
+                            result.observe(&[KeyValue::new("disk", "a")], &[reads, writes]);
+                        }
+                    }
+                })
+            })
+            .unwrap();
+        meter
+            .build_batch_observer_async(|batch| {
+                let stalled = batch.u64_sum_observer("stalled").try_init()?;
+
+                Ok(move |result: BatchObserverResult| {
+                    let observation = stalled.observation(3);
+                    async move {
+                        future::pending::<()>().await;
+                        result.observe(&[], &[observation]);
+                    }
+                })
+            })
+            .unwrap();
+
+        let start = Instant::now();
+        controller.collect().unwrap();
+        assert!(start.elapsed() < Duration::from_secs(5));
+        let mut sums = Vec::new();
+        controller
+            .try_for_each(&ExportKind::Cumulative, &mut |record| {
+                let agg = record.aggregator().unwrap();
+                if let Some(sum) = agg.as_any().downcast_ref::<SumAggregator>() {
+                    sums.push((
+                        record.descriptor().name().to_string(),
+                        sum.sum()?.to_u64(&NumberKind::U64),
+                    ));
+                }
+                Ok(())
+            })
+            .unwrap();
+        sums.sort();
+
+        // The delayed batch completes within the timeout, the stalled one is
+        // cancelled.
+        assert_eq!(
+            sums,
+            vec![("reads".to_string(), 1), ("writes".to_string(), 2)]
+        );
+    }
+
+    #[test]
+    fn spawned_async_observers() {
+        let mut runtime = tokio::runtime::Builder::new()
+            .basic_scheduler()
+            .enable_all()
+            .build()
+            .unwrap();
+        let handle = runtime.handle().clone();
+        let (stop, stopped) = futures::channel::oneshot::channel::<()>();
+        let runtime_thread = thread::spawn(move || runtime.block_on(stopped));
+
+        let mut controller =
+            controllers::pull(Box::new(Selector::Exact), Box::new(ExportKind::Cumulative))
+                .with_observer_timeout(Duration::from_millis(200))
+                .with_observer_spawner(move |future| {
+                    handle.spawn(future);
+                })
+                .with_cache_period(Duration::from_secs(0))
+                .build();
+        let meter = controller.provider().meter("test");
+
+        // Timers only fire while the runtime drives the callbacks.
+        meter
+            .u64_sum_observer_async("delayed", |result| async move {
+                tokio::time::delay_for(Duration::from_millis(10)).await;
+                result.observe(2, &[KeyValue::new("name", "delayed")]);
+            })
+            .init();
+        meter
+            .u64_sum_observer_async("stalled", |result| async move {
+                tokio::time::delay_for(Duration::from_secs(60)).await;
+                result.observe(1, &[KeyValue::new("name", "stalled")]);
+            })
+            .init();
+
+        let mut expected = HashMap::new();
+        expected.insert("name=delayed".to_string(), 2);
+        assert_eq!(
+            collect_sums(&mut controller, &ExportKind::Cumulative),
+            expected
+        );
+
+        stop.send(()).unwrap();
+        runtime_thread.join().unwrap().unwrap();
+    }
+
+    #[cfg(feature = "trace")]
+    #[test]
+    fn exemplar_filtered_labels() {
+        use crate::sdk::{export::metrics::Exemplars, trace as sdktrace};
+        use crate::trace::{TraceContextExt, Tracer, TracerProvider};
+        use crate::Key;
+
+        let mut controller =
+            controllers::pull(Box::new(Selector::Exact), Box::new(ExportKind::Cumulative))
+                .with_views(vec![View::new().with_label_keys(vec![Key::new("method")])])
+                .with_cache_period(Duration::from_secs(0))
+                .build();
+        let counter = controller.provider().meter("test").u64_counter("a").init();
+
+        let provider = sdktrace::TracerProvider::builder().build();
+        let tracer = provider.get_tracer("test", None);
+        let _guard = Context::current_with_span(tracer.start("request")).attach();
+        counter.add(
+            1,
+            &[KeyValue::new("method", "GET"), KeyValue::new("id", "1")],
+        );
+
+        controller.collect().unwrap();
+        let mut filtered_labels = Vec::new();
+        controller
+            .try_for_each(&ExportKind::Cumulative, &mut |record| {
+                let agg = record.aggregator().unwrap();
+                if let Some(sum) = agg.as_any().downcast_ref::<SumAggregator>() {
+                    for exemplar in sum.exemplars()? {
+                        filtered_labels.extend_from_slice(exemplar.filtered_labels());
+                    }
+                }
+                Ok(())
+            })
+            .unwrap();
+        assert_eq!(filtered_labels, vec![KeyValue::new("id", "1")]);
+    }
 }
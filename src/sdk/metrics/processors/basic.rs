@@ -1,9 +1,14 @@
+#[cfg(feature = "serialize")]
+use super::CheckpointSnapshot;
 use crate::sdk::{
     export::metrics::{
         self, Accumulation, Aggregator, AggregatorSelector, CheckpointSet, Checkpointer,
-        ExportKind, ExportKindSelector, LockedProcessor, Processor, Record, Subtractor,
+        ExportKind, ExportKindSelector, LockedProcessor, Processor, Record,
+    },
+    metrics::{
+        views::{View, ViewSelector},
+        Retention,
     },
-    metrics::aggregators::SumAggregator,
     Resource,
 };
 use crate::{
@@ -23,8 +28,9 @@ pub fn basic(
     memory: bool,
 ) -> BasicProcessor {
     BasicProcessor {
-        aggregator_selector,
+        aggregator_selector: ViewSelector::new(Vec::new(), aggregator_selector),
         export_selector,
+        retention: Retention::Forever,
         state: Mutex::new(BasicProcessorState::with_memory(memory)),
     }
 }
@@ -32,12 +38,37 @@ pub fn basic(
 /// Basic metric integration strategy
 #[derive(Debug)]
 pub struct BasicProcessor {
-    aggregator_selector: Box<dyn AggregatorSelector + Send + Sync>,
+    aggregator_selector: ViewSelector,
     export_selector: Box<dyn ExportKindSelector + Send + Sync>,
+    retention: Retention,
     state: Mutex<BasicProcessorState>,
 }
 
 impl BasicProcessor {
+    /// Export the instruments matched by the given views according to the
+    /// first matching view.
+    ///
+    /// Views select the aggregators of the instruments they match, and may
+    /// rename them or reduce the labels they are aggregated over.
+    pub fn with_views(self, views: Vec<View>) -> Self {
+        BasicProcessor {
+            aggregator_selector: ViewSelector::new(views, self.aggregator_selector.into_default()),
+            ..self
+        }
+    }
+
+    /// How long values without updates are remembered, `Retention::Forever`
+    /// by default.
+    ///
+    /// Values are remembered to compute the sums of stateful exports, and to
+    /// export them again if the processor has memory. The cumulative sums of
+    /// forgotten values start over, with a new start time, once they are
+    /// updated again. Configure the accumulator with the same retention so
+    /// that series are forgotten by both at once.
+    pub fn with_retention(self, retention: Retention) -> Self {
+        BasicProcessor { retention, ..self }
+    }
+
     /// Lock this processor to return a mutable locked processor
     pub fn lock(&self) -> Result<BasicLockedProcessor<'_>> {
         self.state
@@ -52,7 +83,7 @@ impl BasicProcessor {
 
 impl Processor for BasicProcessor {
     fn aggregation_selector(&self) -> &dyn AggregatorSelector {
-        self.aggregator_selector.as_ref()
+        &self.aggregator_selector
     }
 }
 
@@ -69,10 +100,20 @@ impl<'a> LockedProcessor for BasicLockedProcessor<'a> {
             return Err(MetricsError::InconsistentState);
         }
 
-        let desc = accumulation.descriptor();
+        // Aggregators are selected for the instrument, which is exported as
+        // described by its view.
+        let instrument = accumulation.descriptor();
+        let view = self.parent.aggregator_selector.view_for(instrument);
+        let view_desc = view.map(|view| view.descriptor(instrument));
+        let desc = view_desc.as_ref().unwrap_or(instrument);
+        let view_labels = view.map(|view| view.labels(accumulation.labels()));
+        let labels = view_labels
+            .as_ref()
+            .unwrap_or_else(|| accumulation.labels());
+
         let mut hasher = FnvHasher::default();
         desc.attribute_hash().hash(&mut hasher);
-        hash_labels(&mut hasher, accumulation.labels().into_iter());
+        hash_labels(&mut hasher, labels.into_iter());
         hash_labels(&mut hasher, accumulation.resource().into_iter());
         let key = StateKey(hasher.finish());
         let agg = accumulation.aggregator();
@@ -81,6 +122,7 @@ impl<'a> LockedProcessor for BasicLockedProcessor<'a> {
             // Advance the update sequence number.
             let same_collection = finished_collection == value.updated;
             value.updated = finished_collection;
+            value.updated_at = SystemTime::now();
 
             // At this point in the code, we have located an existing
             // value for some stateKey.  This can be because:
@@ -127,7 +169,11 @@ impl<'a> LockedProcessor for BasicLockedProcessor<'a> {
             // before merging below.
             if !value.current_owned {
                 let tmp = value.current.clone();
-                if let Some(current) = self.parent.aggregation_selector().aggregator_for(desc) {
+                if let Some(current) = self
+                    .parent
+                    .aggregation_selector()
+                    .aggregator_for(instrument)
+                {
                     value.current = current;
                     value.current_owned = true;
                     tmp.synchronized_move(&value.current, &desc)?;
@@ -148,19 +194,25 @@ impl<'a> LockedProcessor for BasicLockedProcessor<'a> {
         let cumulative = if stateful {
             if desc.instrument_kind().precomputed_sum() {
                 // If we know we need to compute deltas, allocate one.
-                delta = self.parent.aggregation_selector().aggregator_for(desc);
+                delta = self
+                    .parent
+                    .aggregation_selector()
+                    .aggregator_for(instrument);
             }
             // Always allocate a cumulative aggregator if stateful
-            self.parent.aggregation_selector().aggregator_for(desc)
+            self.parent
+                .aggregation_selector()
+                .aggregator_for(instrument)
         } else {
             None
         };
 
+        let interval_start = self.state.interval_start;
         self.state.values.insert(
             key,
             StateValue {
                 descriptor: desc.clone(),
-                labels: accumulation.labels().clone(),
+                labels: labels.clone(),
                 resource: accumulation.resource().clone(),
                 current_owned: false,
                 current: agg.clone(),
@@ -168,6 +220,8 @@ impl<'a> LockedProcessor for BasicLockedProcessor<'a> {
                 cumulative,
                 stateful,
                 updated: finished_collection,
+                updated_at: SystemTime::now(),
+                start: interval_start,
             },
         );
 
@@ -175,6 +229,33 @@ impl<'a> LockedProcessor for BasicLockedProcessor<'a> {
     }
 }
 
+#[cfg(feature = "serialize")]
+impl BasicLockedProcessor<'_> {
+    /// Merge the records of a snapshot into the current collection, as if
+    /// they were accumulated in this process.
+    ///
+    /// Like accumulations, snapshots are merged between `start_collection`
+    /// and `finish_collection`. Each record is merged into an aggregator
+    /// selected by this processor with `Aggregator::merge`, which fails for
+    /// incompatible aggregators.
+    pub fn merge_snapshot(&mut self, snapshot: CheckpointSnapshot) -> Result<()> {
+        snapshot.try_for_each(|descriptor, labels, resource, aggregator| {
+            let current = match self
+                .parent
+                .aggregation_selector()
+                .aggregator_for(descriptor)
+            {
+                Some(current) => current,
+                None => return Ok(()),
+            };
+            current.merge(aggregator, descriptor)?;
+            self.process(metrics::accumulation(
+                descriptor, labels, resource, &current,
+            ))
+        })
+    }
+}
+
 impl Checkpointer for BasicLockedProcessor<'_> {
     fn checkpoint_set(&mut self) -> &mut dyn CheckpointSet {
         &mut *self.state
@@ -195,6 +276,8 @@ impl Checkpointer for BasicLockedProcessor<'_> {
         let finished_collection = self.state.finished_collection;
         self.state.finished_collection = self.state.finished_collection.wrapping_add(1);
         let has_memory = self.state.config.memory;
+        let retention = self.parent.retention;
+        let interval_end = self.state.interval_end;
 
         let mut result = Ok(());
 
@@ -209,23 +292,31 @@ impl Checkpointer for BasicLockedProcessor<'_> {
             let stale = value.updated != finished_collection;
             let stateless = !value.stateful;
 
+            // Forget values without updates for longer than the retention.
+            if stale
+                && retention.expired(
+                    finished_collection.wrapping_sub(value.updated),
+                    interval_end
+                        .duration_since(value.updated_at)
+                        .unwrap_or_default(),
+                )
+            {
+                return false;
+            }
+
             // The following branch updates stateful aggregators. Skip these updates
             // if the aggregator is not stateful or if the aggregator is stale.
             if stale || stateless {
                 // If this processor does not require memory, stale, stateless
                 // entries can be removed. This implies that they were not updated
                 // over the previous full collection interval.
-                if stale && stateless && has_memory {
-                    return false;
-                }
+                return !(stale && stateless && !has_memory);
             }
 
             // Update Aggregator state to support exporting either a
             // delta or a cumulative aggregation.
             if mkind.precomputed_sum() {
-                if let Some(current_subtractor) =
-                    value.current.as_any().downcast_ref::<SumAggregator>()
-                {
+                if let Some(current_subtractor) = value.current.as_subtractor() {
                     // This line is equivalent to:
                     // value.delta = currentSubtractor - value.cumulative
                     if let (Some(cumulative), Some(delta)) =
@@ -322,7 +413,16 @@ impl CheckpointSet for BasicProcessorState {
                 return Ok(());
             }
 
-            match exporter.export_kind_for(&value.descriptor) {
+            let export_kind = exporter.export_kind_for(&value.descriptor);
+            if export_kind.memory_required(instrument_kind) && !value.stateful {
+                return Err(MetricsError::InconsistentExportKind(format!(
+                    "{:?} export of {} requires a processor configured for it",
+                    export_kind,
+                    value.descriptor.name()
+                )));
+            }
+
+            match export_kind {
                 ExportKind::PassThrough => {
                     // No state is required, pass through the checkpointed value.
                     agg = Some(&value.current);
@@ -340,14 +440,20 @@ impl CheckpointSet for BasicProcessorState {
                     // checkpointed value:
                     if value.stateful {
                         agg = value.cumulative.as_ref();
+                        start = value.start;
                     } else {
                         agg = Some(&value.current);
+                        start = self.process_start;
                     }
-
-                    start = self.process_start;
                 }
 
                 ExportKind::Delta => {
+                    // Values remembered from earlier collections have no
+                    // delta in the last interval.
+                    if value.updated != self.finished_collection.wrapping_sub(1) {
+                        return Ok(());
+                    }
+
                     // Precomputed sums are a special case.
                     if instrument_kind.precomputed_sum() {
                         agg = value.delta.as_ref();
@@ -394,6 +500,15 @@ struct StateValue {
     /// accumulator.
     updated: u64,
 
+    /// The time of the last update, supports forgetting values once their
+    /// retention expired.
+    updated_at: SystemTime,
+
+    /// The start of the collection this value was first processed in, which
+    /// starts its cumulative sum. Values forgotten and processed again start
+    /// a new sum.
+    start: SystemTime,
+
     /// Indicates that a cumulative aggregation is being maintained, taken from the
     /// process start time.
     stateful: bool,
@@ -420,3 +535,251 @@ struct StateValue {
     /// the last cumulative value.
     cumulative: Option<Arc<dyn Aggregator + Send + Sync>>,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::metrics::{MeterProvider, NumberKind};
+    use crate::sdk::{
+        export::metrics::Sum,
+        metrics::{
+            aggregators::SumAggregator,
+            controllers::{self, PullController},
+            selectors::{export_kind, simple::Selector},
+        },
+    };
+    use crate::KeyValue;
+    use std::sync::atomic::{AtomicU64, Ordering};
+    use std::time::Duration;
+
+    fn controller(export_selector: Box<dyn ExportKindSelector + Send + Sync>) -> PullController {
+        controllers::pull(Box::new(Selector::Exact), export_selector)
+            .with_cache_period(Duration::from_secs(0))
+            .with_memory(false)
+            .build()
+    }
+
+    fn collect_sums(
+        controller: &mut PullController,
+        export_selector: &dyn ExportKindSelector,
+    ) -> Result<HashMap<String, i64>> {
+        controller.collect()?;
+
+        let mut sums = HashMap::new();
+        controller.try_for_each(export_selector, &mut |record| {
+            let kind = record.descriptor().number_kind();
+            let sum = record
+                .aggregator()
+                .and_then(|agg| agg.as_any().downcast_ref::<SumAggregator>())
+                .ok_or(MetricsError::NoDataCollected)?;
+            sums.insert(
+                record.descriptor().name().to_string(),
+                sum.sum()?.to_i64(kind),
+            );
+            Ok(())
+        })?;
+        Ok(sums)
+    }
+
+    #[test]
+    fn precomputed_sums_as_deltas() {
+        let mut controller = controller(Box::new(ExportKind::Delta));
+        let observed = Arc::new(AtomicU64::new(10));
+        let value = observed.clone();
+        let _observer = controller
+            .provider()
+            .meter("test")
+            .u64_sum_observer("bytes", move |result| {
+                result.observe(value.load(Ordering::SeqCst), &[KeyValue::new("k", "v")])
+            })
+            .init();
+
+        for (total, delta) in &[(10, 10), (15, 5), (15, 0), (40, 25)] {
+            observed.store(*total, Ordering::SeqCst);
+            let sums = collect_sums(&mut controller, &ExportKind::Delta).unwrap();
+            assert_eq!(sums.get("bytes"), Some(delta), "total {}", total);
+        }
+    }
+
+    #[test]
+    fn delta_counters_cumulative_gauges() {
+        let selector = export_kind::Selector::DeltaCounters;
+        let mut controller = controller(Box::new(selector.clone()));
+        let meter = controller.provider().meter("test");
+        let counter = meter.u64_counter("requests").init();
+        let gauge = meter.i64_up_down_counter("connections").init();
+
+        counter.add(3, &[]);
+        gauge.add(3, &[]);
+        let sums = collect_sums(&mut controller, &selector).unwrap();
+        assert_eq!(sums.get("requests"), Some(&3));
+        assert_eq!(sums.get("connections"), Some(&3));
+
+        counter.add(2, &[]);
+        gauge.add(-1, &[]);
+        let sums = collect_sums(&mut controller, &selector).unwrap();
+        assert_eq!(sums.get("requests"), Some(&2));
+        assert_eq!(sums.get("connections"), Some(&2));
+    }
+
+    #[test]
+    fn recreated_cumulative_sums_restart() {
+        let mut controller =
+            controllers::pull(Box::new(Selector::Exact), Box::new(ExportKind::Cumulative))
+                .with_cache_period(Duration::from_secs(0))
+                .with_retention(Retention::Collections(1))
+                .build();
+        let counter = controller
+            .provider()
+            .meter("test")
+            .u64_counter("requests")
+            .init();
+        let sums = |controller: &mut PullController| {
+            controller.collect().unwrap();
+            let mut sums = Vec::new();
+            controller
+                .try_for_each(&ExportKind::Cumulative, &mut |record| {
+                    if let Some(sum) = record
+                        .aggregator()
+                        .and_then(|agg| agg.as_any().downcast_ref::<SumAggregator>())
+                    {
+                        sums.push((sum.sum()?.to_u64(&NumberKind::U64), *record.start_time()));
+                    }
+                    Ok(())
+                })
+                .unwrap();
+            sums
+        };
+
+        counter.add(3, &[]);
+        let first = sums(&mut controller);
+        assert_eq!(first.len(), 1);
+        assert_eq!(first[0].0, 3);
+
+        // Forgotten after collections without updates.
+        for _ in 0..2 {
+            controller.collect().unwrap();
+        }
+        assert!(sums(&mut controller).is_empty());
+
+        // Recreated with a new cumulative sum and start time.
+        counter.add(2, &[]);
+        let recreated = sums(&mut controller);
+        assert_eq!(recreated.len(), 1);
+        assert_eq!(recreated[0].0, 2);
+        assert!(recreated[0].1 > first[0].1);
+    }
+
+    #[test]
+    fn inconsistent_export_kind() {
+        let mut controller = controller(Box::new(ExportKind::Delta));
+        let counter = controller
+            .provider()
+            .meter("test")
+            .u64_counter("requests")
+            .init();
+        counter.add(1, &[]);
+        controller.collect().unwrap();
+
+        // Cumulative sums of delta instruments require the processor to be
+        // configured for them.
+        let result = controller.try_for_each(&ExportKind::Cumulative, &mut |_| Ok(()));
+        assert!(matches!(
+            result,
+            Err(MetricsError::InconsistentExportKind(_))
+        ));
+
+        let mut exported = 0;
+        controller
+            .try_for_each(&export_kind::Selector::Stateless, &mut |_| {
+                exported += 1;
+                Ok(())
+            })
+            .unwrap();
+        assert_eq!(exported, 1);
+    }
+
+    #[cfg(feature = "serialize")]
+    #[test]
+    fn merge_snapshots() {
+        use crate::sdk::export::metrics::Histogram;
+        use crate::sdk::metrics::{
+            aggregators::HistogramAggregator, processors::CheckpointSnapshot,
+        };
+
+        let boundaries = vec![10.0];
+        let mut snapshots = Vec::new();
+        for (requests, latency) in &[(3, 5.0), (4, 50.0)] {
+            let mut worker = controllers::pull(
+                Box::new(Selector::Histogram(boundaries.clone())),
+                Box::new(ExportKind::Delta),
+            )
+            .with_cache_period(Duration::from_secs(0))
+            .build();
+            let meter = worker.provider().meter("worker");
+            meter.u64_counter("requests").init().add(*requests, &[]);
+            meter
+                .f64_value_recorder("latency")
+                .init()
+                .record(*latency, &[KeyValue::new("k", "v")]);
+            worker.collect().unwrap();
+
+            let snapshot = CheckpointSnapshot::new(&mut worker).unwrap();
+            assert_eq!(snapshot.len(), 2);
+            snapshots.push(bincode::serialize(&snapshot).unwrap());
+        }
+
+        let processor = basic(
+            Box::new(Selector::Histogram(boundaries)),
+            Box::new(ExportKind::Cumulative),
+            true,
+        );
+        let mut checkpointer = processor.lock().unwrap();
+        checkpointer.start_collection();
+        for bytes in &snapshots {
+            checkpointer
+                .merge_snapshot(bincode::deserialize(bytes).unwrap())
+                .unwrap();
+        }
+        checkpointer.finish_collection().unwrap();
+
+        let mut merged = 0;
+        checkpointer
+            .checkpoint_set()
+            .try_for_each(&ExportKind::Cumulative, &mut |record| {
+                let kind = record.descriptor().number_kind();
+                let aggregator = record.aggregator().unwrap().as_any();
+                match record.descriptor().name() {
+                    "requests" => {
+                        let sum = aggregator.downcast_ref::<SumAggregator>().unwrap();
+                        assert_eq!(sum.sum()?.to_u64(kind), 7);
+                    }
+                    "latency" => {
+                        let histogram = aggregator.downcast_ref::<HistogramAggregator>().unwrap();
+                        assert_eq!(histogram.histogram()?.counts(), &vec![1.0, 1.0]);
+                        assert_eq!(histogram.sum()?.to_f64(kind), 55.0);
+                        assert_eq!(record.labels().len(), 1);
+                    }
+                    name => panic!("unexpected record {}", name),
+                }
+                merged += 1;
+                Ok(())
+            })
+            .unwrap();
+        assert_eq!(merged, 2);
+
+        // Histograms with other boundaries cannot be merged.
+        let processor = basic(
+            Box::new(Selector::Histogram(vec![1.0, 100.0])),
+            Box::new(ExportKind::Cumulative),
+            true,
+        );
+        let mut checkpointer = processor.lock().unwrap();
+        checkpointer.start_collection();
+        let result = checkpointer.merge_snapshot(bincode::deserialize(&snapshots[0]).unwrap());
+        assert!(matches!(
+            result,
+            Err(MetricsError::InconsistentAggregator(_))
+        ));
+    }
+}
@@ -1,4 +1,9 @@
 //! Metric Processors
 mod basic;
+#[cfg(feature = "serialize")]
+mod snapshot;
 
-pub use basic::{basic, BasicProcessor};
+pub use basic::{basic, BasicLockedProcessor, BasicProcessor};
+#[cfg(feature = "serialize")]
+#[cfg_attr(docsrs, doc(cfg(feature = "serialize")))]
+pub use snapshot::CheckpointSnapshot;
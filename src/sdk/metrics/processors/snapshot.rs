@@ -0,0 +1,243 @@
+//! Serializable snapshots of checkpoint sets.
+use crate::labels::LabelSet;
+use crate::metrics::{Descriptor, InstrumentKind, MetricsError, NumberKind, Result};
+use crate::sdk::{
+    export::metrics::{Aggregator, CheckpointSet},
+    metrics::{
+        aggregators::{
+            ArrayAggregator, DDSKetchAggregator, ExponentialHistogramAggregator,
+            HistogramAggregator, LastValueAggregator, MinMaxSumCountAggregator, SumAggregator,
+        },
+        selectors::export_kind,
+    },
+    Resource,
+};
+use crate::{Key, KeyValue, Unit, Value};
+use serde::{ser, Deserialize, Deserializer, Serialize, Serializer};
+use std::result;
+use std::sync::Arc;
+
+/// The records of a `CheckpointSet`, serialized to aggregate metrics across
+/// processes or to persist them across restarts.
+///
+/// A snapshot is merged into another processor with
+/// [`BasicLockedProcessor::merge_snapshot`]. Its records are captured the way
+/// accumulators hand them to processors: the synchronous instruments and
+/// `ValueObserver`s as the delta of the last collection, and the
+/// `SumObserver`s and `UpDownSumObserver`s as cumulative sums.
+///
+/// The snapshot shares the aggregators of the checkpoint set, serialize it
+/// before the next collection.
+///
+/// ```
+/// use opentelemetry::metrics::MeterProvider;
+/// use opentelemetry::sdk::{
+///     export::metrics::{Checkpointer, ExportKind},
+///     metrics::{controllers, processors::{self, CheckpointSnapshot}, selectors::simple::Selector},
+/// };
+/// use std::time::Duration;
+///
+/// // Pre-aggregate in a worker.
+/// let mut worker = controllers::pull(Box::new(Selector::Exact), Box::new(ExportKind::Delta))
+///     .with_cache_period(Duration::from_secs(0))
+///     .build();
+/// let counter = worker.provider().meter("worker").u64_counter("jobs").init();
+/// counter.add(3, &[]);
+/// worker.collect().unwrap();
+/// let bytes = bincode::serialize(&CheckpointSnapshot::new(&mut worker).unwrap()).unwrap();
+///
+/// // Merge in the parent.
+/// let processor = processors::basic(
+///     Box::new(Selector::Exact),
+///     Box::new(ExportKind::Cumulative),
+///     true,
+/// );
+/// let mut checkpointer = processor.lock().unwrap();
+/// checkpointer.start_collection();
+/// checkpointer
+///     .merge_snapshot(bincode::deserialize(&bytes).unwrap())
+///     .unwrap();
+/// checkpointer.finish_collection().unwrap();
+/// ```
+///
+/// [`BasicLockedProcessor::merge_snapshot`]: struct.BasicLockedProcessor.html#method.merge_snapshot
+#[derive(Debug, Default, Deserialize, Serialize)]
+pub struct CheckpointSnapshot {
+    records: Vec<RecordSnapshot>,
+}
+
+impl CheckpointSnapshot {
+    /// Capture the records of the checkpoint set.
+    ///
+    /// This fails if a record is aggregated by an aggregator that is not
+    /// built into the SDK.
+    pub fn new(checkpoint_set: &mut dyn CheckpointSet) -> Result<Self> {
+        let mut records = Vec::new();
+        checkpoint_set.try_for_each(&export_kind::Selector::Stateless, &mut |record| {
+            let aggregator = match record.aggregator() {
+                Some(aggregator) => aggregator,
+                None => return Ok(()),
+            };
+            let descriptor = record.descriptor();
+            if AggregatorRef::from_aggregator(aggregator.as_ref()).is_none() {
+                return Err(MetricsError::Other(format!(
+                    "{} is aggregated by {:?}, which cannot be serialized",
+                    descriptor.name(),
+                    aggregator
+                )));
+            }
+
+            records.push(RecordSnapshot {
+                name: descriptor.name().to_string(),
+                instrumentation_name: descriptor.instrumentation_name().to_string(),
+                instrument_kind: descriptor.instrument_kind().clone(),
+                number_kind: descriptor.number_kind().clone(),
+                description: descriptor.description().cloned(),
+                unit: descriptor.unit().map(ToString::to_string),
+                labels: key_values(record.labels().iter()),
+                resource: key_values(record.resource().iter()),
+                aggregator: aggregator.clone(),
+            });
+            Ok(())
+        })?;
+
+        Ok(CheckpointSnapshot { records })
+    }
+
+    /// The number of records in this snapshot.
+    pub fn len(&self) -> usize {
+        self.records.len()
+    }
+
+    /// Whether this snapshot has no records.
+    pub fn is_empty(&self) -> bool {
+        self.records.is_empty()
+    }
+
+    /// Visit each record with its descriptor, labels, resource and aggregator.
+    pub(crate) fn try_for_each<F>(self, mut f: F) -> Result<()>
+    where
+        F: FnMut(&Descriptor, &LabelSet, &Resource, &(dyn Aggregator + Send + Sync)) -> Result<()>,
+    {
+        self.records.into_iter().try_for_each(|record| {
+            let mut descriptor = Descriptor::new(
+                record.name,
+                record.instrumentation_name,
+                record.instrument_kind,
+                record.number_kind,
+            );
+            if let Some(description) = record.description {
+                descriptor.set_description(description);
+            }
+            if let Some(unit) = record.unit {
+                descriptor.set_unit(Unit::new(unit));
+            }
+
+            f(
+                &descriptor,
+                &LabelSet::from_labels(record.labels),
+                &Resource::new(record.resource),
+                record.aggregator.as_ref(),
+            )
+        })
+    }
+}
+
+fn key_values<'a, I>(iter: I) -> Vec<KeyValue>
+where
+    I: Iterator<Item = (&'a Key, &'a Value)>,
+{
+    iter.map(|(key, value)| KeyValue::new(key.clone(), value.clone()))
+        .collect()
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+struct RecordSnapshot {
+    name: String,
+    instrumentation_name: String,
+    instrument_kind: InstrumentKind,
+    number_kind: NumberKind,
+    description: Option<String>,
+    unit: Option<String>,
+    labels: Vec<KeyValue>,
+    resource: Vec<KeyValue>,
+    #[serde(
+        serialize_with = "serialize_aggregator",
+        deserialize_with = "deserialize_aggregator"
+    )]
+    aggregator: Arc<dyn Aggregator + Send + Sync>,
+}
+
+/// The built-in aggregators, serialized with the name of their type.
+#[derive(Serialize)]
+enum AggregatorRef<'a> {
+    Sum(&'a SumAggregator),
+    LastValue(&'a LastValueAggregator),
+    MinMaxSumCount(&'a MinMaxSumCountAggregator),
+    Histogram(&'a HistogramAggregator),
+    ExponentialHistogram(&'a ExponentialHistogramAggregator),
+    DDSketch(&'a DDSKetchAggregator),
+    Array(&'a ArrayAggregator),
+}
+
+impl<'a> AggregatorRef<'a> {
+    fn from_aggregator(aggregator: &'a (dyn Aggregator + Send + Sync)) -> Option<Self> {
+        let any = aggregator.as_any();
+        if let Some(sum) = any.downcast_ref() {
+            Some(AggregatorRef::Sum(sum))
+        } else if let Some(last_value) = any.downcast_ref() {
+            Some(AggregatorRef::LastValue(last_value))
+        } else if let Some(mmsc) = any.downcast_ref() {
+            Some(AggregatorRef::MinMaxSumCount(mmsc))
+        } else if let Some(histogram) = any.downcast_ref() {
+            Some(AggregatorRef::Histogram(histogram))
+        } else if let Some(histogram) = any.downcast_ref() {
+            Some(AggregatorRef::ExponentialHistogram(histogram))
+        } else if let Some(sketch) = any.downcast_ref() {
+            Some(AggregatorRef::DDSketch(sketch))
+        } else if let Some(array) = any.downcast_ref() {
+            Some(AggregatorRef::Array(array))
+        } else {
+            None
+        }
+    }
+}
+
+/// The deserialized counterpart of `AggregatorRef`.
+#[derive(Deserialize)]
+enum OwnedAggregator {
+    Sum(SumAggregator),
+    LastValue(LastValueAggregator),
+    MinMaxSumCount(MinMaxSumCountAggregator),
+    Histogram(HistogramAggregator),
+    ExponentialHistogram(ExponentialHistogramAggregator),
+    DDSketch(DDSKetchAggregator),
+    Array(ArrayAggregator),
+}
+
+fn serialize_aggregator<S: Serializer>(
+    aggregator: &Arc<dyn Aggregator + Send + Sync>,
+    serializer: S,
+) -> result::Result<S::Ok, S::Error> {
+    match AggregatorRef::from_aggregator(aggregator.as_ref()) {
+        Some(aggregator) => aggregator.serialize(serializer),
+        None => Err(ser::Error::custom(format!(
+            "{:?} cannot be serialized",
+            aggregator
+        ))),
+    }
+}
+
+fn deserialize_aggregator<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> result::Result<Arc<dyn Aggregator + Send + Sync>, D::Error> {
+    Ok(match OwnedAggregator::deserialize(deserializer)? {
+        OwnedAggregator::Sum(aggregator) => Arc::new(aggregator),
+        OwnedAggregator::LastValue(aggregator) => Arc::new(aggregator),
+        OwnedAggregator::MinMaxSumCount(aggregator) => Arc::new(aggregator),
+        OwnedAggregator::Histogram(aggregator) => Arc::new(aggregator),
+        OwnedAggregator::ExponentialHistogram(aggregator) => Arc::new(aggregator),
+        OwnedAggregator::DDSketch(aggregator) => Arc::new(aggregator),
+        OwnedAggregator::Array(aggregator) => Arc::new(aggregator),
+    })
+}
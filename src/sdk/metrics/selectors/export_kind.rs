@@ -0,0 +1,44 @@
+//! Export Kind Selectors
+use crate::metrics::{Descriptor, InstrumentKind};
+use crate::sdk::export::metrics::{ExportKind, ExportKindSelector};
+
+/// Export kind selection strategies, choosing the export kind per instrument.
+///
+/// Use `ExportKind` itself as selector to export all instruments alike.
+#[derive(Clone, Debug)]
+pub enum Selector {
+    /// Exports each instrument in the kind that does not require the processor
+    /// to keep state: deltas for the synchronous instruments and
+    /// `ValueObserver`s, and cumulative sums for the `SumObserver`s and
+    /// `UpDownSumObserver`s.
+    Stateless,
+    /// Exports monotonic sums and distributions as deltas, and gauges
+    /// cumulatively, as expected by StatsD-like backends.
+    ///
+    /// `Counter`s, `SumObserver`s and `ValueRecorder`s are exported as deltas,
+    /// `UpDownCounter`s, `UpDownSumObserver`s and `ValueObserver`s as their
+    /// current value.
+    DeltaCounters,
+}
+
+impl ExportKindSelector for Selector {
+    fn export_kind_for(&self, descriptor: &Descriptor) -> ExportKind {
+        match self {
+            Selector::Stateless => {
+                if descriptor.instrument_kind().precomputed_sum() {
+                    ExportKind::Cumulative
+                } else {
+                    ExportKind::Delta
+                }
+            }
+            Selector::DeltaCounters => match descriptor.instrument_kind() {
+                InstrumentKind::Counter
+                | InstrumentKind::SumObserver
+                | InstrumentKind::ValueRecorder => ExportKind::Delta,
+                InstrumentKind::UpDownCounter
+                | InstrumentKind::UpDownSumObserver
+                | InstrumentKind::ValueObserver => ExportKind::Cumulative,
+            },
+        }
+    }
+}
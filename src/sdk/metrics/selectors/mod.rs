@@ -1,2 +1,3 @@
 //! Metric Selectors
+pub mod export_kind;
 pub mod simple;
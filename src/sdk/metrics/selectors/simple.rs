@@ -24,6 +24,10 @@ pub enum Selector {
     /// for metrics. This selector uses more memory than `Inexpensive` because
     /// it uses a counter per bucket.
     Histogram(Vec<f64>),
+    /// A simple aggregation selector that uses sum, and exponential histogram
+    /// aggregators with at most the given number of buckets for metrics. Unlike
+    /// `Histogram`, the bucket boundaries adapt to the recorded values.
+    ExponentialHistogram(usize),
 }
 
 impl AggregatorSelector for Selector {
@@ -56,6 +60,13 @@ impl AggregatorSelector for Selector {
                 }
                 _ => Some(Arc::new(aggregators::sum())),
             },
+            Selector::ExponentialHistogram(max_size) => match descriptor.instrument_kind() {
+                InstrumentKind::ValueObserver => Some(Arc::new(aggregators::last_value())),
+                InstrumentKind::ValueRecorder => Some(Arc::new(
+                    aggregators::exponential_histogram(descriptor, *max_size),
+                )),
+                _ => Some(Arc::new(aggregators::sum())),
+            },
         }
     }
 }
@@ -0,0 +1,483 @@
+//! Metric Views
+//!
+//! Views customize how the instruments they match are aggregated and
+//! exported, without changing the instrumentation itself. A view can rename
+//! an instrument, replace its description, choose its aggregator, restrict
+//! the label keys it is aggregated over and label its measurements with
+//! entries of the baggage they are recorded in.
+//!
+//! ```
+//! use opentelemetry::metrics::InstrumentKind;
+//! use opentelemetry::sdk::metrics::{selectors::simple::Selector, views::View};
+//! use opentelemetry::Key;
+//!
+//! let views = vec![
+//!     // Drop the high-cardinality `user.id` label of a third-party library.
+//!     View::new()
+//!         .with_meter_name("third.party")
+//!         .with_instrument_name("requests")
+//!         .with_label_keys(vec![Key::new("method")]),
+//!     // Record latencies in custom buckets.
+//!     View::new()
+//!         .with_instrument_name("*.duration")
+//!         .with_instrument_kind(InstrumentKind::ValueRecorder)
+//!         .with_aggregator_selector(Selector::Histogram(vec![0.1, 0.5, 1.0])),
+//!     // Label all other instruments with the tenant propagated in the baggage.
+//!     View::new().with_baggage_keys(vec![Key::new("tenant.id")]),
+//! ];
+//! ```
+use crate::labels::LabelSet;
+use crate::metrics::{Descriptor, InstrumentKind};
+use crate::sdk::export::metrics::{Aggregator, AggregatorSelector};
+use crate::{Key, KeyValue, Unit};
+use std::sync::Arc;
+
+/// A view selects instruments by instrument name, meter name and instrument
+/// kind, and overrides how they are exported.
+///
+/// A view without any selection criteria matches all instruments. Instrument
+/// names may contain `*` wildcards, matching any sequence of characters.
+#[derive(Clone, Debug, Default)]
+pub struct View {
+    instrument_name: Option<String>,
+    meter_name: Option<String>,
+    instrument_kind: Option<InstrumentKind>,
+    name: Option<String>,
+    description: Option<String>,
+    aggregator_selector: Option<Arc<dyn AggregatorSelector + Send + Sync>>,
+    label_keys: Option<Vec<Key>>,
+    baggage_keys: Option<Vec<Key>>,
+}
+
+impl View {
+    /// Create a new view matching all instruments.
+    pub fn new() -> Self {
+        View::default()
+    }
+
+    /// Match instruments with the given name, which may contain `*` wildcards.
+    pub fn with_instrument_name<T: Into<String>>(self, pattern: T) -> Self {
+        View {
+            instrument_name: Some(pattern.into()),
+            ..self
+        }
+    }
+
+    /// Match instruments created by the meter with the given name.
+    pub fn with_meter_name<T: Into<String>>(self, name: T) -> Self {
+        View {
+            meter_name: Some(name.into()),
+            ..self
+        }
+    }
+
+    /// Match instruments of the given kind.
+    pub fn with_instrument_kind(self, kind: InstrumentKind) -> Self {
+        View {
+            instrument_kind: Some(kind),
+            ..self
+        }
+    }
+
+    /// Export the matching instruments under a new name.
+    pub fn with_name<T: Into<String>>(self, name: T) -> Self {
+        View {
+            name: Some(name.into()),
+            ..self
+        }
+    }
+
+    /// Export the matching instruments with a new description.
+    pub fn with_description<T: Into<String>>(self, description: T) -> Self {
+        View {
+            description: Some(description.into()),
+            ..self
+        }
+    }
+
+    /// Select the aggregators of the matching instruments with the given
+    /// selector.
+    pub fn with_aggregator_selector<T>(self, selector: T) -> Self
+    where
+        T: AggregatorSelector + Send + Sync + 'static,
+    {
+        View {
+            aggregator_selector: Some(Arc::new(selector)),
+            ..self
+        }
+    }
+
+    /// Aggregate the matching instruments over the given label keys only,
+    /// dropping all other labels.
+    pub fn with_label_keys(self, keys: Vec<Key>) -> Self {
+        View {
+            label_keys: Some(keys),
+            ..self
+        }
+    }
+
+    /// Label the measurements of the matching instruments with the values of
+    /// the given keys in the baggage of the context they are recorded in.
+    ///
+    /// Measurements take the baggage of the current context, or of the
+    /// context given to `Meter::record_batch_with_context`. Bound instruments
+    /// take the baggage of the context they are bound in. Labels given
+    /// explicitly take precedence over baggage entries with the same key.
+    pub fn with_baggage_keys(self, keys: Vec<Key>) -> Self {
+        View {
+            baggage_keys: Some(keys),
+            ..self
+        }
+    }
+
+    /// The baggage keys added to the labels of the matching instruments.
+    pub(crate) fn baggage_keys(&self) -> Option<&Vec<Key>> {
+        self.baggage_keys.as_ref()
+    }
+
+    /// Whether this view applies to the given instrument.
+    pub fn matches(&self, descriptor: &Descriptor) -> bool {
+        self.instrument_name
+            .as_ref()
+            .map_or(true, |pattern| matches(pattern, descriptor.name()))
+            && self
+                .meter_name
+                .as_ref()
+                .map_or(true, |name| name == descriptor.instrumentation_name())
+            && self
+                .instrument_kind
+                .as_ref()
+                .map_or(true, |kind| kind == descriptor.instrument_kind())
+    }
+
+    /// The descriptor the given instrument is exported with.
+    pub fn descriptor(&self, descriptor: &Descriptor) -> Descriptor {
+        let mut exported = match self.name.as_ref() {
+            Some(name) => {
+                let mut renamed = Descriptor::new(
+                    name.clone(),
+                    descriptor.instrumentation_name().to_string(),
+                    descriptor.instrument_kind().clone(),
+                    descriptor.number_kind().clone(),
+                );
+                if let Some(description) = descriptor.description() {
+                    renamed.set_description(description.clone());
+                }
+                if let Some(unit) = descriptor.unit() {
+                    renamed.set_unit(Unit::new(unit));
+                }
+                renamed
+            }
+            None => descriptor.clone(),
+        };
+
+        if let Some(description) = self.description.as_ref() {
+            exported.set_description(description.clone());
+        }
+
+        exported
+    }
+
+    /// The labels the given label set is aggregated over, which include the
+    /// baggage keys of this view.
+    pub fn labels(&self, labels: &LabelSet) -> LabelSet {
+        match self.label_keys.as_ref() {
+            Some(_) => LabelSet::from_labels(
+                labels
+                    .iter()
+                    .filter(|(key, _)| self.keeps(key))
+                    .map(|(key, value)| KeyValue::new(key.clone(), value.clone())),
+            ),
+            None => labels.clone(),
+        }
+    }
+
+    /// The labels of the given label set this view drops.
+    pub fn filtered_labels(&self, labels: &LabelSet) -> Vec<KeyValue> {
+        labels
+            .iter()
+            .filter(|(key, _)| !self.keeps(key))
+            .map(|(key, value)| KeyValue::new(key.clone(), value.clone()))
+            .collect()
+    }
+
+    fn keeps(&self, key: &Key) -> bool {
+        self.label_keys.as_ref().map_or(true, |keys| {
+            keys.contains(key)
+                || self
+                    .baggage_keys
+                    .as_ref()
+                    .map_or(false, |baggage_keys| baggage_keys.contains(key))
+        })
+    }
+}
+
+/// An `AggregatorSelector` choosing the aggregators of the instruments matched
+/// by a view with the view's selector, and the aggregators of all other
+/// instruments with the default selector.
+///
+/// Each instrument is exported according to the first view matching it.
+#[derive(Debug)]
+pub struct ViewSelector {
+    views: Vec<View>,
+    default: Box<dyn AggregatorSelector + Send + Sync>,
+}
+
+impl ViewSelector {
+    /// Create a new selector applying the given views.
+    pub fn new(views: Vec<View>, default: Box<dyn AggregatorSelector + Send + Sync>) -> Self {
+        ViewSelector { views, default }
+    }
+
+    pub(crate) fn into_default(self) -> Box<dyn AggregatorSelector + Send + Sync> {
+        self.default
+    }
+
+    /// The view applying to the given instrument, if any.
+    pub fn view_for(&self, descriptor: &Descriptor) -> Option<&View> {
+        self.views.iter().find(|view| view.matches(descriptor))
+    }
+}
+
+impl AggregatorSelector for ViewSelector {
+    fn aggregator_for(&self, descriptor: &Descriptor) -> Option<Arc<dyn Aggregator + Send + Sync>> {
+        match self
+            .view_for(descriptor)
+            .and_then(|view| view.aggregator_selector.as_ref())
+        {
+            Some(selector) => selector.aggregator_for(descriptor),
+            None => self.default.aggregator_for(descriptor),
+        }
+    }
+}
+
+/// Matches the name against a pattern where `*` matches any sequence of
+/// characters.
+fn matches(pattern: &str, name: &str) -> bool {
+    let mut parts = pattern.split('*');
+    let first = parts.next().unwrap_or("");
+    let mut rest = match name.strip_prefix(first) {
+        Some(rest) => rest,
+        None => return false,
+    };
+
+    let mut parts = parts.peekable();
+    while let Some(part) = parts.next() {
+        if parts.peek().is_none() {
+            // The last part must match the end of the name.
+            return rest.ends_with(part);
+        }
+        match rest.find(part) {
+            Some(idx) => rest = &rest[idx + part.len()..],
+            None => return false,
+        }
+    }
+
+    // No wildcard in the pattern.
+    rest.is_empty()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::baggage::BaggageExt;
+    use crate::labels;
+    use crate::metrics::{MeterProvider, NumberKind};
+    use crate::sdk::{
+        export::metrics::{CheckpointSet, ExportKind, Histogram, Sum},
+        metrics::{
+            aggregators::{HistogramAggregator, SumAggregator},
+            controllers,
+            selectors::simple::Selector,
+        },
+    };
+    use crate::Context;
+    use std::collections::HashMap;
+
+    #[test]
+    fn match_patterns() {
+        let data = vec![
+            ("http.server.duration", "http.server.duration", true),
+            ("http.server.duration", "http.server.duration.x", false),
+            ("http.*", "http.server.duration", true),
+            ("http.*", "rpc.server.duration", false),
+            ("*.duration", "http.server.duration", true),
+            ("*.duration", "http.server.size", false),
+            ("http.*.size", "http.server.request.size", true),
+            ("http.*.size", "http.size", false),
+            ("*", "anything", true),
+            ("a*b*c", "abc", true),
+            ("a*b*c", "acb", false),
+        ];
+
+        for (pattern, name, expected) in data {
+            assert_eq!(matches(pattern, name), expected, "{} {}", pattern, name);
+        }
+    }
+
+    #[test]
+    fn match_instruments() {
+        let descriptor = Descriptor::new(
+            "http.server.duration".to_string(),
+            "http".to_string(),
+            InstrumentKind::ValueRecorder,
+            NumberKind::F64,
+        );
+
+        let data = vec![
+            (View::new(), true),
+            (
+                View::new().with_instrument_name("http.server.duration"),
+                true,
+            ),
+            (View::new().with_instrument_name("http.*"), true),
+            (View::new().with_instrument_name("*.size"), false),
+            (View::new().with_meter_name("http"), true),
+            (View::new().with_meter_name("rpc"), false),
+            (
+                View::new().with_instrument_kind(InstrumentKind::ValueRecorder),
+                true,
+            ),
+            (
+                View::new()
+                    .with_instrument_name("http.*")
+                    .with_instrument_kind(InstrumentKind::Counter),
+                false,
+            ),
+        ];
+
+        for (view, expected) in data {
+            assert_eq!(view.matches(&descriptor), expected, "{:?}", view);
+        }
+    }
+
+    #[test]
+    fn apply_views() {
+        let mut controller =
+            controllers::pull(Box::new(Selector::Exact), Box::new(ExportKind::Cumulative))
+                .with_views(vec![
+                    View::new()
+                        .with_instrument_name("requests")
+                        .with_name("http.requests")
+                        .with_description("HTTP requests")
+                        .with_label_keys(vec![Key::new("method")]),
+                    View::new()
+                        .with_instrument_kind(InstrumentKind::ValueRecorder)
+                        .with_aggregator_selector(Selector::Histogram(vec![1.0])),
+                ])
+                .with_cache_period(std::time::Duration::from_secs(0))
+                .build();
+
+        let meter = controller.provider().meter("test");
+        let counter = meter.u64_counter("requests").init();
+        let recorder = meter.f64_value_recorder("latency").init();
+
+        counter.add(
+            1,
+            &[KeyValue::new("method", "GET"), KeyValue::new("user", "a")],
+        );
+        counter.add(
+            2,
+            &[KeyValue::new("method", "GET"), KeyValue::new("user", "b")],
+        );
+        counter.add(
+            4,
+            &[KeyValue::new("method", "POST"), KeyValue::new("user", "a")],
+        );
+        recorder.record(0.5, &[]);
+        recorder.record(1.5, &[]);
+
+        controller.collect().unwrap();
+
+        let encoder = labels::default_encoder();
+        let mut sums = HashMap::new();
+        let mut buckets = Vec::new();
+        controller
+            .try_for_each(&ExportKind::Cumulative, &mut |record| {
+                let agg = record.aggregator().unwrap();
+                if let Some(sum) = agg.as_any().downcast_ref::<SumAggregator>() {
+                    assert_eq!(record.descriptor().name(), "http.requests");
+                    assert_eq!(
+                        record.descriptor().description(),
+                        Some(&"HTTP requests".to_string())
+                    );
+                    sums.insert(
+                        record.labels().encoded(Some(encoder.as_ref())),
+                        sum.sum()?.to_u64(&NumberKind::U64),
+                    );
+                } else if let Some(histogram) = agg.as_any().downcast_ref::<HistogramAggregator>() {
+                    assert_eq!(record.descriptor().name(), "latency");
+                    buckets = histogram.histogram()?.counts().clone();
+                }
+                Ok(())
+            })
+            .unwrap();
+
+        let mut expected = HashMap::new();
+        expected.insert("method=GET".to_string(), 3);
+        expected.insert("method=POST".to_string(), 4);
+        assert_eq!(sums, expected);
+        assert_eq!(buckets, vec![1.0, 1.0]);
+    }
+
+    #[test]
+    fn baggage_labels() {
+        let mut controller =
+            controllers::pull(Box::new(Selector::Exact), Box::new(ExportKind::Cumulative))
+                .with_views(vec![View::new()
+                    .with_instrument_name("requests")
+                    .with_label_keys(vec![Key::new("method")])
+                    .with_baggage_keys(vec![Key::new("tenant")])])
+                .with_cache_period(std::time::Duration::from_secs(0))
+                .build();
+
+        let meter = controller.provider().meter("test");
+        let counter = meter.u64_counter("requests").init();
+        let get = [KeyValue::new("method", "GET")];
+        let post = [KeyValue::new("method", "POST")];
+
+        let tenant = |id: &str| {
+            Context::current_with_baggage(vec![
+                KeyValue::new("tenant", id.to_string()),
+                KeyValue::new("region", "eu"),
+            ])
+        };
+        let bound = {
+            let _guard = tenant("a").attach();
+            counter.add(1, &get);
+            counter.add(
+                8,
+                &[KeyValue::new("method", "GET"), KeyValue::new("tenant", "c")],
+            );
+            counter.bind(&post)
+        };
+        bound.add(4);
+        meter.record_batch_with_context(&tenant("b"), &get, vec![counter.measurement(2)]);
+        counter.add(16, &get);
+
+        controller.collect().unwrap();
+
+        let encoder = labels::default_encoder();
+        let mut sums = HashMap::new();
+        controller
+            .try_for_each(&ExportKind::Cumulative, &mut |record| {
+                let agg = record.aggregator().unwrap();
+                if let Some(sum) = agg.as_any().downcast_ref::<SumAggregator>() {
+                    sums.insert(
+                        record.labels().encoded(Some(encoder.as_ref())),
+                        sum.sum()?.to_u64(&NumberKind::U64),
+                    );
+                }
+                Ok(())
+            })
+            .unwrap();
+
+        let mut expected = HashMap::new();
+        expected.insert("method=GET,tenant=a".to_string(), 1);
+        expected.insert("method=GET,tenant=b".to_string(), 2);
+        expected.insert("method=GET,tenant=c".to_string(), 8);
+        expected.insert("method=POST,tenant=a".to_string(), 4);
+        expected.insert("method=GET".to_string(), 16);
+        assert_eq!(sums, expected);
+    }
+}
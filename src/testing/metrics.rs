@@ -0,0 +1,482 @@
+//! In-memory metrics export for testing instrumentation.
+//!
+//! The [`InMemoryExporter`] captures every exported record as an owned
+//! snapshot, which tests can look up by metric name and labels.
+//! [`in_memory`] wires it to a push controller collected on demand:
+//!
+//! ```
+//! use opentelemetry::{metrics::MeterProvider, testing::metrics::{in_memory, ExportedValue}, KeyValue};
+//!
+//! let metrics = in_memory().build();
+//! let counter = metrics.provider().meter("test").u64_counter("requests").init();
+//!
+//! counter.add(2, &[KeyValue::new("method", "GET")]);
+//! metrics.collect().unwrap();
+//!
+//! let record = metrics
+//!     .exporter()
+//!     .get("requests", &[KeyValue::new("method", "GET")])
+//!     .unwrap();
+//! assert_eq!(record.value(), &ExportedValue::Sum(2.0));
+//! ```
+use crate::labels::LabelSet;
+use crate::metrics::{registry::RegistryMeterProvider, Descriptor, MetricsError, Result};
+use crate::sdk::{
+    export::metrics::{
+        CheckpointSet, Count, ExponentialBuckets, ExponentialHistogram, ExportKind,
+        ExportKindSelector, Exporter, Histogram, LastValue, Max, Min, Points, Record, Sum,
+    },
+    metrics::{
+        aggregators::{
+            ArrayAggregator, DDSKetchAggregator, ExponentialHistogramAggregator,
+            HistogramAggregator, LastValueAggregator, MinMaxSumCountAggregator, SumAggregator,
+        },
+        controllers::{self, PushController},
+        selectors::simple::Selector,
+        views::View,
+    },
+    Resource,
+};
+use crate::KeyValue;
+use std::sync::{Arc, Mutex};
+use std::time::SystemTime;
+
+/// The aggregated value of an exported record, by aggregator type.
+///
+/// Numbers are converted to `f64` regardless of the instrument's number kind.
+#[derive(Clone, Debug, PartialEq)]
+pub enum ExportedValue {
+    /// The value of a `SumAggregator`.
+    Sum(f64),
+    /// The value of a `LastValueAggregator`.
+    LastValue {
+        /// The last observed value.
+        value: f64,
+        /// When the value was observed.
+        timestamp: SystemTime,
+    },
+    /// The value of a `MinMaxSumCountAggregator`, or the summary of a
+    /// `DDSKetchAggregator`.
+    MinMaxSumCount {
+        /// The smallest recorded value.
+        min: f64,
+        /// The largest recorded value.
+        max: f64,
+        /// The sum of the recorded values.
+        sum: f64,
+        /// The number of recorded values.
+        count: u64,
+    },
+    /// The value of a `HistogramAggregator`.
+    Histogram {
+        /// The sum of the recorded values.
+        sum: f64,
+        /// The number of recorded values.
+        count: u64,
+        /// The bucket boundaries.
+        boundaries: Vec<f64>,
+        /// The bucket counts, one more than the boundaries.
+        counts: Vec<f64>,
+    },
+    /// The value of an `ExponentialHistogramAggregator`.
+    ExponentialHistogram {
+        /// The sum of the recorded values.
+        sum: f64,
+        /// The number of recorded values.
+        count: u64,
+        /// The resolution of the buckets.
+        scale: i8,
+        /// The number of recorded zeros.
+        zero_count: u64,
+        /// The buckets of the positive values.
+        positive: ExponentialBuckets,
+        /// The buckets of the magnitudes of the negative values.
+        negative: ExponentialBuckets,
+    },
+    /// The values recorded by an `ArrayAggregator`, in ascending order.
+    Points(Vec<f64>),
+}
+
+/// An owned snapshot of an exported `Record`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ExportedRecord {
+    descriptor: Descriptor,
+    labels: Vec<KeyValue>,
+    resource: Resource,
+    value: ExportedValue,
+    start_time: SystemTime,
+    end_time: SystemTime,
+}
+
+impl ExportedRecord {
+    fn new(record: &Record<'_>) -> Result<Self> {
+        Ok(ExportedRecord {
+            descriptor: record.descriptor().clone(),
+            labels: owned_labels(record.labels()),
+            resource: record.resource().clone(),
+            value: exported_value(record)?,
+            start_time: *record.start_time(),
+            end_time: *record.end_time(),
+        })
+    }
+
+    /// The descriptor of the exported instrument.
+    pub fn descriptor(&self) -> &Descriptor {
+        &self.descriptor
+    }
+
+    /// The name of the exported instrument.
+    pub fn name(&self) -> &str {
+        self.descriptor.name()
+    }
+
+    /// The labels of the record, sorted by key.
+    pub fn labels(&self) -> &[KeyValue] {
+        &self.labels
+    }
+
+    /// The resource of the record.
+    pub fn resource(&self) -> &Resource {
+        &self.resource
+    }
+
+    /// The aggregated value of the record.
+    pub fn value(&self) -> &ExportedValue {
+        &self.value
+    }
+
+    /// The start of the interval the record was aggregated over.
+    pub fn start_time(&self) -> &SystemTime {
+        &self.start_time
+    }
+
+    /// The end of the interval the record was aggregated over.
+    pub fn end_time(&self) -> &SystemTime {
+        &self.end_time
+    }
+}
+
+fn owned_labels(labels: &LabelSet) -> Vec<KeyValue> {
+    labels
+        .iter()
+        .map(|(key, value)| KeyValue::new(key.clone(), value.clone()))
+        .collect()
+}
+
+fn exported_value(record: &Record<'_>) -> Result<ExportedValue> {
+    let agg = record.aggregator().ok_or(MetricsError::NoDataCollected)?;
+    let kind = record.descriptor().number_kind();
+    let any = agg.as_any();
+
+    if let Some(sum) = any.downcast_ref::<SumAggregator>() {
+        Ok(ExportedValue::Sum(sum.sum()?.to_f64(kind)))
+    } else if let Some(last_value) = any.downcast_ref::<LastValueAggregator>() {
+        let (value, timestamp) = last_value.last_value()?;
+        Ok(ExportedValue::LastValue {
+            value: value.to_f64(kind),
+            timestamp,
+        })
+    } else if let Some(mmsc) = any.downcast_ref::<MinMaxSumCountAggregator>() {
+        Ok(ExportedValue::MinMaxSumCount {
+            min: mmsc.min()?.to_f64(kind),
+            max: mmsc.max()?.to_f64(kind),
+            sum: mmsc.sum()?.to_f64(kind),
+            count: mmsc.count()?,
+        })
+    } else if let Some(sketch) = any.downcast_ref::<DDSKetchAggregator>() {
+        Ok(ExportedValue::MinMaxSumCount {
+            min: sketch.min()?.to_f64(kind),
+            max: sketch.max()?.to_f64(kind),
+            sum: sketch.sum()?.to_f64(kind),
+            count: sketch.count()?,
+        })
+    } else if let Some(histogram) = any.downcast_ref::<HistogramAggregator>() {
+        let buckets = histogram.histogram()?;
+        Ok(ExportedValue::Histogram {
+            sum: histogram.sum()?.to_f64(kind),
+            count: buckets.counts().iter().sum::<f64>() as u64,
+            boundaries: buckets.boundaries().clone(),
+            counts: buckets.counts().clone(),
+        })
+    } else if let Some(histogram) = any.downcast_ref::<ExponentialHistogramAggregator>() {
+        Ok(ExportedValue::ExponentialHistogram {
+            sum: histogram.sum()?.to_f64(kind),
+            count: histogram.count()?,
+            scale: histogram.scale()?,
+            zero_count: histogram.zero_count()?,
+            positive: histogram.positive()?,
+            negative: histogram.negative()?,
+        })
+    } else if let Some(array) = any.downcast_ref::<ArrayAggregator>() {
+        Ok(ExportedValue::Points(
+            array
+                .points()?
+                .iter()
+                .map(|point| point.to_f64(kind))
+                .collect(),
+        ))
+    } else {
+        Err(MetricsError::Other(format!(
+            "unsupported aggregator for {}",
+            record.descriptor().name()
+        )))
+    }
+}
+
+/// An exporter keeping every exported record in memory.
+///
+/// Clones of the exporter share the captured records, so a clone can be
+/// handed to a controller while the test inspects the records.
+#[derive(Clone, Debug)]
+pub struct InMemoryExporter {
+    export_kind: ExportKind,
+    records: Arc<Mutex<Vec<ExportedRecord>>>,
+}
+
+impl InMemoryExporter {
+    /// Create a new exporter exporting with the given export kind.
+    pub fn new(export_kind: ExportKind) -> Self {
+        InMemoryExporter {
+            export_kind,
+            records: Arc::new(Mutex::new(Vec::new())),
+        }
+    }
+
+    /// All captured records, in export order.
+    pub fn records(&self) -> Vec<ExportedRecord> {
+        self.records
+            .lock()
+            .map(|records| records.clone())
+            .unwrap_or_default()
+    }
+
+    /// The most recently captured record of the given metric with exactly the
+    /// given labels, in any order.
+    pub fn get(&self, name: &str, labels: &[KeyValue]) -> Option<ExportedRecord> {
+        let labels = owned_labels(&LabelSet::from_labels(labels.iter().cloned()));
+        self.records.lock().ok().and_then(|records| {
+            records
+                .iter()
+                .rev()
+                .find(|record| record.name() == name && record.labels == labels)
+                .cloned()
+        })
+    }
+
+    /// All captured records of the given metric, in export order.
+    pub fn get_all(&self, name: &str) -> Vec<ExportedRecord> {
+        self.records
+            .lock()
+            .map(|records| {
+                records
+                    .iter()
+                    .filter(|record| record.name() == name)
+                    .cloned()
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    /// Discard all captured records.
+    pub fn reset(&self) {
+        if let Ok(mut records) = self.records.lock() {
+            records.clear();
+        }
+    }
+}
+
+impl Default for InMemoryExporter {
+    fn default() -> Self {
+        InMemoryExporter::new(ExportKind::Cumulative)
+    }
+}
+
+impl ExportKindSelector for InMemoryExporter {
+    fn export_kind_for(&self, descriptor: &Descriptor) -> ExportKind {
+        self.export_kind.export_kind_for(descriptor)
+    }
+}
+
+impl Exporter for InMemoryExporter {
+    fn export(&self, checkpoint_set: &mut dyn CheckpointSet) -> Result<()> {
+        let mut exported = Vec::new();
+        checkpoint_set.try_for_each(self, &mut |record| {
+            exported.push(ExportedRecord::new(record)?);
+            Ok(())
+        })?;
+
+        self.records
+            .lock()
+            .map_err(From::from)
+            .map(|mut records| records.extend(exported))
+    }
+}
+
+/// Returns a builder for an in-memory metrics pipeline.
+pub fn in_memory() -> InMemoryMetricsBuilder {
+    InMemoryMetricsBuilder {
+        aggregator_selector: Selector::Exact,
+        export_kind: ExportKind::Cumulative,
+        resource: None,
+        views: Vec::new(),
+    }
+}
+
+/// Configuration for an `InMemoryMetrics` pipeline.
+#[derive(Debug)]
+pub struct InMemoryMetricsBuilder {
+    aggregator_selector: Selector,
+    export_kind: ExportKind,
+    resource: Option<Resource>,
+    views: Vec<View>,
+}
+
+impl InMemoryMetricsBuilder {
+    /// Configure the aggregator selector, defaults to `Selector::Exact`.
+    ///
+    /// Views can select other aggregators for individual instruments.
+    pub fn with_aggregator_selector(self, selector: Selector) -> Self {
+        InMemoryMetricsBuilder {
+            aggregator_selector: selector,
+            ..self
+        }
+    }
+
+    /// Configure the export kind, defaults to `ExportKind::Cumulative`.
+    pub fn with_export_kind(self, export_kind: ExportKind) -> Self {
+        InMemoryMetricsBuilder {
+            export_kind,
+            ..self
+        }
+    }
+
+    /// Configure the resource of all meters.
+    pub fn with_resource(self, resource: Resource) -> Self {
+        InMemoryMetricsBuilder {
+            resource: Some(resource),
+            ..self
+        }
+    }
+
+    /// Configure the views applied to the instruments.
+    pub fn with_views(self, views: Vec<View>) -> Self {
+        InMemoryMetricsBuilder { views, ..self }
+    }
+
+    /// Build the pipeline.
+    pub fn build(self) -> InMemoryMetrics {
+        let exporter = InMemoryExporter::new(self.export_kind.clone());
+        let mut builder = controllers::push(
+            self.aggregator_selector,
+            self.export_kind,
+            exporter.clone(),
+            // Nothing is exported periodically, only on `collect`.
+            |_worker| (),
+            |_period| futures::stream::pending::<()>(),
+        )
+        .with_views(self.views);
+        if let Some(resource) = self.resource {
+            builder = builder.with_resource(resource);
+        }
+
+        InMemoryMetrics {
+            controller: builder.build(),
+            exporter,
+        }
+    }
+}
+
+/// A metrics pipeline exporting to an `InMemoryExporter` whenever it is
+/// collected.
+#[derive(Debug)]
+pub struct InMemoryMetrics {
+    controller: PushController,
+    exporter: InMemoryExporter,
+}
+
+impl InMemoryMetrics {
+    /// The meter provider of the instruments under test.
+    pub fn provider(&self) -> RegistryMeterProvider {
+        self.controller.provider()
+    }
+
+    /// Synchronously collect all instruments and export them to the
+    /// exporter.
+    pub fn collect(&self) -> Result<()> {
+        self.controller.force_flush()
+    }
+
+    /// The exporter holding the collected records.
+    pub fn exporter(&self) -> &InMemoryExporter {
+        &self.exporter
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::metrics::MeterProvider;
+
+    #[test]
+    fn capture_records() {
+        let metrics = in_memory()
+            .with_aggregator_selector(Selector::Histogram(vec![1.0]))
+            .with_export_kind(ExportKind::Delta)
+            .with_resource(Resource::new(vec![KeyValue::new("service", "test")]))
+            .build();
+        let meter = metrics.provider().meter("test");
+        let counter = meter.u64_counter("requests").init();
+        let recorder = meter.f64_value_recorder("latency").init();
+
+        counter.add(1, &[KeyValue::new("b", "2"), KeyValue::new("a", "1")]);
+        counter.add(2, &[KeyValue::new("a", "1"), KeyValue::new("b", "2")]);
+        recorder.record(0.5, &[]);
+        recorder.record(1.5, &[]);
+        metrics.collect().unwrap();
+
+        let requests = metrics
+            .exporter()
+            .get(
+                "requests",
+                &[KeyValue::new("a", "1"), KeyValue::new("b", "2")],
+            )
+            .unwrap();
+        assert_eq!(requests.value(), &ExportedValue::Sum(3.0));
+        assert_eq!(
+            requests.labels(),
+            &[KeyValue::new("a", "1"), KeyValue::new("b", "2")][..]
+        );
+        assert_eq!(
+            requests.resource(),
+            &Resource::new(vec![KeyValue::new("service", "test")])
+        );
+        assert!(metrics.exporter().get("requests", &[]).is_none());
+
+        assert_eq!(
+            metrics.exporter().get("latency", &[]).unwrap().value(),
+            &ExportedValue::Histogram {
+                sum: 2.0,
+                count: 2,
+                boundaries: vec![1.0],
+                counts: vec![1.0, 1.0],
+            }
+        );
+
+        counter.add(4, &[KeyValue::new("a", "1"), KeyValue::new("b", "2")]);
+        metrics.collect().unwrap();
+
+        let values = metrics
+            .exporter()
+            .get_all("requests")
+            .iter()
+            .map(|record| record.value().clone())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            values,
+            vec![ExportedValue::Sum(3.0), ExportedValue::Sum(4.0)]
+        );
+
+        metrics.exporter().reset();
+        assert!(metrics.exporter().records().is_empty());
+    }
+}
@@ -1 +1,6 @@
+//! Utilities for testing instrumentation.
+#[cfg(feature = "metrics")]
+#[cfg_attr(docsrs, doc(cfg(feature = "metrics")))]
+pub mod metrics;
+#[cfg(test)]
 pub mod trace;